@@ -5,21 +5,20 @@
  * server settings, download paths, quality preferences, and performance options.
  *
  * Uses `strum` macros to derive enums that map environment strings
- * to internal quality and codec preference enums used by yt-dlp.
+ * to the quality and codec preferences we turn into yt-dlp format selectors.
  */
 
 use dotenvy::dotenv;
 use std::env;
 use strum_macros::{EnumIter, EnumString};
-
-use yt_dlp::model::{AudioCodecPreference, AudioQuality, VideoCodecPreference, VideoQuality};
+use uuid::Uuid;
 
 /*
  * Environment-parseable enums with FromStr implementations.
  * These enums represent user-friendly strings in environment variables,
- * which then get converted to yt_dlp enums.
+ * which handlers::ytdlp turns into a yt-dlp format selector.
  */
-#[derive(Debug, EnumString, EnumIter)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString, EnumIter)]
 #[strum(serialize_all = "PascalCase")]
 pub enum VideoQualityEnv {
     Best,
@@ -29,7 +28,7 @@ pub enum VideoQualityEnv {
     Worst,
 }
 
-#[derive(Debug, EnumString, EnumIter)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString, EnumIter)]
 #[strum(serialize_all = "lowercase")]
 pub enum VideoCodecPreferenceEnv {
     VP9,
@@ -38,7 +37,7 @@ pub enum VideoCodecPreferenceEnv {
     Any,
 }
 
-#[derive(Debug, EnumString, EnumIter)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString, EnumIter)]
 #[strum(serialize_all = "PascalCase")]
 pub enum AudioQualityEnv {
     Best,
@@ -48,7 +47,7 @@ pub enum AudioQualityEnv {
     Worst,
 }
 
-#[derive(Debug, EnumString, EnumIter)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString, EnumIter)]
 #[strum(serialize_all = "lowercase")]
 pub enum AudioCodecPreferenceEnv {
     Opus,
@@ -57,56 +56,6 @@ pub enum AudioCodecPreferenceEnv {
     Any,
 }
 
-/*
- * Implement conversion from environment enums to yt_dlp enums.
- * This allows seamless mapping after parsing environment variables.
- */
-impl From<VideoQualityEnv> for VideoQuality {
-    fn from(env: VideoQualityEnv) -> Self {
-        match env {
-            VideoQualityEnv::Best => VideoQuality::Best,
-            VideoQualityEnv::High => VideoQuality::High,
-            VideoQualityEnv::Medium => VideoQuality::Medium,
-            VideoQualityEnv::Low => VideoQuality::Low,
-            VideoQualityEnv::Worst => VideoQuality::Worst,
-        }
-    }
-}
-
-impl From<VideoCodecPreferenceEnv> for VideoCodecPreference {
-    fn from(env: VideoCodecPreferenceEnv) -> Self {
-        match env {
-            VideoCodecPreferenceEnv::VP9 => VideoCodecPreference::VP9,
-            VideoCodecPreferenceEnv::AVC1 => VideoCodecPreference::AVC1,
-            VideoCodecPreferenceEnv::AV1 => VideoCodecPreference::AV1,
-            VideoCodecPreferenceEnv::Any => VideoCodecPreference::Any,
-        }
-    }
-}
-
-impl From<AudioQualityEnv> for AudioQuality {
-    fn from(env: AudioQualityEnv) -> Self {
-        match env {
-            AudioQualityEnv::Best => AudioQuality::Best,
-            AudioQualityEnv::High => AudioQuality::High,
-            AudioQualityEnv::Medium => AudioQuality::Medium,
-            AudioQualityEnv::Low => AudioQuality::Low,
-            AudioQualityEnv::Worst => AudioQuality::Worst,
-        }
-    }
-}
-
-impl From<AudioCodecPreferenceEnv> for AudioCodecPreference {
-    fn from(env: AudioCodecPreferenceEnv) -> Self {
-        match env {
-            AudioCodecPreferenceEnv::Opus => AudioCodecPreference::Opus,
-            AudioCodecPreferenceEnv::Aac => AudioCodecPreference::AAC,
-            AudioCodecPreferenceEnv::MP3 => AudioCodecPreference::MP3,
-            AudioCodecPreferenceEnv::Any => AudioCodecPreference::Any,
-        }
-    }
-}
-
 /*
  * Main configuration struct holding all configurable parameters.
  * This struct is used throughout the application to get settings.
@@ -123,18 +72,51 @@ pub struct Config {
     pub download_dir: String,
     pub cleanup_after_minutes: u64,
 
+    // Disk budget (in bytes, 0 disables): cap on the cache and free space to keep
+    pub max_cache_bytes: u64,
+    pub min_free_bytes: u64,
+    // Per-video limits (0 disables each): longest video and largest file we'll fetch
+    pub max_duration_seconds: u64,
+    pub max_filesize_bytes: u64,
+
     // Video and audio quality and codec preferences
-    pub video_quality: VideoQuality,
-    pub video_codec: VideoCodecPreference,
-    pub audio_quality: AudioQuality,
-    pub audio_codec: AudioCodecPreference,
+    pub video_quality: VideoQualityEnv,
+    pub video_codec: VideoCodecPreferenceEnv,
+    pub audio_quality: AudioQualityEnv,
+    pub audio_codec: AudioCodecPreferenceEnv,
 
     // Performance tuning parameters
     pub max_concurrent_downloads: usize,
     pub timeout_seconds: u64,
+    pub max_playlist_items: usize,
+    pub resolve_short_links: bool,
+
+    // Per-client limits on POST /download (0 disables each)
+    pub rate_limit_per_minute: u32,
+    pub rate_limit_burst: u32,
+    pub max_jobs_per_client: usize,
+    // Take client addresses from X-Forwarded-For (only behind a trusted proxy)
+    pub trust_proxy_headers: bool,
+
+    // Which URLs may be downloaded: comma-separated domain lists (empty allows
+    // all) and whether hosts on private or local networks are fair game
+    pub allowed_domains: String,
+    pub denied_domains: String,
+    pub allow_private_addresses: bool,
 
     // Web interface settings
     pub enable_web_ui: bool,
+
+    // Access control: API keys ("key" or "key:scope+scope"), a key file and the
+    // web UI's shared secret; all empty leaves the server open
+    pub api_keys: String,
+    pub api_keys_file: String,
+    pub web_ui_secret: String,
+
+    // File URLs: signed with FILE_URL_SECRET and valid for a while, unless public
+    pub public_file_urls: bool,
+    pub file_url_secret: String,
+    pub file_url_ttl_seconds: u64,
 }
 
 /*
@@ -150,13 +132,32 @@ impl Default for Config {
             use_https: false,
             download_dir: "./downloads".to_string(),
             cleanup_after_minutes: 10,
-            video_quality: VideoQuality::Best,
-            video_codec: VideoCodecPreference::VP9,
-            audio_quality: AudioQuality::Best,
-            audio_codec: AudioCodecPreference::Opus,
+            max_cache_bytes: 0,
+            min_free_bytes: 0,
+            max_duration_seconds: 0,
+            max_filesize_bytes: 0,
+            video_quality: VideoQualityEnv::Best,
+            video_codec: VideoCodecPreferenceEnv::VP9,
+            audio_quality: AudioQualityEnv::Best,
+            audio_codec: AudioCodecPreferenceEnv::Opus,
             max_concurrent_downloads: 5,
             timeout_seconds: 300, // 5 minutes
+            max_playlist_items: 500,
+            resolve_short_links: true,
+            rate_limit_per_minute: 0,
+            rate_limit_burst: 5,
+            max_jobs_per_client: 0,
+            trust_proxy_headers: false,
+            allowed_domains: String::new(),
+            denied_domains: String::new(),
+            allow_private_addresses: false,
             enable_web_ui: true,
+            api_keys: String::new(),
+            api_keys_file: String::new(),
+            web_ui_secret: String::new(),
+            public_file_urls: false,
+            file_url_secret: String::new(),
+            file_url_ttl_seconds: 24 * 60 * 60, // 1 day
         }
     }
 }
@@ -180,16 +181,46 @@ impl Config {
                 "CLEANUP_AFTER_MINUTES",
                 default.cleanup_after_minutes,
             ),
-            video_quality: parse_env_enum("VIDEO_QUALITY", VideoQualityEnv::Best).into(),
-            video_codec: parse_env_codec_enum("VIDEO_CODEC", VideoCodecPreferenceEnv::VP9).into(),
-            audio_quality: parse_env_enum("AUDIO_QUALITY", AudioQualityEnv::Best).into(),
-            audio_codec: parse_env_codec_enum("AUDIO_CODEC", AudioCodecPreferenceEnv::Opus).into(),
+            max_cache_bytes: parse_env("MAX_CACHE_BYTES", default.max_cache_bytes),
+            min_free_bytes: parse_env("MIN_FREE_BYTES", default.min_free_bytes),
+            max_duration_seconds: parse_env("MAX_DURATION_SECONDS", default.max_duration_seconds),
+            max_filesize_bytes: parse_env("MAX_FILESIZE_BYTES", default.max_filesize_bytes),
+            video_quality: parse_env_enum("VIDEO_QUALITY", default.video_quality),
+            video_codec: parse_env_codec_enum("VIDEO_CODEC", default.video_codec),
+            audio_quality: parse_env_enum("AUDIO_QUALITY", default.audio_quality),
+            audio_codec: parse_env_codec_enum("AUDIO_CODEC", default.audio_codec),
             max_concurrent_downloads: parse_env(
                 "MAX_CONCURRENT_DOWNLOADS",
                 default.max_concurrent_downloads,
             ),
             timeout_seconds: parse_env("TIMEOUT_SECONDS", default.timeout_seconds),
+            max_playlist_items: parse_env("MAX_PLAYLIST_ITEMS", default.max_playlist_items),
+            resolve_short_links: parse_env("RESOLVE_SHORT_LINKS", default.resolve_short_links),
+            rate_limit_per_minute: parse_env(
+                "RATE_LIMIT_PER_MINUTE",
+                default.rate_limit_per_minute,
+            ),
+            rate_limit_burst: parse_env("RATE_LIMIT_BURST", default.rate_limit_burst),
+            max_jobs_per_client: parse_env("MAX_JOBS_PER_CLIENT", default.max_jobs_per_client),
+            trust_proxy_headers: parse_env("TRUST_PROXY_HEADERS", default.trust_proxy_headers),
+            allowed_domains: parse_env("ALLOWED_DOMAINS", default.allowed_domains),
+            denied_domains: parse_env("DENIED_DOMAINS", default.denied_domains),
+            allow_private_addresses: parse_env(
+                "ALLOW_PRIVATE_ADDRESSES",
+                default.allow_private_addresses,
+            ),
             enable_web_ui: parse_env("ENABLE_WEB_UI", default.enable_web_ui),
+            api_keys: parse_env("API_KEYS", default.api_keys),
+            api_keys_file: parse_env("API_KEYS_FILE", default.api_keys_file),
+            web_ui_secret: parse_env("WEB_UI_SECRET", default.web_ui_secret),
+            public_file_urls: parse_env("PUBLIC_FILE_URLS", default.public_file_urls),
+            // Without a configured secret, sign with a random one for this run only
+            file_url_secret: Some(parse_env("FILE_URL_SECRET", default.file_url_secret))
+                .filter(|secret| !secret.is_empty())
+                .unwrap_or_else(|| {
+                    format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+                }),
+            file_url_ttl_seconds: parse_env("FILE_URL_TTL_SECONDS", default.file_url_ttl_seconds),
         }
     }
 
@@ -226,7 +257,7 @@ where
 {
     env::var(key)
         .ok()
-        .and_then(|v| v.parse().ok())
+        .and_then(|v| parse_quality(&v))
         .unwrap_or(default)
 }
 
@@ -240,10 +271,20 @@ where
 {
     env::var(key)
         .ok()
-        .and_then(|v| v.to_lowercase().parse().ok())
+        .and_then(|v| parse_codec(&v))
         .unwrap_or(default)
 }
 
+/// Parses a quality name exactly like VIDEO_QUALITY/AUDIO_QUALITY (PascalCase, e.g. "Best").
+pub fn parse_quality<T: std::str::FromStr>(value: &str) -> Option<T> {
+    value.parse().ok()
+}
+
+/// Parses a codec name exactly like VIDEO_CODEC/AUDIO_CODEC (any case, e.g. "VP9" or "vp9").
+pub fn parse_codec<T: std::str::FromStr>(value: &str) -> Option<T> {
+    value.to_lowercase().parse().ok()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -258,9 +299,25 @@ mod tests {
         assert_eq!(config.address(), "0.0.0.0:3000");
         assert_eq!(config.download_dir, "./downloads");
         assert_eq!(config.cleanup_after_minutes, 10);
+        assert_eq!(config.max_cache_bytes, 0);
+        assert_eq!(config.min_free_bytes, 0);
+        assert_eq!(config.max_duration_seconds, 0);
+        assert_eq!(config.max_filesize_bytes, 0);
         assert_eq!(config.max_concurrent_downloads, 5);
         assert_eq!(config.timeout_seconds, 300);
+        assert_eq!(config.max_playlist_items, 500);
+        assert!(config.resolve_short_links);
+        assert_eq!(config.rate_limit_per_minute, 0);
+        assert_eq!(config.rate_limit_burst, 5);
+        assert_eq!(config.max_jobs_per_client, 0);
+        assert!(!config.trust_proxy_headers);
+        assert!(config.allowed_domains.is_empty());
+        assert!(!config.allow_private_addresses);
         assert!(config.enable_web_ui);
+        assert!(config.api_keys.is_empty());
+        assert!(config.web_ui_secret.is_empty());
+        assert!(!config.public_file_urls);
+        assert_eq!(config.file_url_ttl_seconds, 86_400);
         assert!(!config.use_https);
     }
 
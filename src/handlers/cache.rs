@@ -0,0 +1,338 @@
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tracing::{info, warn};
+
+use crate::handlers::downloader::parse_cached_file_name;
+use crate::handlers::meta::read_meta;
+use crate::handlers::options::is_media_extension;
+use crate::utils::cleanup::{PIN_MARKER, is_pinned, is_video_directory, last_access_time};
+use crate::utils::video_id::cache_key_for;
+
+/*
+ * Read-only view of DOWNLOAD_DIR, plus manual eviction.
+ *
+ * A directory counts as a cached video exactly when the cleanup scheduler
+ * would treat it as one (is_video_directory), and its variants are the
+ * published media files as the downloader names them: "{title}.{ext}" or
+ * "{title}.{variant}.{ext}". Titles come from meta.json when there is one.
+ * Pinning drops a marker file in the directory that cleanup leaves alone.
+ *
+ * Directories are named by cache key, "{platform}-{id}"; entries from the old
+ * flat "{id}" layout are moved over at startup by migrate_flat_entries when
+ * their meta.json names the platform, and otherwise by migrate_flat_entry the
+ * first time a download's metadata does.
+ */
+
+/// One cached video, as listed by GET /cache.
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheEntry {
+    pub video_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Everything in the directory: media, thumbnail, subtitles and leftovers.
+    pub size_bytes: u64,
+    /// Unix timestamp, in seconds.
+    pub last_accessed: u64,
+    /// Pinned videos never expire and are never evicted to stay under quota.
+    pub pinned: bool,
+    pub variants: Vec<CachedVariant>,
+}
+
+/// One published file of a cached video.
+#[derive(Debug, Clone, Serialize)]
+pub struct CachedVariant {
+    /// "" for the default mp4.
+    pub variant: String,
+    pub file_name: String,
+    pub size_bytes: u64,
+}
+
+/// Lists every cached video under `download_dir`, most recently used first.
+pub fn list_cache(download_dir: &Path) -> Vec<CacheEntry> {
+    let Ok(entries) = std::fs::read_dir(download_dir) else {
+        return Vec::new();
+    };
+    let mut cached: Vec<_> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir() && is_video_directory(path))
+        .filter_map(|path| cache_entry(&path))
+        .collect();
+    cached.sort_by(|a, b| {
+        b.last_accessed
+            .cmp(&a.last_accessed)
+            .then_with(|| a.video_id.cmp(&b.video_id))
+    });
+    cached
+}
+
+/// Reads a single cached video; None when `dir` isn't one.
+pub fn cache_entry(dir: &Path) -> Option<CacheEntry> {
+    let video_id = dir.file_name()?.to_str()?.to_string();
+    let mut size_bytes = 0;
+    let mut variants = Vec::new();
+    for entry in std::fs::read_dir(dir).ok()?.flatten() {
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if !metadata.is_file() {
+            continue;
+        }
+        size_bytes += metadata.len();
+
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        let variant = parse_cached_file_name(&file_name)
+            .filter(|(_, _, ext)| is_media_extension(ext))
+            .map(|(_, variant, _)| variant.to_string());
+        if let Some(variant) = variant {
+            variants.push(CachedVariant {
+                variant,
+                file_name,
+                size_bytes: metadata.len(),
+            });
+        }
+    }
+    variants.sort_by(|a, b| a.variant.cmp(&b.variant));
+
+    let last_accessed = last_access_time(dir)
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+
+    Some(CacheEntry {
+        title: read_meta(dir).map(|meta| meta.video.title),
+        video_id,
+        size_bytes,
+        last_accessed,
+        pinned: is_pinned(dir),
+        variants,
+    })
+}
+
+/// Resolves a video ID to its cache directory, refusing anything that could
+/// point outside DOWNLOAD_DIR.
+pub fn cache_dir_for(download_dir: &Path, video_id: &str) -> Option<PathBuf> {
+    let safe =
+        !video_id.is_empty() && !video_id.starts_with('.') && !video_id.contains(['/', '\\']);
+    safe.then(|| download_dir.join(video_id))
+}
+
+/// Removes a cached video and everything in its directory.
+pub fn evict(dir: &Path) -> std::io::Result<()> {
+    std::fs::remove_dir_all(dir)?;
+    info!(path = %dir.display(), "Evicted cached video");
+    Ok(())
+}
+
+/// Pins or unpins a cached video.
+pub fn set_pinned(dir: &Path, pinned: bool) -> std::io::Result<()> {
+    let marker = dir.join(PIN_MARKER);
+    if pinned {
+        std::fs::write(&marker, b"")?;
+    } else {
+        match std::fs::remove_file(&marker) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+    info!(path = %dir.display(), pinned, "Updated pin");
+    Ok(())
+}
+
+/*
+ * Moves video directories from the old flat "{id}" layout to "{platform}-{id}".
+ * Only directories whose meta.json names the extractor can be attributed to a
+ * platform; the rest keep serving their file URLs until a download of the same
+ * ID moves them through migrate_flat_entry.
+ * Returns the number of directories moved.
+ */
+pub fn migrate_flat_entries(download_dir: &Path) -> usize {
+    let Ok(entries) = std::fs::read_dir(download_dir) else {
+        return 0;
+    };
+
+    let mut migrated = 0;
+    for dir in entries.flatten().map(|entry| entry.path()) {
+        if !dir.is_dir() || !is_video_directory(&dir) {
+            continue;
+        }
+        let Some(meta) = read_meta(&dir) else {
+            continue;
+        };
+        let Some(extractor) = meta.video.extractor.as_deref() else {
+            continue;
+        };
+        // Old entries are named after the bare ID; anything else is already keyed
+        if dir.file_name().and_then(|name| name.to_str()) != Some(meta.video.id.as_str()) {
+            continue;
+        }
+
+        let target = download_dir.join(cache_key_for(Some(extractor), &meta.video.id));
+        if move_entry(&dir, &target) {
+            migrated += 1;
+        }
+    }
+
+    if migrated > 0 {
+        info!(
+            migrated,
+            "Moved cached videos to platform-namespaced directories"
+        );
+    }
+    migrated
+}
+
+/*
+ * Moves the old flat "{video_id}" directory to `cache_key`, once a download's
+ * metadata has told us the platform. Entries cached before meta.json existed
+ * can only be attributed this way. Returns true when the entry was moved.
+ */
+pub fn migrate_flat_entry(download_dir: &Path, video_id: &str, cache_key: &str) -> bool {
+    let Some(dir) = cache_dir_for(download_dir, video_id) else {
+        return false;
+    };
+    if video_id == cache_key || !dir.is_dir() || !is_video_directory(&dir) {
+        return false;
+    }
+    // A meta.json naming another platform means the ID only happens to match
+    let other_platform = read_meta(&dir)
+        .and_then(|meta| meta.video.extractor)
+        .is_some_and(|extractor| cache_key_for(Some(&extractor), video_id) != cache_key);
+    if other_platform {
+        return false;
+    }
+
+    let moved = move_entry(&dir, &download_dir.join(cache_key));
+    if moved {
+        info!(path = %dir.display(), key = cache_key, "Moved cached video to its namespaced key");
+    }
+    moved
+}
+
+// Renames an old entry into place unless something already lives there
+fn move_entry(dir: &Path, target: &Path) -> bool {
+    if target.exists() {
+        warn!(
+            path = %dir.display(),
+            target = %target.display(),
+            "Already cached under its namespaced key, leaving the old entry to expire"
+        );
+        return false;
+    }
+    match std::fs::rename(dir, target) {
+        Ok(()) => true,
+        Err(e) => {
+            warn!(path = %dir.display(), error = %e, "Failed to migrate cache entry");
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_support::TestDirectory;
+
+    #[test]
+    fn lists_what_cleanup_recognizes_with_variants() {
+        let download_dir = TestDirectory::new();
+        let video = download_dir.join("dQw4w9WgXcQ");
+        let unrelated = download_dir.join("notes");
+        std::fs::create_dir_all(&video).unwrap();
+        std::fs::create_dir_all(&unrelated).unwrap();
+        std::fs::write(video.join("Title.mp4"), vec![0_u8; 2048]).unwrap();
+        std::fs::write(video.join("Title.audio.mp3"), vec![0_u8; 1024]).unwrap();
+        std::fs::write(video.join("Title.en.srt"), b"1").unwrap();
+        std::fs::write(video.join(".Title.abcd1234.tmp.mp4"), b"partial").unwrap();
+        std::fs::write(
+            video.join("meta.json"),
+            r#"{"id": "dQw4w9WgXcQ", "url": "https://youtu.be/dQw4w9WgXcQ", "title": "Never Gonna Give You Up"}"#,
+        )
+        .unwrap();
+        std::fs::write(unrelated.join("readme.txt"), b"not a video").unwrap();
+
+        let listing = list_cache(&download_dir);
+
+        assert_eq!(listing.len(), 1);
+        let entry = &listing[0];
+        assert_eq!(entry.video_id, "dQw4w9WgXcQ");
+        assert_eq!(entry.title.as_deref(), Some("Never Gonna Give You Up"));
+        let variants: Vec<_> = entry.variants.iter().map(|v| v.variant.as_str()).collect();
+        assert_eq!(variants, ["", "audio"]);
+        assert!(entry.size_bytes > 3072);
+    }
+
+    #[test]
+    fn migrates_flat_entries_that_name_their_extractor() {
+        let download_dir = TestDirectory::new();
+        let flat = |video_id: &str, meta: Option<&str>| {
+            let dir = download_dir.join(video_id);
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join("Title.mp4"), vec![0_u8; 1024]).unwrap();
+            if let Some(meta) = meta {
+                std::fs::write(dir.join("meta.json"), meta).unwrap();
+            }
+        };
+        flat(
+            "1234567890",
+            Some(
+                r#"{"id": "1234567890", "url": "https://vimeo.com/1234567890", "title": "Vimeo", "extractor": "Vimeo"}"#,
+            ),
+        );
+        flat(
+            "7123456789012345678",
+            Some(
+                r#"{"id": "7123456789012345678", "url": "https://vm.tiktok.com/x", "title": "Unknown"}"#,
+            ),
+        );
+        flat("dQw4w9WgXcQ", None);
+
+        let migrated = migrate_flat_entries(&download_dir);
+        let again = migrate_flat_entries(&download_dir);
+        let mut names: Vec<_> = std::fs::read_dir(&download_dir)
+            .unwrap()
+            .flatten()
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+
+        assert_eq!(migrated, 1);
+        assert_eq!(again, 0);
+        assert_eq!(
+            names,
+            ["7123456789012345678", "dQw4w9WgXcQ", "vimeo-1234567890"]
+        );
+    }
+
+    #[test]
+    fn download_metadata_migrates_entries_without_meta() {
+        let download_dir = TestDirectory::new();
+        let flat = download_dir.join("dQw4w9WgXcQ");
+        std::fs::create_dir_all(&flat).unwrap();
+        std::fs::write(flat.join("Title.mp4"), vec![0_u8; 1024]).unwrap();
+
+        let wrong_id = migrate_flat_entry(&download_dir, "other", "youtube-other");
+        let moved = migrate_flat_entry(&download_dir, "dQw4w9WgXcQ", "youtube-dQw4w9WgXcQ");
+        let landed = download_dir.join("youtube-dQw4w9WgXcQ/Title.mp4").is_file();
+        let escaped = migrate_flat_entry(&download_dir, "..", "youtube-x");
+
+        assert!(!wrong_id);
+        assert!(moved);
+        assert!(landed);
+        assert!(!escaped);
+    }
+
+    #[test]
+    fn cache_dirs_stay_inside_the_download_dir() {
+        let download_dir = Path::new("downloads");
+        assert_eq!(
+            cache_dir_for(download_dir, "dQw4w9WgXcQ"),
+            Some(PathBuf::from("downloads/dQw4w9WgXcQ"))
+        );
+        assert!(cache_dir_for(download_dir, "..").is_none());
+        assert!(cache_dir_for(download_dir, "a/../../etc").is_none());
+        assert!(cache_dir_for(download_dir, "").is_none());
+    }
+}
@@ -1,55 +1,152 @@
 use crate::config::Config;
+use crate::handlers::cache::migrate_flat_entry;
+use crate::handlers::inflight::InFlightGuard;
+use crate::handlers::info::VideoInfo;
+use crate::handlers::jobs::{JobHandle, JobStatus};
+use crate::handlers::limits::{check_download_size, check_video};
+use crate::handlers::meta::{FileMeta, VideoDetails, VideoMeta, read_meta, unix_now, write_meta};
+use crate::handlers::options::{DownloadOptions, Subtitles};
+use crate::handlers::ytdlp::{
+    CANCELLED_ERROR, DownloadCommand, SubtitleCommand, fetch_metadata, generate_thumbnail,
+    run_download, run_subtitle_download,
+};
+use crate::state::AppState;
+use crate::utils::canonical_url::canonicalize_url;
+use crate::utils::cleanup::enforce_disk_budget_blocking;
+use crate::utils::url_policy::check_url;
 use crate::utils::video_id::extract_cache_id;
+use serde_json::Value;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::{OnceCell, OwnedSemaphorePermit};
+use tokio_util::sync::CancellationToken;
 use yt_dlp::Downloader;
 extern crate sanitize_filename;
-use std::cell::RefCell;
 use std::time::{Duration, Instant};
 use tracing::{debug, error, info, warn};
 
 const MIN_VALID_VIDEO_SIZE_BYTES: u64 = 1024;
+/// Every video directory keeps one poster image, shared by all its variants.
+pub const THUMBNAIL_FILE_NAME: &str = "thumbnail.jpg";
 // Browser-like UA so sites that challenge bare yt-dlp (e.g. TikTok) still work.
-// Forwarded to extractors by our local yt-dlp fork.
-const BROWSER_USER_AGENT: &str = "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) \
+// Forwarded to extractors by our local yt-dlp fork, and passed to the CLI for downloads.
+pub(crate) const BROWSER_USER_AGENT: &str = "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) \
      AppleWebKit/537.36 (KHTML, like Gecko) Chrome/136.0.0.0 Safari/537.36";
 
 /*
  * Initializes the multi-platform yt-dlp downloader.
  * Reuses existing yt-dlp and ffmpeg binaries, installing only missing binaries.
- * Returns a configured fetcher ready to download videos.
+ * Returns a configured fetcher ready to fetch video metadata.
  */
-pub fn init_yt_dlp() -> Result<Downloader, Box<dyn std::error::Error>> {
-    let app_config = Config::from_env();
+async fn init_yt_dlp(
+    app_config: &Config,
+) -> Result<Downloader, Box<dyn std::error::Error + Send + Sync>> {
     let libraries_dir = PathBuf::from("libs"); // Directory for external libs
     let output_dir = PathBuf::from(&app_config.download_dir); // Directory for downloads
 
-    // Create a Tokio runtime to initialize the async downloader in a blocking context
-    let rt = tokio::runtime::Runtime::new()?;
+    let fetcher = Downloader::with_new_binaries(libraries_dir, output_dir)
+        .await?
+        .with_timeout(Duration::from_secs(app_config.timeout_seconds))
+        .with_max_concurrent_downloads(app_config.max_concurrent_downloads)
+        .with_user_agent(BROWSER_USER_AGENT)
+        .build()
+        .await?;
+    Ok(fetcher)
+}
+
+/// Initializes yt-dlp, wiping and reinstalling libs/ once if the binaries are corrupted.
+async fn init_yt_dlp_with_repair(
+    app_config: &Config,
+) -> Result<Downloader, Box<dyn std::error::Error + Send + Sync>> {
+    match init_yt_dlp(app_config).await {
+        Ok(f) => Ok(f),
+        Err(e)
+            if e.to_string().contains("invalid Zip archive")
+                || e.to_string().contains("Could not find EOCD") =>
+        {
+            warn!(error = %e, "Corrupted yt-dlp libraries detected — reinstalling");
+
+            // Remove corrupted libs directory
+            let libs_dir = PathBuf::from("libs");
+            if libs_dir.exists() {
+                std::fs::remove_dir_all(&libs_dir).unwrap_or_else(|e| {
+                    error!(error = %e, "Failed to remove corrupted libs directory");
+                });
+                info!("Removed corrupted libs, retrying initialization");
+            }
+
+            // Retry initialization
+            init_yt_dlp(app_config).await.inspect_err(|retry_error| {
+                error!(error = %retry_error, "yt-dlp init failed after cleanup");
+            })
+        }
+        Err(e) => {
+            error!(error = %e, "yt-dlp init failed");
+            Err(e)
+        }
+    }
+}
 
-    let fetcher = rt.block_on(async {
-        Downloader::with_new_binaries(libraries_dir, output_dir)
-            .await?
-            .with_timeout(Duration::from_secs(app_config.timeout_seconds))
-            .with_max_concurrent_downloads(app_config.max_concurrent_downloads)
-            .with_user_agent(BROWSER_USER_AGENT)
-            .build()
+/*
+ * The one yt-dlp Downloader shared by every request.
+ * Built on first use and kept for the life of the process; a failed init is
+ * retried by the next caller instead of being cached.
+ */
+#[derive(Clone, Default)]
+pub struct SharedDownloader {
+    cell: Arc<OnceCell<Downloader>>,
+}
+
+impl SharedDownloader {
+    pub async fn get(
+        &self,
+        config: &Config,
+    ) -> Result<&Downloader, Box<dyn std::error::Error + Send + Sync>> {
+        self.cell
+            .get_or_try_init(|| init_yt_dlp_with_repair(config))
             .await
-    })?;
-    Ok(fetcher)
+    }
 }
 
-/// True when a file is a finished cache entry (not an in-progress temp download).
-fn is_final_cached_mp4(path: &Path) -> bool {
-    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
-        return false;
-    };
-    // In-progress downloads use hidden names like ".Title.abcd1234.tmp.mp4"
-    path.extension().is_some_and(|ext| ext == "mp4") && !name.starts_with('.')
+/// File name of a published download: "{title}.{ext}", or "{title}.{variant}.{ext}".
+fn cached_file_name(title: &str, variant: &str, ext: &str) -> String {
+    if variant.is_empty() {
+        format!("{title}.{ext}")
+    } else {
+        format!("{title}.{variant}.{ext}")
+    }
 }
 
-/// Looks for a reusable mp4 under downloads/{video_id}/.
+/// Splits a published file name into (title, variant, extension); "" is the default variant.
+/// None for hidden in-progress downloads like ".Title.abcd1234.tmp.mp4".
+pub(crate) fn parse_cached_file_name(name: &str) -> Option<(&str, &str, &str)> {
+    if name.starts_with('.') {
+        return None;
+    }
+    let (stem, ext) = name.rsplit_once('.')?;
+    // Cleaned titles never contain dots, so anything after the first one is the variant
+    Some(match stem.split_once('.') {
+        None => (stem, "", ext),
+        Some((title, variant)) => (title, variant, ext),
+    })
+}
+
+/// True when a file is a finished cache entry for this variant (not an in-progress temp download).
+fn is_cached_variant(path: &Path, variant: &str, ext: &str) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .and_then(parse_cached_file_name)
+        .is_some_and(|(_, found_variant, found_ext)| found_variant == variant && found_ext == ext)
+}
+
+/// Looks for a reusable download of this variant under downloads/{video_id}/.
 /// Removes tiny/poisoned final files so the next download can replace them.
-fn find_cached_mp4(download_dir: &Path, video_id: &str) -> Option<PathBuf> {
+fn find_cached_file(
+    download_dir: &Path,
+    video_id: &str,
+    variant: &str,
+    ext: &str,
+) -> Option<PathBuf> {
     let cache_dir = download_dir.join(video_id);
     if !cache_dir.is_dir() {
         return None;
@@ -58,7 +155,7 @@ fn find_cached_mp4(download_dir: &Path, video_id: &str) -> Option<PathBuf> {
     let entries = std::fs::read_dir(&cache_dir).ok()?;
     for entry in entries.flatten() {
         let path = entry.path();
-        if !path.is_file() || !is_final_cached_mp4(&path) {
+        if !path.is_file() || !is_cached_variant(&path, variant, ext) {
             continue;
         }
 
@@ -76,9 +173,39 @@ fn find_cached_mp4(download_dir: &Path, video_id: &str) -> Option<PathBuf> {
     None
 }
 
+/// True for our hidden in-progress files: the ".Title.<job>.tmp.mp4" output plus
+/// the per-format pieces and .part files yt-dlp writes next to it.
+fn is_incomplete_download(name: &str) -> bool {
+    name.starts_with('.') && name.contains(".tmp.")
+}
+
 /// Drops leftover in-progress downloads in a video cache directory.
 fn scrub_incomplete_downloads(cache_dir: &Path) {
+    remove_matching_files(cache_dir, is_incomplete_download);
+}
+
+/// Drops the in-progress files of a single job (temp names start with `file_stem`).
+fn scrub_job_downloads(cache_dir: &Path, file_stem: &str) {
+    let prefix = format!("{file_stem}.");
+    remove_matching_files(cache_dir, |name| name.starts_with(&prefix));
+}
+
+/// Bytes a single job's in-progress files take up so far.
+fn job_download_bytes(cache_dir: &Path, file_stem: &str) -> u64 {
     let Ok(entries) = std::fs::read_dir(cache_dir) else {
+        return 0;
+    };
+    let prefix = format!("{file_stem}.");
+    entries
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with(&prefix))
+        .filter_map(|entry| entry.metadata().ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+fn remove_matching_files(dir: &Path, matches: impl Fn(&str) -> bool) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
         return;
     };
     for entry in entries.flatten() {
@@ -86,18 +213,37 @@ fn scrub_incomplete_downloads(cache_dir: &Path) {
         let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
             continue;
         };
-        if path.is_file() && name.starts_with('.') && name.ends_with(".tmp.mp4") {
+        if path.is_file() && matches(name) {
             let _ = std::fs::remove_file(&path);
         }
     }
 }
 
-/// Publishes a completed download by renaming the temp file into place.
-/// Rejects undersized outputs so they never become cache hits.
+/// A finished download, with the video's details when we have them.
+#[derive(Debug)]
+pub struct CompletedDownload {
+    pub path: PathBuf,
+    pub video: Option<VideoDetails>,
+    pub duration: Duration,
+}
+
+/// What publish_completed_download records about the new file in meta.json.
+struct PublishRecord<'a> {
+    video: &'a VideoDetails,
+    variant: &'a str,
+    format: String,
+    /// Keeps concurrent jobs off each other's temp files.
+    temp_tag: &'a str,
+}
+
+/// Publishes a completed download by renaming the temp file into place, then
+/// records it in the video's meta.json. Rejects undersized outputs so they
+/// never become cache hits.
 fn publish_completed_download(
     temp_path: &Path,
     final_path: &Path,
-) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    record: &PublishRecord,
+) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
     let size = std::fs::metadata(temp_path)?.len();
     if size < MIN_VALID_VIDEO_SIZE_BYTES {
         let _ = std::fs::remove_file(temp_path);
@@ -111,23 +257,296 @@ fn publish_completed_download(
         std::fs::remove_file(final_path)?;
     }
     std::fs::rename(temp_path, final_path)?;
+
+    // The file is already usable, so a failed meta.json write only costs details
+    if let Err(e) = record_published_file(final_path, size, record) {
+        warn!(path = %final_path.display(), error = %e, "Failed to write meta.json");
+    }
     Ok(final_path.to_path_buf())
 }
 
+/// Adds a published file to meta.json, keeping the URL the video was first requested with.
+fn record_published_file(
+    final_path: &Path,
+    size: u64,
+    record: &PublishRecord,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (Some(cache_dir), Some(name)) = (
+        final_path.parent(),
+        final_path.file_name().and_then(|n| n.to_str()),
+    ) else {
+        return Ok(());
+    };
+
+    let mut meta = read_meta(cache_dir).unwrap_or_else(|| VideoMeta::new(record.video.clone()));
+    meta.video = VideoDetails {
+        canonical_url: Some(canonicalize_url(&meta.video.url)),
+        url: meta.video.url,
+        ..record.video.clone()
+    };
+    // Forget files that were removed or replaced since
+    meta.files.retain(|file, _| cache_dir.join(file).is_file());
+    meta.files.insert(
+        name.to_string(),
+        FileMeta {
+            variant: record.variant.to_string(),
+            format: record.format.clone(),
+            size_bytes: size,
+            downloaded_at: unix_now(),
+        },
+    );
+    write_meta(cache_dir, &meta, record.temp_tag)
+}
+
+/// The video's thumbnail, if one has been fetched or generated.
+pub fn find_thumbnail(media_path: &Path) -> Option<PathBuf> {
+    let thumbnail = media_path.parent()?.join(THUMBNAIL_FILE_NAME);
+    thumbnail.is_file().then_some(thumbnail)
+}
+
+/// Moves the thumbnail yt-dlp wrote next to a temp download ("{temp_stem}.jpg") into place.
+fn publish_thumbnail(cache_dir: &Path, temp_stem: &str) {
+    let temp = cache_dir.join(format!("{temp_stem}.jpg"));
+    if !temp.is_file() {
+        return;
+    }
+    if let Err(e) = std::fs::rename(&temp, cache_dir.join(THUMBNAIL_FILE_NAME)) {
+        warn!(path = %temp.display(), error = %e, "Failed to publish thumbnail");
+    }
+}
+
 /*
- * Downloads a video from the given URL and associates it with a job ID.
- * Measures download duration, logs progress and errors.
+ * Gives a cached video a thumbnail when it has none, e.g. because it was
+ * downloaded before thumbnails were fetched or the site didn't offer one.
+ * Generated from the mp4 itself; audio-only downloads have nothing to draw from.
+ */
+async fn ensure_thumbnail(media_path: &Path, job: &JobHandle) {
+    let Some(cache_dir) = media_path.parent() else {
+        return;
+    };
+    let is_video = media_path.extension().is_some_and(|ext| ext == "mp4");
+    if !is_video || find_thumbnail(media_path).is_some() {
+        return;
+    }
+
+    let job_suffix = job.id().get(..8).unwrap_or("download");
+    let temp = cache_dir.join(format!(".thumbnail.{job_suffix}.tmp.jpg"));
+    let result = match generate_thumbnail(media_path, &temp).await {
+        Ok(()) => std::fs::rename(&temp, cache_dir.join(THUMBNAIL_FILE_NAME)).map_err(Into::into),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(&temp);
+        warn!(job = %job.id(), error = %e, "Thumbnail generation failed");
+    }
+}
+
+/// Adds the thumbnail and any requested subtitle sidecars to a published download.
+async fn complete_cache_entry(
+    url: &str,
+    media_path: &Path,
+    options: &DownloadOptions,
+    job: &JobHandle,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    ensure_thumbnail(media_path, job).await;
+    ensure_subtitle_sidecars(url, media_path, options, job).await
+}
+
+/// Title part of a published file name ("Title" for "Title.audio.mp3").
+fn cached_title(media_path: &Path) -> Option<&str> {
+    let name = media_path.file_name()?.to_str()?;
+    name.split_once('.').map(|(title, _)| title)
+}
+
+/// Subtitle sidecars of this download that exist on disk, in the requested language order.
+/// Sidecars are named "{title}.{lang}.{ext}" and shared by every variant of the video.
+pub fn find_subtitle_sidecars(media_path: &Path, subtitles: &Subtitles) -> Vec<PathBuf> {
+    let (Some(cache_dir), Some(title), Some(ext)) = (
+        media_path.parent(),
+        cached_title(media_path),
+        subtitles.sidecar_extension(),
+    ) else {
+        return Vec::new();
+    };
+    subtitles
+        .languages
+        .iter()
+        .map(|language| cache_dir.join(format!("{title}.{language}.{ext}")))
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+/*
+ * Makes sure the requested subtitle sidecars sit next to a published download.
+ * Fetches only when some are missing (a cached video may predate the request),
+ * writing hidden temp files and renaming them into place like the media itself.
+ * Languages the video doesn't have are skipped; failures other than
+ * cancellation are logged and leave the video usable.
+ */
+async fn ensure_subtitle_sidecars(
+    url: &str,
+    media_path: &Path,
+    options: &DownloadOptions,
+    job: &JobHandle,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let Some(subtitles) = options.sidecar_subtitles() else {
+        return Ok(());
+    };
+    let (Some(cache_dir), Some(title), Some(ext)) = (
+        media_path.parent(),
+        cached_title(media_path),
+        subtitles.sidecar_extension(),
+    ) else {
+        return Ok(());
+    };
+    if find_subtitle_sidecars(media_path, subtitles).len() == subtitles.languages.len() {
+        return Ok(());
+    }
+
+    let job_suffix = job.id().get(..8).unwrap_or("download");
+    let temp_stem = format!(".{title}.{job_suffix}.tmp");
+    let command = SubtitleCommand {
+        url,
+        output_dir: cache_dir,
+        file_stem: &temp_stem,
+        subtitles,
+    };
+    let result = run_subtitle_download(&command, job.cancellation()).await;
+    if result.is_ok() {
+        publish_subtitle_sidecars(cache_dir, &temp_stem, title, ext);
+    }
+    scrub_job_downloads(cache_dir, &temp_stem);
+
+    match result {
+        Err(e) if job.cancellation().is_cancelled() => Err(e),
+        Err(e) => {
+            warn!(job = %job.id(), error = %e, "Subtitle download failed");
+            Ok(())
+        }
+        Ok(()) => Ok(()),
+    }
+}
+
+/// Renames ".{title}.{job}.tmp.{lang}.{ext}" files to "{title}.{lang}.{ext}".
+fn publish_subtitle_sidecars(cache_dir: &Path, temp_stem: &str, title: &str, ext: &str) {
+    let Ok(entries) = std::fs::read_dir(cache_dir) else {
+        return;
+    };
+    let prefix = format!("{temp_stem}.");
+    let suffix = format!(".{ext}");
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Some(language) = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|name| name.strip_prefix(&prefix))
+            .and_then(|rest| rest.strip_suffix(&suffix))
+        else {
+            continue;
+        };
+        let sidecar = cache_dir.join(format!("{title}.{language}.{ext}"));
+        if let Err(e) = std::fs::rename(&path, &sidecar) {
+            warn!(path = %sidecar.display(), error = %e, "Failed to publish subtitles");
+        }
+    }
+}
+
+/// Claims the in-flight slot for `video_id`, waiting for any running download of it.
+/// The wait ends early if the job is cancelled or times out.
+async fn claim_in_flight(
+    state: &AppState,
+    video_id: &str,
+    job: &JobHandle,
+) -> Result<InFlightGuard, Box<dyn std::error::Error + Send + Sync>> {
+    tokio::select! {
+        guard = state.in_flight.acquire(video_id) => Ok(guard),
+        _ = job.cancellation().cancelled() => Err(CANCELLED_ERROR.into()),
+    }
+}
+
+/// Cache key for a URL without asking yt-dlp: parsed from the URL itself, or
+/// from the page a short link redirects to when RESOLVE_SHORT_LINKS is on.
+async fn cache_id_for(state: &AppState, canonical_url: &str) -> Option<String> {
+    if let Some(cache_id) = extract_cache_id(canonical_url) {
+        return Some(cache_id);
+    }
+    if !state.config.resolve_short_links {
+        return None;
+    }
+    let target = state.short_links.resolve(canonical_url).await?;
+    extract_cache_id(&target)
+}
+
+/*
+ * Fetches normalized metadata for a URL without downloading anything.
+ * Served from the info cache when the URL's video ID was looked up recently.
+ */
+pub async fn fetch_video_info(
+    state: &AppState,
+    url: &str,
+) -> Result<Arc<VideoInfo>, Box<dyn std::error::Error + Send + Sync>> {
+    let cache_id = cache_id_for(state, &canonicalize_url(url)).await;
+    if let Some(info) = cache_id.and_then(|id| state.info_cache.get(&id)) {
+        debug!(video = %info.id, "Info cache hit");
+        return Ok(info);
+    }
+
+    state.downloader.get(&state.config).await?;
+    tokio::time::timeout(
+        Duration::from_secs(state.config.timeout_seconds),
+        fetch_and_cache_info(state, url, &CancellationToken::new()),
+    )
+    .await
+    .map_err(|_| "Metadata fetch timed out")?
+    .map(|(info, _)| info)
+}
+
+/// Runs yt-dlp's metadata dump and caches the result under the video's cache key.
+/// Shared by /info and downloads, so both key the same video the same way. The raw
+/// info JSON is returned too, for downloads to hand back to yt-dlp.
+async fn fetch_and_cache_info(
+    state: &AppState,
+    url: &str,
+    cancel: &CancellationToken,
+) -> Result<(Arc<VideoInfo>, Value), Box<dyn std::error::Error + Send + Sync>> {
+    let permit = ytdlp_permit(state, cancel).await?;
+    let metadata = fetch_metadata(url, cancel).await?;
+    drop(permit);
+    let info = VideoInfo::from_metadata(&metadata).ok_or("yt-dlp returned unreadable metadata")?;
+    Ok((state.info_cache.insert(info), metadata))
+}
+
+/// One of the MAX_CONCURRENT_DOWNLOADS yt-dlp slots, or the caller's cancellation.
+async fn ytdlp_permit(
+    state: &AppState,
+    cancel: &CancellationToken,
+) -> Result<OwnedSemaphorePermit, Box<dyn std::error::Error + Send + Sync>> {
+    tokio::select! {
+        permit = state.ytdlp_permits.clone().acquire_owned() => Ok(permit?),
+        _ = cancel.cancelled() => Err(CANCELLED_ERROR.into()),
+    }
+}
+
+/*
+ * Downloads a video from the given URL on behalf of a job.
+ * Measures download duration, reports each stage to the job, logs progress and errors.
  * Creates job-specific directory, sanitizes filenames, and uses quality and codec config.
- * Returns the final path of the downloaded file and the duration taken.
+ * Returns the final path of the downloaded file, the video's details (from
+ * meta.json on cache hits) and the duration taken.
  */
-pub fn download_video(
+pub async fn download_video(
+    state: &AppState,
     url: String,
-    job_id: String,
-) -> Result<(PathBuf, Duration), Box<dyn std::error::Error>> {
+    options: &DownloadOptions,
+    job: &JobHandle,
+) -> Result<CompletedDownload, Box<dyn std::error::Error + Send + Sync>> {
     let start = Instant::now();
-    let config = Config::from_env();
+    let config = &state.config;
+    let job_id = job.id();
+    let variant = options.variant_key();
+    let ext = options.extension();
 
-    info!(job = %job_id, url = %url, "Job started");
+    info!(job = %job_id, url = %url, variant = %variant, "Job started");
 
     // Check if download directory exists
     let download_dir = PathBuf::from(&config.download_dir);
@@ -141,9 +560,24 @@ pub fn download_video(
     }
 
     // Fast path: parse the platform id from the URL and reuse a cached file
-    // without initializing yt-dlp or fetching metadata.
-    if let Some(cache_id) = extract_cache_id(&url) {
-        if let Some(path) = find_cached_mp4(&download_dir, &cache_id) {
+    // without initializing yt-dlp or fetching metadata. If another job is already
+    // downloading that id, wait for it and pick up the file it publishes.
+    let canonical_url = canonicalize_url(&url);
+    let cache_id = cache_id_for(state, &canonical_url).await;
+    // URLs we can't key up front coalesce on their canonical form instead. That
+    // claim is held to the end, so a repeat request waits, then hits the cache.
+    // It is only ever taken while holding no other claim, so it can't deadlock.
+    let _url_claim = match cache_id {
+        Some(_) => None,
+        None => Some(claim_in_flight(state, &canonical_url, job).await?),
+    };
+    let mut in_flight = None;
+    if let Some(cache_id) = cache_id {
+        if find_cached_file(&download_dir, &cache_id, &variant, ext).is_none() {
+            in_flight = Some(claim_in_flight(state, &cache_id, job).await?);
+        }
+        if let Some(path) = find_cached_file(&download_dir, &cache_id, &variant, ext) {
+            complete_cache_entry(&url, &path, options, job).await?;
             let duration = start.elapsed();
             info!(
                 job = %job_id,
@@ -152,71 +586,91 @@ pub fn download_video(
                 took = format_args!("{:.2}s", duration.as_secs_f64()),
                 "Cache hit, skipped metadata"
             );
-            return Ok((path, duration));
+            let video = path.parent().and_then(read_meta).map(|meta| meta.video);
+            return Ok(CompletedDownload {
+                path,
+                video,
+                duration,
+            });
         }
     }
 
-    // Initialize yt-dlp fetcher with corruption handling
-    let fetcher = match init_yt_dlp() {
-        Ok(f) => f,
-        Err(e)
-            if e.to_string().contains("invalid Zip archive")
-                || e.to_string().contains("Could not find EOCD") =>
-        {
-            warn!(job = %job_id, error = %e, "Corrupted yt-dlp libraries detected — reinstalling");
+    // Binaries are installed (and repaired if corrupted) on first use
+    state.downloader.get(config).await.inspect_err(|e| {
+        error!(job = %job_id, error = %e, "yt-dlp unavailable");
+    })?;
 
-            // Remove corrupted libs directory
-            let libs_dir = PathBuf::from("libs");
-            if libs_dir.exists() {
-                std::fs::remove_dir_all(&libs_dir).unwrap_or_else(|e| {
-                    error!(error = %e, "Failed to remove corrupted libs directory");
-                });
-                info!("Removed corrupted libs, retrying initialization");
-            }
+    // Store video_id for the completion log
+    let mut cached_video_id = Option::<String>::None;
 
-            // Retry initialization
-            match init_yt_dlp() {
-                Ok(f) => f,
-                Err(retry_error) => {
-                    error!(job = %job_id, error = %retry_error, "yt-dlp init failed after cleanup");
-                    return Err(retry_error);
-                }
-            }
-        }
-        Err(e) => {
-            error!(job = %job_id, error = %e, "yt-dlp init failed");
-            return Err(e);
+    let result = async {
+        if job.cancellation().is_cancelled() {
+            return Err(CANCELLED_ERROR.into());
         }
-    };
-
-    // Create a runtime to run async video info fetching and downloading
-    let rt = tokio::runtime::Runtime::new()?;
-
-    // Store video_id
-    let cached_video_id = RefCell::new(Option::<String>::None);
 
-    let result = rt.block_on(async {
         info!(job = %job_id, "Fetching metadata");
-        let video = fetcher.fetch_video_infos(url.clone()).await?;
+        job.set_status(JobStatus::FetchingMetadata);
+        let (info, metadata) = fetch_and_cache_info(state, &url, job.cancellation()).await?;
 
-        info!(job = %job_id, title = %video.title, "Metadata fetched");
+        info!(job = %job_id, title = %info.title, "Metadata fetched");
+        let details = VideoDetails::from_info(&url, &info);
 
-        // Fallback cache check using yt-dlp's canonical id (covers short links
-        // and URL shapes we couldn't parse up front).
-        let video_id = &video.id;
-        *cached_video_id.borrow_mut() = Some(video_id.clone());
-        if let Some(path) = find_cached_mp4(&download_dir, video_id) {
+        // Fallback cache check using yt-dlp's canonical id and extractor (covers
+        // short links and URL shapes we couldn't parse up front).
+        let cache_key = &info.cache_key();
+        cached_video_id = Some(cache_key.clone());
+
+        // Hold exactly one video claim, on the canonical id; swapping is safe because
+        // every holder re-checks the cache once it gets the claim.
+        if in_flight
+            .as_ref()
+            .is_none_or(|guard| guard.key() != cache_key)
+        {
+            drop(in_flight.take());
+            in_flight = Some(claim_in_flight(state, cache_key, job).await?);
+        }
+        // Entries from the flat "{id}" layout without a meta.json only move now
+        migrate_flat_entry(&download_dir, &info.id, cache_key);
+        if let Some(path) = find_cached_file(&download_dir, cache_key, &variant, ext) {
             info!(
                 job = %job_id,
-                video = %video_id,
+                video = %cache_key,
                 path = %path.display(),
                 "Cache hit after metadata"
             );
-            return Ok(path);
+            // Entries cached before meta.json existed get one now
+            if let Some(cache_dir) = path.parent().filter(|dir| read_meta(dir).is_none()) {
+                let job_suffix = job_id.get(..8).unwrap_or("download");
+                let _ = write_meta(cache_dir, &VideoMeta::new(details.clone()), job_suffix);
+            }
+            return Ok((path, details));
+        }
+
+        // Cache miss — yt-dlp may have followed redirects to reach the page, so its
+        // final URL gets the same checks as the submitted one
+        if let Some(page_url) = &details.webpage_url {
+            check_url(config, page_url).await.inspect_err(|e| {
+                warn!(job = %job_id, url = %page_url, error = %e, "Refusing redirected download");
+            })?;
         }
 
-        // Cache miss — download into downloads/{video_id}/
-        let cache_dir = download_dir.join(video_id);
+        // Refuse videos over the length and size limits before fetching anything
+        check_video(config, &info, options).inspect_err(|e| {
+            warn!(job = %job_id, video = %cache_key, error = %e, "Refusing download");
+        })?;
+        options.check_format(&info).inspect_err(|e| {
+            warn!(job = %job_id, video = %cache_key, error = %e, "Refusing download");
+        })?;
+
+        // Make room first, and refuse rather than fill the disk
+        enforce_disk_budget_blocking(config.clone(), state.in_flight.clone())
+            .await
+            .inspect_err(|e| {
+                warn!(job = %job_id, video = %cache_key, error = %e, "Refusing download");
+            })?;
+
+        // Download into downloads/{platform}-{id}/
+        let cache_dir = download_dir.join(cache_key);
         std::fs::create_dir_all(&cache_dir)?;
         scrub_incomplete_downloads(&cache_dir);
         debug!(job = %job_id, dir = %cache_dir.display(), "Cache directory created");
@@ -247,47 +701,95 @@ pub fn download_video(
         }
 
         // Sanitize filename to avoid illegal characters
-        let clean_title = clean(&sanitize_filename::sanitize(&video.title));
+        let clean_title = clean(&sanitize_filename::sanitize(&info.title));
         let clean_title = if clean_title.is_empty() {
             "video".to_string()
         } else {
             clean_title
         };
-        // Keep a real .mp4 extension so remux/ffmpeg behave, but hide the name
-        // so find_cached_mp4 ignores it until we atomically publish.
+        // Keep the real extension so remux/ffmpeg behave, but hide the name
+        // so find_cached_file ignores it until we atomically publish.
         let job_suffix = job_id.get(..8).unwrap_or("download");
-        let temp_relative = format!("{video_id}/.{clean_title}.{job_suffix}.tmp.mp4");
-        let final_relative = format!("{video_id}/{clean_title}.mp4");
-        let final_path = download_dir.join(&final_relative);
+        let temp_stem = format!(".{clean_title}.{job_suffix}.tmp");
+        let final_path = cache_dir.join(cached_file_name(&clean_title, &variant, ext));
+        // Claims are never awaited while holding a permit, so waiting here is deadlock-free
+        let permit = ytdlp_permit(state, job.cancellation()).await?;
+        // yt-dlp loads the metadata we already have instead of extracting the page again
+        let info_json = cache_dir.join(format!("{temp_stem}.info.json"));
+        std::fs::write(&info_json, serde_json::to_vec(&metadata)?)?;
 
         info!(
             job = %job_id,
-            video = %video_id,
-            quality = ?config.video_quality,
-            vcodec = ?config.video_codec,
-            acodec = ?config.audio_codec,
+            video = %cache_key,
+            mode = ?options.mode,
+            quality = ?options.video_quality,
+            vcodec = ?options.video_codec,
+            acodec = ?options.audio_codec,
             "Downloading"
         );
 
         // Download to a temp name, then rename into place only if it looks valid.
-        let temp_path = match fetcher
-            .download(&video, &temp_relative)
-            .video_quality(config.video_quality)
-            .video_codec(config.video_codec.clone())
-            .audio_quality(config.audio_quality)
-            .audio_codec(config.audio_codec.clone())
-            .execute()
-            .await
-        {
+        job.set_status(JobStatus::Downloading);
+        let command = DownloadCommand {
+            url: &url,
+            info_json: Some(&info_json),
+            output_dir: &cache_dir,
+            file_stem: &temp_stem,
+            options,
+        };
+        // Tripped (without cancelling the job) once the temp files pass MAX_FILESIZE_BYTES
+        let abort = job.cancellation().child_token();
+        let watch_size = config.max_filesize_bytes > 0;
+        let mut over_limit = None;
+        let downloaded = run_download(&command, &abort, |progress| {
+            job.set_progress(progress);
+            if watch_size && over_limit.is_none() {
+                let on_disk =
+                    job_download_bytes(&cache_dir, &temp_stem).max(progress.downloaded_bytes);
+                over_limit = check_download_size(config, on_disk).err();
+                if over_limit.is_some() {
+                    abort.cancel();
+                }
+            }
+        })
+        .await;
+        drop(permit);
+        let _ = std::fs::remove_file(&info_json);
+        let downloaded = match over_limit {
+            Some(limit) => {
+                warn!(
+                    job = %job_id,
+                    video = %cache_key,
+                    error = %limit,
+                    "Stopped download over the size limit"
+                );
+                Err(limit.into())
+            }
+            // Merging can still land over the limit, so check the finished file too
+            None => downloaded.and_then(|path| {
+                check_download_size(config, std::fs::metadata(&path)?.len())?;
+                Ok(path)
+            }),
+        };
+        let temp_path = match downloaded {
             Ok(path) => path,
             Err(e) => {
-                let leftover = download_dir.join(&temp_relative);
-                let _ = std::fs::remove_file(&leftover);
-                return Err(e.into());
+                // Covers cancellation, timeouts and the size limit: yt-dlp is dead,
+                // drop its leftovers
+                scrub_job_downloads(&cache_dir, &temp_stem);
+                return Err(e);
             }
         };
 
-        let published = match publish_completed_download(&temp_path, &final_path) {
+        job.set_status(JobStatus::Publishing);
+        publish_thumbnail(&cache_dir, &temp_stem);
+        let record = PublishRecord {
+            video: &details,
+            variant: &variant,
+            format: options.format_selector(),
+            temp_tag: job_suffix,
+        };
+        let published = match publish_completed_download(&temp_path, &final_path, &record) {
             Ok(path) => path,
             Err(e) => {
                 let _ = std::fs::remove_file(&temp_path);
@@ -295,16 +797,24 @@ pub fn download_video(
             }
         };
 
-        Ok::<_, Box<dyn std::error::Error>>(published)
-    });
+        Ok::<_, Box<dyn std::error::Error + Send + Sync>>((published, details))
+    }
+    .await;
+
+    // Thumbnail and sidecars are added while we still hold the in-flight claim
+    let result = match result {
+        Ok((path, details)) => complete_cache_entry(&url, &path, options, job)
+            .await
+            .map(|()| (path, details)),
+        Err(e) => Err(e),
+    };
 
     let duration = start.elapsed();
 
     // Log and return results based on success or failure
     match result {
-        Ok(video_path) => {
-            let video_id_borrowed = cached_video_id.borrow();
-            let video_id_log = video_id_borrowed.as_deref().unwrap_or("unknown");
+        Ok((video_path, details)) => {
+            let video_id_log = cached_video_id.as_deref().unwrap_or("unknown");
             info!(
                 job = %job_id,
                 video = %video_id_log,
@@ -312,7 +822,19 @@ pub fn download_video(
                 took = format_args!("{:.2}s", duration.as_secs_f64()),
                 "Download complete"
             );
-            Ok((video_path, duration))
+            Ok(CompletedDownload {
+                path: video_path,
+                video: Some(details),
+                duration,
+            })
+        }
+        Err(e) if job.cancellation().is_cancelled() => {
+            info!(
+                job = %job_id,
+                took = format_args!("{:.2}s", duration.as_secs_f64()),
+                "Download stopped, temp files scrubbed"
+            );
+            Err(e)
         }
         Err(e) => {
             error!(
@@ -329,60 +851,27 @@ pub fn download_video(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::ffi::{OsStr, OsString};
+    use crate::config::{
+        AudioCodecPreferenceEnv, AudioQualityEnv, VideoCodecPreferenceEnv, VideoQualityEnv,
+    };
+    use crate::utils::test_support::TestDirectory;
     use std::fs;
-    use uuid::Uuid;
 
-    struct TempDownloadDir(PathBuf);
-
-    impl TempDownloadDir {
-        fn new() -> Self {
-            let path =
-                std::env::temp_dir().join(format!("snatchr-youtube-test-{}", Uuid::new_v4()));
-            fs::create_dir_all(&path).expect("temporary download directory should be created");
-            Self(path)
-        }
-    }
-
-    impl Drop for TempDownloadDir {
-        fn drop(&mut self) {
-            let _ = fs::remove_dir_all(&self.0);
-        }
-    }
-
-    struct EnvVarGuard {
-        key: &'static str,
-        previous: Option<OsString>,
-    }
-
-    impl EnvVarGuard {
-        fn set(key: &'static str, value: impl AsRef<OsStr>) -> Self {
-            let previous = std::env::var_os(key);
-
-            // This ignored test must be run by itself with one test thread.
-            unsafe {
-                std::env::set_var(key, value);
-            }
-
-            Self { key, previous }
-        }
-    }
-
-    impl Drop for EnvVarGuard {
-        fn drop(&mut self) {
-            unsafe {
-                if let Some(previous) = &self.previous {
-                    std::env::set_var(self.key, previous);
-                } else {
-                    std::env::remove_var(self.key);
-                }
-            }
-        }
+    // Low quality and any codec keep the ignored real-download tests quick
+    fn test_state(download_dir: &Path) -> AppState {
+        AppState::new(Config {
+            download_dir: download_dir.to_string_lossy().into_owned(),
+            video_quality: VideoQualityEnv::Low,
+            video_codec: VideoCodecPreferenceEnv::Any,
+            audio_quality: AudioQualityEnv::Low,
+            audio_codec: AudioCodecPreferenceEnv::Any,
+            ..Config::default()
+        })
     }
 
     #[test]
-    fn find_cached_mp4_ignores_incomplete_temp_files() {
-        let download_dir = TempDownloadDir::new();
+    fn find_cached_file_ignores_incomplete_temp_files() {
+        let download_dir = TestDirectory::new();
         let video_id = "abc123";
         let video_dir = download_dir.0.join(video_id);
         fs::create_dir_all(&video_dir).unwrap();
@@ -393,21 +882,95 @@ mod tests {
         )
         .unwrap();
 
-        assert!(find_cached_mp4(&download_dir.0, video_id).is_none());
+        assert!(find_cached_file(&download_dir.0, video_id, "", "mp4").is_none());
 
         let final_path = video_dir.join("Title.mp4");
         fs::write(&final_path, vec![0_u8; MIN_VALID_VIDEO_SIZE_BYTES as usize]).unwrap();
-        assert_eq!(find_cached_mp4(&download_dir.0, video_id), Some(final_path));
+        assert_eq!(
+            find_cached_file(&download_dir.0, video_id, "", "mp4"),
+            Some(final_path)
+        );
+    }
+
+    #[test]
+    fn variants_of_one_video_never_match_each_other() {
+        let download_dir = TestDirectory::new();
+        let video_id = "abc123";
+        let video_dir = download_dir.0.join(video_id);
+        fs::create_dir_all(&video_dir).unwrap();
+
+        let audio = video_dir.join(cached_file_name("Title", "audio", "mp3"));
+        fs::write(&audio, vec![0_u8; MIN_VALID_VIDEO_SIZE_BYTES as usize]).unwrap();
+        assert_eq!(audio.file_name().unwrap(), "Title.audio.mp3");
+
+        assert!(find_cached_file(&download_dir.0, video_id, "", "mp4").is_none());
+        assert!(find_cached_file(&download_dir.0, video_id, "audio", "flac").is_none());
+        assert_eq!(
+            find_cached_file(&download_dir.0, video_id, "audio", "mp3"),
+            Some(audio)
+        );
+
+        let video = video_dir.join(cached_file_name("Title", "", "mp4"));
+        fs::write(&video, vec![0_u8; MIN_VALID_VIDEO_SIZE_BYTES as usize]).unwrap();
+        assert_eq!(
+            find_cached_file(&download_dir.0, video_id, "", "mp4"),
+            Some(video)
+        );
+    }
+
+    #[test]
+    fn scrub_job_downloads_leaves_other_jobs_alone() {
+        let download_dir = TestDirectory::new();
+        let mine = download_dir.0.join(".Title.aaaa1111.tmp.f137.mp4");
+        let mine_part = download_dir.0.join(".Title.aaaa1111.tmp.f251.webm.part");
+        let theirs = download_dir.0.join(".Title.bbbb2222.tmp.mp4");
+        for path in [&mine, &mine_part, &theirs] {
+            fs::write(path, b"partial").unwrap();
+        }
+
+        // Only this job's pieces count toward its size limit
+        assert_eq!(
+            job_download_bytes(&download_dir.0, ".Title.aaaa1111.tmp"),
+            14
+        );
+        scrub_job_downloads(&download_dir.0, ".Title.aaaa1111.tmp");
+
+        assert!(!mine.exists());
+        assert!(!mine_part.exists());
+        assert!(theirs.exists());
+    }
+
+    fn test_record(video: &VideoDetails) -> PublishRecord<'_> {
+        PublishRecord {
+            video,
+            variant: "",
+            format: "bv*+ba/b".to_string(),
+            temp_tag: "aaaa1111",
+        }
+    }
+
+    fn test_details() -> VideoDetails {
+        VideoDetails {
+            id: "abc123".to_string(),
+            url: "https://example.com/watch/abc123".to_string(),
+            canonical_url: Some("https://example.com/watch/abc123".to_string()),
+            webpage_url: None,
+            title: "Title".to_string(),
+            uploader: None,
+            extractor: None,
+            duration_seconds: None,
+        }
     }
 
     #[test]
     fn publish_rejects_undersized_downloads() {
-        let download_dir = TempDownloadDir::new();
+        let download_dir = TestDirectory::new();
         let temp = download_dir.0.join(".video.tmp.mp4");
         let final_path = download_dir.0.join("video.mp4");
         fs::write(&temp, b"tiny").unwrap();
 
-        let err = publish_completed_download(&temp, &final_path).unwrap_err();
+        let err = publish_completed_download(&temp, &final_path, &test_record(&test_details()))
+            .unwrap_err();
         assert!(err.to_string().contains("too small"));
         assert!(!temp.exists());
         assert!(!final_path.exists());
@@ -415,51 +978,173 @@ mod tests {
 
     #[test]
     fn publish_renames_valid_download_into_place() {
-        let download_dir = TempDownloadDir::new();
+        let download_dir = TestDirectory::new();
         let temp = download_dir.0.join(".video.tmp.mp4");
         let final_path = download_dir.0.join("video.mp4");
         fs::write(&temp, vec![0_u8; MIN_VALID_VIDEO_SIZE_BYTES as usize]).unwrap();
 
-        let published = publish_completed_download(&temp, &final_path).unwrap();
+        let published =
+            publish_completed_download(&temp, &final_path, &test_record(&test_details())).unwrap();
         assert_eq!(published, final_path);
         assert!(final_path.exists());
         assert!(!temp.exists());
+
+        let meta = read_meta(&download_dir.0).expect("meta.json should be written");
+        assert_eq!(meta.video.title, "Title");
+        assert_eq!(
+            meta.files["video.mp4"].size_bytes,
+            MIN_VALID_VIDEO_SIZE_BYTES
+        );
+        assert_eq!(meta.files["video.mp4"].variant, "");
+    }
+
+    #[test]
+    fn subtitle_sidecars_are_published_beside_the_media() {
+        use crate::handlers::options::{SubtitleOutput, SubtitleSource};
+
+        let download_dir = TestDirectory::new();
+        let media = download_dir.0.join("Title.audio.mp3");
+        fs::write(&media, vec![0_u8; MIN_VALID_VIDEO_SIZE_BYTES as usize]).unwrap();
+        fs::write(download_dir.0.join(".Title.aaaa1111.tmp.en.srt"), b"1").unwrap();
+        fs::write(download_dir.0.join(".Title.aaaa1111.tmp.pt-BR.srt"), b"1").unwrap();
+
+        publish_subtitle_sidecars(&download_dir.0, ".Title.aaaa1111.tmp", "Title", "srt");
+
+        let subtitles = Subtitles {
+            languages: vec!["pt-BR".to_string(), "de".to_string(), "en".to_string()],
+            source: SubtitleSource::Manual,
+            output: SubtitleOutput::Srt,
+        };
+        assert_eq!(
+            find_subtitle_sidecars(&media, &subtitles),
+            [
+                download_dir.0.join("Title.pt-BR.srt"),
+                download_dir.0.join("Title.en.srt")
+            ]
+        );
     }
 
     #[test]
-    fn cache_hit_from_url_skips_yt_dlp() {
-        let download_dir = TempDownloadDir::new();
+    fn thumbnail_from_the_download_is_shared_by_every_variant() {
+        let download_dir = TestDirectory::new();
+        let media = download_dir.0.join("Title.720p.mp4");
+        fs::write(&media, vec![0_u8; MIN_VALID_VIDEO_SIZE_BYTES as usize]).unwrap();
+        assert!(find_thumbnail(&media).is_none());
+
+        fs::write(download_dir.0.join(".Title.aaaa1111.tmp.jpg"), b"jpg").unwrap();
+        publish_thumbnail(&download_dir.0, ".Title.aaaa1111.tmp");
+
+        let thumbnail = download_dir.0.join(THUMBNAIL_FILE_NAME);
+        assert_eq!(find_thumbnail(&media), Some(thumbnail.clone()));
+        assert_eq!(
+            find_thumbnail(&download_dir.0.join("Title.audio.mp3")),
+            Some(thumbnail)
+        );
+    }
+
+    #[tokio::test]
+    async fn cache_hit_from_url_skips_yt_dlp() {
+        let download_dir = TestDirectory::new();
         let video_id = "dQw4w9WgXcQ";
-        let video_dir = download_dir.0.join(video_id);
+        let video_dir = download_dir.0.join(format!("youtube-{video_id}"));
         fs::create_dir_all(&video_dir).expect("cache directory should be created");
 
         let cached_file = video_dir.join("cached.mp4");
         fs::write(&cached_file, vec![0_u8; MIN_VALID_VIDEO_SIZE_BYTES as usize])
             .expect("cached file should be written");
-
-        let _download_dir = EnvVarGuard::set("DOWNLOAD_DIR", &download_dir.0);
-        let (path, _duration) = download_video(
-            format!("https://www.youtube.com/watch?v={video_id}"),
-            "cache-hit-test".to_string(),
+        fs::write(
+            video_dir.join("meta.json"),
+            r#"{"id": "dQw4w9WgXcQ", "url": "https://youtu.be/dQw4w9WgXcQ", "title": "Cached", "files": {}}"#,
         )
-        .expect("cached video should be returned without downloading");
+        .expect("meta.json should be written");
+
+        let state = test_state(&download_dir.0);
+        let options = DownloadOptions::from_config(&state.config);
+        let url = format!("https://www.youtube.com/watch?v={video_id}");
+        let job = state.jobs.create(&url);
+        let download = download_video(&state, url, &options, &job)
+            .await
+            .expect("cached video should be returned without downloading");
 
-        assert_eq!(path, cached_file);
+        assert_eq!(download.path, cached_file);
+        assert_eq!(
+            download.video.map(|video| video.title).as_deref(),
+            Some("Cached")
+        );
     }
 
-    #[test]
+    #[tokio::test]
+    async fn info_and_downloads_share_cache_keys() {
+        let download_dir = TestDirectory::new();
+        let state = test_state(&download_dir.0);
+        let video_id = "dQw4w9WgXcQ";
+        // What a download's metadata fetch leaves in the info cache
+        let info = VideoInfo::from_metadata(&serde_json::json!({
+            "id": video_id,
+            "extractor_key": "Youtube",
+            "title": "Fetched by a download",
+        }))
+        .unwrap();
+        let info = state.info_cache.insert(info);
+
+        let url = format!("https://youtu.be/{video_id}?si=share");
+        assert_eq!(
+            extract_cache_id(&canonicalize_url(&url)),
+            Some(info.cache_key())
+        );
+        // Served from the cache, without asking yt-dlp again
+        let cached = fetch_video_info(&state, &url)
+            .await
+            .expect("/info should reuse the download's metadata");
+        assert!(Arc::ptr_eq(&cached, &info));
+    }
+
+    #[tokio::test]
+    async fn concurrent_request_waits_for_in_flight_download() {
+        let download_dir = TestDirectory::new();
+        let state = test_state(&download_dir.0);
+        let video_id = "dQw4w9WgXcQ";
+        let cache_key = format!("youtube-{video_id}");
+        let first = state.in_flight.acquire(&cache_key).await;
+        let options = DownloadOptions::from_config(&state.config);
+
+        let url = format!("https://www.youtube.com/watch?v={video_id}");
+        let job = state.jobs.create(&url);
+        let waiter = tokio::spawn({
+            let state = state.clone();
+            async move {
+                download_video(&state, url, &options, &job)
+                    .await
+                    .map_err(|e| e.to_string())
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiter.is_finished());
+
+        // The first download publishes its file and releases the video
+        let video_dir = download_dir.0.join(&cache_key);
+        fs::create_dir_all(&video_dir).unwrap();
+        let published = video_dir.join("Title.mp4");
+        fs::write(&published, vec![0_u8; MIN_VALID_VIDEO_SIZE_BYTES as usize]).unwrap();
+        drop(first);
+
+        let download = waiter.await.unwrap().expect("waiter should reuse the file");
+        assert_eq!(download.path, published);
+        assert!(download.video.is_none());
+    }
+
+    #[tokio::test]
     #[ignore = "downloads a real YouTube video; run manually"]
-    fn downloads_real_youtube_video() {
-        let download_dir = TempDownloadDir::new();
-        let _download_dir = EnvVarGuard::set("DOWNLOAD_DIR", &download_dir.0);
-        let _video_quality = EnvVarGuard::set("VIDEO_QUALITY", "Low");
-        let _video_codec = EnvVarGuard::set("VIDEO_CODEC", "any");
-        let _audio_quality = EnvVarGuard::set("AUDIO_QUALITY", "Low");
-        let _audio_codec = EnvVarGuard::set("AUDIO_CODEC", "any");
+    async fn downloads_real_youtube_video() {
+        let download_dir = TestDirectory::new();
+        let state = test_state(&download_dir.0);
         let url = std::env::var("SNATCHR_TEST_YOUTUBE_URL")
             .unwrap_or_else(|_| "https://www.youtube.com/watch?v=tCDvOQI3pco".to_string());
 
-        let (path, duration) = download_video(url, "youtube-smoke-test".to_string())
+        let options = DownloadOptions::from_config(&state.config);
+        let job = state.jobs.create(&url);
+        let CompletedDownload { path, duration, .. } = download_video(&state, url, &options, &job)
+            .await
             .expect("YouTube video should download successfully");
         let metadata = fs::metadata(&path).expect("downloaded video should exist");
 
@@ -469,20 +1154,19 @@ mod tests {
         assert!(!duration.is_zero());
     }
 
-    #[test]
+    #[tokio::test]
     #[ignore = "downloads a real TikTok video; run manually"]
-    fn downloads_real_tiktok_video() {
-        let download_dir = TempDownloadDir::new();
-        let _download_dir = EnvVarGuard::set("DOWNLOAD_DIR", &download_dir.0);
-        let _video_quality = EnvVarGuard::set("VIDEO_QUALITY", "Low");
-        let _video_codec = EnvVarGuard::set("VIDEO_CODEC", "any");
-        let _audio_quality = EnvVarGuard::set("AUDIO_QUALITY", "Low");
-        let _audio_codec = EnvVarGuard::set("AUDIO_CODEC", "any");
+    async fn downloads_real_tiktok_video() {
+        let download_dir = TestDirectory::new();
+        let state = test_state(&download_dir.0);
         let url = std::env::var("SNATCHR_TEST_TIKTOK_URL").unwrap_or_else(|_| {
             "https://www.tiktok.com/@rickastleyofficial/video/7593022588272561430".to_string()
         });
 
-        let (path, duration) = download_video(url, "tiktok-smoke-test".to_string())
+        let options = DownloadOptions::from_config(&state.config);
+        let job = state.jobs.create(&url);
+        let CompletedDownload { path, duration, .. } = download_video(&state, url, &options, &job)
+            .await
             .expect("TikTok video should download successfully");
         let metadata = fs::metadata(&path).expect("downloaded video should exist");
 
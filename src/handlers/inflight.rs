@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+use tracing::info;
+
+/*
+ * Registry of downloads currently in flight, keyed by cache ID.
+ *
+ * Whoever holds the guard for a video is the only one allowed to fetch it into
+ * downloads/{video_id}/. A second request for the same video waits for that
+ * guard, then re-checks the cache and picks up the file the first request
+ * published instead of starting its own yt-dlp run. If the first request failed
+ * the waiter simply tries the download itself.
+ */
+
+#[derive(Clone, Default)]
+pub struct InFlightDownloads {
+    locks: Arc<Mutex<HashMap<String, Weak<AsyncMutex<()>>>>>,
+}
+
+/// Held for as long as a download of `key` is in progress.
+pub struct InFlightGuard {
+    key: String,
+    _guard: OwnedMutexGuard<()>,
+}
+
+impl InFlightGuard {
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl InFlightDownloads {
+    /// Waits until no other download of `key` is running, then claims it.
+    pub async fn acquire(&self, key: &str) -> InFlightGuard {
+        let lock = self.lock_for(key);
+        let guard = match lock.clone().try_lock_owned() {
+            Ok(guard) => guard,
+            Err(_) => {
+                info!(video = %key, "Waiting for in-flight download of the same video");
+                lock.lock_owned().await
+            }
+        };
+
+        InFlightGuard {
+            key: key.to_string(),
+            _guard: guard,
+        }
+    }
+
+    /// Claims `key` only if no download of it is running right now.
+    pub fn try_acquire(&self, key: &str) -> Option<InFlightGuard> {
+        let guard = self.lock_for(key).try_lock_owned().ok()?;
+        Some(InFlightGuard {
+            key: key.to_string(),
+            _guard: guard,
+        })
+    }
+
+    // Returns the shared lock for `key`, creating it if nobody holds one.
+    // Entries whose lock was dropped by every holder and waiter are pruned here.
+    fn lock_for(&self, key: &str) -> Arc<AsyncMutex<()>> {
+        let mut locks = self.locks();
+        locks.retain(|_, lock| lock.strong_count() > 0);
+
+        if let Some(lock) = locks.get(key).and_then(Weak::upgrade) {
+            return lock;
+        }
+        let lock = Arc::new(AsyncMutex::new(()));
+        locks.insert(key.to_string(), Arc::downgrade(&lock));
+        lock
+    }
+
+    fn locks(&self) -> MutexGuard<'_, HashMap<String, Weak<AsyncMutex<()>>>> {
+        self.locks.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::time::timeout;
+
+    #[tokio::test]
+    async fn second_download_of_same_video_waits_for_the_first() {
+        let in_flight = InFlightDownloads::default();
+        let first = in_flight.acquire("dQw4w9WgXcQ").await;
+
+        let waiter = tokio::spawn({
+            let in_flight = in_flight.clone();
+            async move { in_flight.acquire("dQw4w9WgXcQ").await.key().to_string() }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiter.is_finished());
+
+        // Other videos are not held up
+        let other = timeout(Duration::from_secs(1), in_flight.acquire("abc123")).await;
+        assert!(other.is_ok());
+
+        drop(first);
+        let key = timeout(Duration::from_secs(1), waiter)
+            .await
+            .expect("waiter should proceed once the first download finishes")
+            .unwrap();
+        assert_eq!(key, "dQw4w9WgXcQ");
+    }
+
+    #[tokio::test]
+    async fn try_acquire_fails_while_a_download_runs() {
+        let in_flight = InFlightDownloads::default();
+        let running = in_flight.acquire("dQw4w9WgXcQ").await;
+        assert!(in_flight.try_acquire("dQw4w9WgXcQ").is_none());
+        assert!(in_flight.try_acquire("abc123").is_some());
+
+        drop(running);
+        assert!(in_flight.try_acquire("dQw4w9WgXcQ").is_some());
+    }
+
+    #[tokio::test]
+    async fn released_entries_are_pruned() {
+        let in_flight = InFlightDownloads::default();
+        drop(in_flight.acquire("one").await);
+        drop(in_flight.acquire("two").await);
+
+        let _held = in_flight.acquire("three").await;
+        assert_eq!(in_flight.locks().len(), 1);
+    }
+}
@@ -0,0 +1,271 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::utils::video_id::cache_key_for;
+
+/*
+ * Normalized video metadata for GET /info.
+ *
+ * yt-dlp's metadata is huge and differs per extractor; we keep the handful of
+ * fields clients actually decide on (title, duration, uploader, thumbnails and
+ * formats) under stable names. Results are cached per cache key for INFO_TTL,
+ * and download_video refreshes the cache with every metadata fetch it makes.
+ */
+
+const INFO_TTL: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VideoInfo {
+    pub id: String,
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub webpage_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extractor: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_seconds: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uploader: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upload_date: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub view_count: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thumbnail: Option<String>,
+    pub thumbnails: Vec<ThumbnailInfo>,
+    pub formats: Vec<FormatInfo>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ThumbnailInfo {
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FormatInfo {
+    pub format_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ext: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fps: Option<f64>,
+    /// None for audio-only formats.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vcodec: Option<String>,
+    /// None for video-only formats.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub acodec: Option<String>,
+    /// Exact size when known, otherwise yt-dlp's estimate.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filesize: Option<u64>,
+    /// Total bitrate in kbit/s.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tbr: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+impl VideoInfo {
+    /// The video's cache key, "{platform}-{id}", as extract_cache_id builds it from URLs.
+    pub fn cache_key(&self) -> String {
+        cache_key_for(self.extractor.as_deref(), &self.id)
+    }
+
+    /// Normalizes the info JSON yt-dlp dumps for a video (see `fetch_metadata`).
+    pub fn from_metadata(video: &impl Serialize) -> Option<Self> {
+        Self::from_value(&serde_json::to_value(video).ok()?)
+    }
+
+    // Field names follow yt-dlp's info JSON, which the Downloader model mirrors
+    fn from_value(value: &Value) -> Option<Self> {
+        let thumbnails = value
+            .get("thumbnails")
+            .and_then(Value::as_array)
+            .map(|thumbnails| {
+                thumbnails
+                    .iter()
+                    .filter_map(|thumbnail| {
+                        Some(ThumbnailInfo {
+                            url: str_field(thumbnail, "url")?,
+                            width: u64_field(thumbnail, "width"),
+                            height: u64_field(thumbnail, "height"),
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let formats = value
+            .get("formats")
+            .and_then(Value::as_array)
+            .map(|formats| formats.iter().filter_map(FormatInfo::from_value).collect())
+            .unwrap_or_default();
+
+        Some(Self {
+            id: str_field(value, "id")?,
+            title: str_field(value, "title").unwrap_or_default(),
+            webpage_url: str_field(value, "webpage_url"),
+            extractor: str_field(value, "extractor_key").or_else(|| str_field(value, "extractor")),
+            duration_seconds: value.get("duration").and_then(Value::as_f64),
+            uploader: str_field(value, "uploader").or_else(|| str_field(value, "channel")),
+            upload_date: str_field(value, "upload_date"),
+            view_count: u64_field(value, "view_count"),
+            thumbnail: str_field(value, "thumbnail"),
+            thumbnails,
+            formats,
+        })
+    }
+}
+
+impl FormatInfo {
+    fn from_value(value: &Value) -> Option<Self> {
+        Some(Self {
+            format_id: str_field(value, "format_id")?,
+            ext: str_field(value, "ext"),
+            width: u64_field(value, "width"),
+            height: u64_field(value, "height"),
+            fps: value.get("fps").and_then(Value::as_f64),
+            vcodec: codec_field(value, "vcodec"),
+            acodec: codec_field(value, "acodec"),
+            filesize: u64_field(value, "filesize").or_else(|| u64_field(value, "filesize_approx")),
+            tbr: value.get("tbr").and_then(Value::as_f64),
+            note: str_field(value, "format_note"),
+        })
+    }
+}
+
+fn str_field(value: &Value, key: &str) -> Option<String> {
+    value
+        .get(key)
+        .and_then(Value::as_str)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+}
+
+// Sizes and counts sometimes come through as floats
+fn u64_field(value: &Value, key: &str) -> Option<u64> {
+    let field = value.get(key)?;
+    field
+        .as_u64()
+        .or_else(|| field.as_f64().map(|number| number as u64))
+}
+
+// yt-dlp writes "none" for a missing video or audio stream
+fn codec_field(value: &Value, key: &str) -> Option<String> {
+    str_field(value, key).filter(|codec| codec != "none")
+}
+
+/// Recently fetched metadata, keyed by cache key.
+#[derive(Clone, Default)]
+pub struct InfoCache {
+    entries: Arc<Mutex<HashMap<String, (Instant, Arc<VideoInfo>)>>>,
+}
+
+impl InfoCache {
+    pub fn get(&self, cache_key: &str) -> Option<Arc<VideoInfo>> {
+        self.get_at(cache_key, Instant::now())
+    }
+
+    pub fn insert(&self, info: VideoInfo) -> Arc<VideoInfo> {
+        self.insert_at(info, Instant::now())
+    }
+
+    fn get_at(&self, cache_key: &str, now: Instant) -> Option<Arc<VideoInfo>> {
+        self.entries()
+            .get(cache_key)
+            .filter(|(fetched_at, _)| now.duration_since(*fetched_at) < INFO_TTL)
+            .map(|(_, info)| info.clone())
+    }
+
+    fn insert_at(&self, info: VideoInfo, now: Instant) -> Arc<VideoInfo> {
+        let info = Arc::new(info);
+        let mut entries = self.entries();
+        entries.retain(|_, (fetched_at, _)| now.duration_since(*fetched_at) < INFO_TTL);
+        entries.insert(info.cache_key(), (now, info.clone()));
+        info
+    }
+
+    fn entries(&self) -> MutexGuard<'_, HashMap<String, (Instant, Arc<VideoInfo>)>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sample() -> Value {
+        json!({
+            "id": "dQw4w9WgXcQ",
+            "title": "Never Gonna Give You Up",
+            "webpage_url": "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+            "extractor_key": "Youtube",
+            "duration": 213.0,
+            "channel": "Rick Astley",
+            "view_count": 1.5e9,
+            "thumbnails": [
+                {"url": "https://i.ytimg.com/vi/dQw4w9WgXcQ/hqdefault.jpg", "width": 480, "height": 360},
+                {"id": "broken"}
+            ],
+            "formats": [
+                {"format_id": "251", "ext": "webm", "vcodec": "none", "acodec": "opus", "tbr": 130.5},
+                {"format_id": "137", "ext": "mp4", "width": 1920, "height": 1080, "vcodec": "avc1.640028",
+                 "acodec": "none", "filesize_approx": 52428800, "format_note": "1080p"}
+            ]
+        })
+    }
+
+    #[test]
+    fn normalizes_metadata() {
+        let info = VideoInfo::from_value(&sample()).unwrap();
+
+        assert_eq!(info.id, "dQw4w9WgXcQ");
+        assert_eq!(info.extractor.as_deref(), Some("Youtube"));
+        assert_eq!(info.duration_seconds, Some(213.0));
+        assert_eq!(info.uploader.as_deref(), Some("Rick Astley"));
+        assert_eq!(info.view_count, Some(1_500_000_000));
+        assert_eq!(info.thumbnails.len(), 1);
+
+        let audio = &info.formats[0];
+        assert!(audio.vcodec.is_none());
+        assert_eq!(audio.acodec.as_deref(), Some("opus"));
+        let video = &info.formats[1];
+        assert_eq!(video.height, Some(1080));
+        assert_eq!(video.filesize, Some(52_428_800));
+        assert!(video.acodec.is_none());
+    }
+
+    #[test]
+    fn metadata_without_an_id_is_rejected() {
+        assert!(VideoInfo::from_value(&json!({"title": "No id"})).is_none());
+    }
+
+    #[test]
+    fn cached_info_expires_after_ttl() {
+        let cache = InfoCache::default();
+        let fetched_at = Instant::now();
+        cache.insert_at(VideoInfo::from_value(&sample()).unwrap(), fetched_at);
+
+        assert!(cache.get_at("youtube-dQw4w9WgXcQ", fetched_at).is_some());
+        assert!(cache.get_at("dQw4w9WgXcQ", fetched_at).is_none());
+        assert!(cache.get_at("missing", fetched_at).is_none());
+        assert!(
+            cache
+                .get_at(
+                    "youtube-dQw4w9WgXcQ",
+                    fetched_at + INFO_TTL + Duration::from_secs(1)
+                )
+                .is_none()
+        );
+    }
+}
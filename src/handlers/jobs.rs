@@ -0,0 +1,505 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+use crate::handlers::meta::VideoDetails;
+use crate::handlers::ytdlp::DownloadProgress;
+
+/*
+ * In-memory registry of download jobs.
+ *
+ * POST /download registers a job and returns its ID straight away; the worker
+ * reports each stage here and GET /jobs/{id} reads it back. Every job keeps a
+ * watch channel so GET /jobs/{id}/events can stream changes as they happen, and
+ * a cancellation token that DELETE /jobs/{id} (or the timeout) trips to stop
+ * the worker. Once a job is finished its state is final.
+ * Playlist jobs own one child job per video and list them as items, which
+ * follow each child's status and progress as it runs; cancelling the playlist
+ * cancels every child that is still running.
+ * Finished jobs are forgotten after FINISHED_JOB_TTL so the map doesn't grow forever.
+ */
+
+const FINISHED_JOB_TTL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    FetchingMetadata,
+    Downloading,
+    Publishing,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    pub fn is_finished(self) -> bool {
+        matches!(
+            self,
+            JobStatus::Done | JobStatus::Failed | JobStatus::Cancelled
+        )
+    }
+}
+
+/// Public view of a job, as returned by GET /jobs/{id}.
+#[derive(Debug, Clone, Serialize)]
+pub struct JobSnapshot {
+    pub job_id: String,
+    pub status: JobStatus,
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub progress: Option<DownloadProgress>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thumbnail_url: Option<String>,
+    /// Subtitle sidecars published next to the file, when requested.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub subtitle_urls: Vec<String>,
+    /// Title, uploader, duration and source URL of the downloaded video.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub video: Option<VideoDetails>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub items: Option<Vec<BatchItem>>,
+}
+
+/// One video of a playlist job, mirrored from its own child job.
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchItem {
+    pub job_id: String,
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    pub status: JobStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub progress: Option<DownloadProgress>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Why the video was refused before downloading, e.g. "blocked_address".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<&'static str>,
+}
+
+/// Public URLs of a finished download.
+#[derive(Debug, Clone, Default)]
+pub struct PublishedFiles {
+    pub file_url: String,
+    pub thumbnail_url: Option<String>,
+    pub subtitle_urls: Vec<String>,
+    pub video: Option<VideoDetails>,
+}
+
+struct JobEntry {
+    snapshot: watch::Sender<JobSnapshot>,
+    cancel: CancellationToken,
+    finished_at: Option<Instant>,
+    children: Vec<String>,
+    parent: Option<String>,
+}
+
+#[derive(Clone, Default)]
+pub struct JobRegistry {
+    jobs: Arc<Mutex<HashMap<String, JobEntry>>>,
+}
+
+impl JobRegistry {
+    /// Registers a new queued job for `url` and returns a handle for the worker.
+    pub fn create(&self, url: &str) -> JobHandle {
+        self.insert(url, CancellationToken::new(), None)
+    }
+
+    /// Registers a queued job for one video of a playlist job.
+    pub fn create_child(&self, parent: &JobHandle, url: &str) -> JobHandle {
+        self.insert(url, parent.cancel.child_token(), Some(&parent.id))
+    }
+
+    fn insert(&self, url: &str, cancel: CancellationToken, parent: Option<&str>) -> JobHandle {
+        let job_id = Uuid::new_v4().to_string();
+
+        let mut jobs = self.lock();
+        prune_finished(&mut jobs, Instant::now());
+        if let Some(parent) = parent.and_then(|id| jobs.get_mut(id)) {
+            parent.children.push(job_id.clone());
+        }
+        jobs.insert(
+            job_id.clone(),
+            JobEntry {
+                snapshot: watch::Sender::new(JobSnapshot {
+                    job_id: job_id.clone(),
+                    status: JobStatus::Queued,
+                    url: url.to_string(),
+                    progress: None,
+                    file_url: None,
+                    thumbnail_url: None,
+                    subtitle_urls: Vec::new(),
+                    video: None,
+                    error: None,
+                    items: None,
+                }),
+                cancel: cancel.clone(),
+                finished_at: None,
+                children: Vec::new(),
+                parent: parent.map(str::to_string),
+            },
+        );
+
+        JobHandle {
+            id: job_id,
+            registry: self.clone(),
+            cancel,
+        }
+    }
+
+    pub fn get(&self, job_id: &str) -> Option<JobSnapshot> {
+        self.lock()
+            .get(job_id)
+            .map(|entry| entry.snapshot.borrow().clone())
+    }
+
+    /// Receives every change to the job until it is pruned.
+    pub fn subscribe(&self, job_id: &str) -> Option<watch::Receiver<JobSnapshot>> {
+        self.lock()
+            .get(job_id)
+            .map(|entry| entry.snapshot.subscribe())
+    }
+
+    /*
+     * Cancels a running job: trips its token so the worker kills yt-dlp and
+     * scrubs its temp files, and marks it cancelled right away (along with any
+     * unfinished children of a playlist job).
+     * Returns None for unknown jobs and Some(false) if it had already finished.
+     */
+    pub fn cancel(&self, job_id: &str) -> Option<bool> {
+        let mut jobs = self.lock();
+        let entry = jobs.get_mut(job_id)?;
+        if entry.finished_at.is_some() {
+            return Some(false);
+        }
+
+        let children = std::mem::take(&mut entry.children);
+        mark_cancelled(entry);
+        for child in children {
+            if let Some(child) = jobs.get_mut(&child) {
+                mark_cancelled(child);
+            }
+        }
+        Some(true)
+    }
+
+    fn update(&self, job_id: &str, apply: impl FnOnce(&mut JobSnapshot)) {
+        let mut jobs = self.lock();
+        let Some(entry) = jobs.get_mut(job_id) else {
+            return;
+        };
+        apply_update(entry, apply);
+
+        // A playlist's item follows its child job as it runs
+        let Some(parent) = entry.parent.clone() else {
+            return;
+        };
+        let child = entry.snapshot.borrow().clone();
+        if let Some(parent) = jobs.get_mut(&parent) {
+            apply_update(parent, |job| {
+                let mut items = job.items.iter_mut().flatten();
+                if let Some(item) = items.find(|item| item.job_id == child.job_id) {
+                    item.status = child.status;
+                    item.progress = child.progress;
+                    item.file_url = child.file_url;
+                    item.error = child.error;
+                }
+            });
+        }
+    }
+
+    // A poisoned lock only means another worker panicked mid-update; the map
+    // itself is still usable.
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, JobEntry>> {
+        self.jobs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn mark_cancelled(entry: &mut JobEntry) {
+    entry.cancel.cancel();
+    apply_update(entry, |job| {
+        job.status = JobStatus::Cancelled;
+        job.error = Some("Download cancelled".to_string());
+    });
+}
+
+// Applies a change unless the job already finished (late worker updates are dropped)
+fn apply_update(entry: &mut JobEntry, apply: impl FnOnce(&mut JobSnapshot)) {
+    if entry.finished_at.is_some() {
+        return;
+    }
+
+    entry.snapshot.send_modify(apply);
+    if entry.snapshot.borrow().status.is_finished() {
+        entry.finished_at = Some(Instant::now());
+    }
+}
+
+// Drops jobs that finished more than FINISHED_JOB_TTL ago
+fn prune_finished(jobs: &mut HashMap<String, JobEntry>, now: Instant) {
+    jobs.retain(|_, entry| {
+        entry
+            .finished_at
+            .is_none_or(|at| now.duration_since(at) < FINISHED_JOB_TTL)
+    });
+}
+
+/// Worker-side handle used to report progress on a single job.
+#[derive(Clone)]
+pub struct JobHandle {
+    id: String,
+    registry: JobRegistry,
+    cancel: CancellationToken,
+}
+
+impl JobHandle {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Tripped when the job is cancelled or times out; the worker should stop.
+    pub fn cancellation(&self) -> &CancellationToken {
+        &self.cancel
+    }
+
+    pub fn set_status(&self, status: JobStatus) {
+        self.registry.update(&self.id, |job| job.status = status);
+    }
+
+    pub fn set_progress(&self, progress: DownloadProgress) {
+        self.registry
+            .update(&self.id, |job| job.progress = Some(progress));
+    }
+
+    pub fn complete(&self, file_url: String) {
+        self.complete_download(PublishedFiles {
+            file_url,
+            ..PublishedFiles::default()
+        });
+    }
+
+    /// Marks the job done with the file and everything published alongside it.
+    pub fn complete_download(&self, files: PublishedFiles) {
+        self.registry.update(&self.id, |job| {
+            job.status = JobStatus::Done;
+            job.file_url = Some(files.file_url);
+            job.thumbnail_url = files.thumbnail_url;
+            job.subtitle_urls = files.subtitle_urls;
+            job.video = files.video;
+        });
+    }
+
+    pub fn fail(&self, error: String) {
+        self.registry.update(&self.id, |job| {
+            job.status = JobStatus::Failed;
+            job.error = Some(error);
+        });
+    }
+
+    /// Lists the videos of a playlist job.
+    pub fn set_items(&self, items: Vec<BatchItem>) {
+        self.registry
+            .update(&self.id, |job| job.items = Some(items));
+    }
+
+    /// Marks a playlist job done, summarizing any videos that failed.
+    pub fn complete_batch(&self) {
+        self.registry.update(&self.id, |job| {
+            let items = job.items.as_deref().unwrap_or_default();
+            let failed = items
+                .iter()
+                .filter(|item| item.status != JobStatus::Done)
+                .count();
+            job.status = JobStatus::Done;
+            if failed > 0 {
+                job.error = Some(format!("{failed} of {} videos failed", items.len()));
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_jobs_start_queued() {
+        let jobs = JobRegistry::default();
+        let job = jobs.create("https://example.com/video");
+
+        let snapshot = jobs.get(job.id()).expect("job should be registered");
+        assert_eq!(snapshot.status, JobStatus::Queued);
+        assert_eq!(snapshot.url, "https://example.com/video");
+        assert!(snapshot.file_url.is_none());
+        assert!(snapshot.error.is_none());
+    }
+
+    #[test]
+    fn handle_reports_stages_and_result() {
+        let jobs = JobRegistry::default();
+        let job = jobs.create("https://example.com/video");
+
+        job.set_status(JobStatus::Downloading);
+        assert_eq!(jobs.get(job.id()).unwrap().status, JobStatus::Downloading);
+
+        job.complete("http://localhost/files/id/video.mp4".to_string());
+        let snapshot = jobs.get(job.id()).unwrap();
+        assert_eq!(snapshot.status, JobStatus::Done);
+        assert_eq!(
+            snapshot.file_url.as_deref(),
+            Some("http://localhost/files/id/video.mp4")
+        );
+
+        let failed = jobs.create("https://example.com/other");
+        failed.fail("Download timeout".to_string());
+        let snapshot = jobs.get(failed.id()).unwrap();
+        assert_eq!(snapshot.status, JobStatus::Failed);
+        assert_eq!(snapshot.error.as_deref(), Some("Download timeout"));
+    }
+
+    #[test]
+    fn subscribers_see_progress_updates() {
+        let jobs = JobRegistry::default();
+        let job = jobs.create("https://example.com/video");
+        let mut events = jobs.subscribe(job.id()).expect("job should be registered");
+
+        job.set_progress(DownloadProgress {
+            percent: Some(50.0),
+            downloaded_bytes: 512,
+            total_bytes: Some(1024),
+            speed_bytes_per_sec: None,
+            eta_seconds: None,
+        });
+
+        assert!(events.has_changed().unwrap());
+        let snapshot = events.borrow_and_update().clone();
+        assert_eq!(snapshot.progress.unwrap().downloaded_bytes, 512);
+    }
+
+    #[test]
+    fn cancel_trips_token_and_freezes_job() {
+        let jobs = JobRegistry::default();
+        let job = jobs.create("https://example.com/video");
+        job.set_status(JobStatus::Downloading);
+
+        assert_eq!(jobs.cancel(job.id()), Some(true));
+        assert!(job.cancellation().is_cancelled());
+
+        // The worker's own failure report must not overwrite the cancellation
+        job.fail("Download error: Download cancelled".to_string());
+        let snapshot = jobs.get(job.id()).unwrap();
+        assert_eq!(snapshot.status, JobStatus::Cancelled);
+        assert_eq!(snapshot.error.as_deref(), Some("Download cancelled"));
+    }
+
+    #[test]
+    fn cancel_reports_finished_and_unknown_jobs() {
+        let jobs = JobRegistry::default();
+        let job = jobs.create("https://example.com/video");
+        job.complete("http://localhost/files/id/video.mp4".to_string());
+
+        assert_eq!(jobs.cancel(job.id()), Some(false));
+        assert!(!job.cancellation().is_cancelled());
+        assert_eq!(jobs.cancel("missing"), None);
+    }
+
+    #[test]
+    fn cancelling_a_playlist_cancels_its_unfinished_children() {
+        let jobs = JobRegistry::default();
+        let playlist = jobs.create("https://example.com/playlist");
+        let done = jobs.create_child(&playlist, "https://example.com/one");
+        let running = jobs.create_child(&playlist, "https://example.com/two");
+        done.complete("http://localhost/files/one/video.mp4".to_string());
+
+        assert_eq!(jobs.cancel(playlist.id()), Some(true));
+        assert!(running.cancellation().is_cancelled());
+        assert_eq!(jobs.get(running.id()).unwrap().status, JobStatus::Cancelled);
+        assert_eq!(jobs.get(done.id()).unwrap().status, JobStatus::Done);
+    }
+
+    #[test]
+    fn playlist_items_follow_children_and_summarize_failures() {
+        let jobs = JobRegistry::default();
+        let playlist = jobs.create("https://example.com/playlist");
+        let children: Vec<_> = ["https://example.com/one", "https://example.com/two"]
+            .into_iter()
+            .map(|url| jobs.create_child(&playlist, url))
+            .collect();
+        playlist.set_items(
+            children
+                .iter()
+                .map(|child| {
+                    let snapshot = jobs.get(child.id()).unwrap();
+                    BatchItem {
+                        job_id: snapshot.job_id,
+                        url: snapshot.url,
+                        title: None,
+                        status: snapshot.status,
+                        progress: None,
+                        file_url: None,
+                        error: None,
+                        error_code: None,
+                    }
+                })
+                .collect(),
+        );
+
+        children[0].set_status(JobStatus::Downloading);
+        children[0].set_progress(DownloadProgress {
+            percent: Some(25.0),
+            downloaded_bytes: 256,
+            total_bytes: Some(1024),
+            speed_bytes_per_sec: None,
+            eta_seconds: None,
+        });
+        let items = jobs.get(playlist.id()).unwrap().items.unwrap();
+        assert_eq!(items[0].status, JobStatus::Downloading);
+        assert_eq!(items[0].progress.unwrap().downloaded_bytes, 256);
+        assert_eq!(items[1].status, JobStatus::Queued);
+
+        children[0].complete("http://localhost/files/one/video.mp4".to_string());
+        children[1].fail("Download error: private video".to_string());
+        playlist.complete_batch();
+
+        let snapshot = jobs.get(playlist.id()).unwrap();
+        let items = snapshot.items.unwrap();
+        assert_eq!(snapshot.status, JobStatus::Done);
+        assert_eq!(snapshot.error.as_deref(), Some("1 of 2 videos failed"));
+        assert_eq!(
+            items[0].file_url.as_deref(),
+            Some("http://localhost/files/one/video.mp4")
+        );
+        assert_eq!(items[1].status, JobStatus::Failed);
+    }
+
+    #[test]
+    fn unknown_job_is_none() {
+        assert!(JobRegistry::default().get("missing").is_none());
+    }
+
+    #[test]
+    fn prunes_only_long_finished_jobs() {
+        let jobs = JobRegistry::default();
+        let running = jobs.create("https://example.com/running");
+        let finished = jobs.create("https://example.com/finished");
+        finished.complete("http://localhost/files/id/video.mp4".to_string());
+
+        let later = Instant::now() + FINISHED_JOB_TTL + Duration::from_secs(1);
+        prune_finished(&mut jobs.lock(), later);
+
+        assert!(jobs.get(running.id()).is_some());
+        assert!(jobs.get(finished.id()).is_none());
+    }
+}
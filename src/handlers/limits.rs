@@ -0,0 +1,288 @@
+use std::fmt;
+
+use crate::config::{Config, VideoQualityEnv};
+use crate::handlers::info::{FormatInfo, VideoInfo};
+use crate::handlers::options::{DownloadMode, DownloadOptions};
+use crate::handlers::ytdlp::height_cap;
+
+/*
+ * Per-video limits: MAX_DURATION_SECONDS and MAX_FILESIZE_BYTES.
+ *
+ * A single livestream VOD can fill the disk, so once metadata is fetched,
+ * check_video refuses videos over either limit before anything is downloaded.
+ * The size is only an estimate from the formats yt-dlp lists, and some sites
+ * list no sizes at all, so the download is watched as well: once its temp files
+ * grow past MAX_FILESIZE_BYTES, yt-dlp is stopped and the files are scrubbed.
+ * Clips count only their own section against both limits.
+ */
+
+/// Which limit a video broke.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LimitExceeded {
+    Duration { seconds: f64, max_seconds: u64 },
+    EstimatedSize { bytes: u64, max_bytes: u64 },
+    FileSize { max_bytes: u64 },
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Duration {
+                seconds,
+                max_seconds,
+            } => write!(
+                f,
+                "Video is {} long, over this server's limit of {}",
+                clock(*seconds),
+                clock(*max_seconds as f64)
+            ),
+            Self::EstimatedSize { bytes, max_bytes } => write!(
+                f,
+                "Video would be about {}, over this server's file size limit of {}",
+                human_bytes(*bytes),
+                human_bytes(*max_bytes)
+            ),
+            Self::FileSize { max_bytes } => write!(
+                f,
+                "Download grew past this server's file size limit of {} and was stopped",
+                human_bytes(*max_bytes)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+/// Refuses a video whose length or estimated size is over the configured limits.
+pub fn check_video(
+    config: &Config,
+    info: &VideoInfo,
+    options: &DownloadOptions,
+) -> Result<(), LimitExceeded> {
+    let max_seconds = config.max_duration_seconds;
+    if let Some(seconds) = wanted_seconds(info.duration_seconds, options)
+        .filter(|seconds| max_seconds > 0 && *seconds > max_seconds as f64)
+    {
+        return Err(LimitExceeded::Duration {
+            seconds,
+            max_seconds,
+        });
+    }
+
+    let max_bytes = config.max_filesize_bytes;
+    if let Some(bytes) =
+        estimated_size(info, options).filter(|bytes| max_bytes > 0 && *bytes > max_bytes)
+    {
+        return Err(LimitExceeded::EstimatedSize { bytes, max_bytes });
+    }
+    Ok(())
+}
+
+/// Refuses a download once `bytes` of it are on disk and that's over MAX_FILESIZE_BYTES.
+pub fn check_download_size(config: &Config, bytes: u64) -> Result<(), LimitExceeded> {
+    let max_bytes = config.max_filesize_bytes;
+    if max_bytes > 0 && bytes > max_bytes {
+        return Err(LimitExceeded::FileSize { max_bytes });
+    }
+    Ok(())
+}
+
+/*
+ * Rough size of what `options` will download, following format_selector: the
+ * tallest video under the height cap (the lowest for the worst quality) plus
+ * the best audio when that video has none. Formats without a listed size are
+ * estimated from their bitrate. None when nothing useful is listed.
+ */
+pub fn estimated_size(info: &VideoInfo, options: &DownloadOptions) -> Option<u64> {
+    let full = info.duration_seconds;
+    let size = |format: &FormatInfo| {
+        format
+            .filesize
+            .or_else(|| Some((format.tbr? * 125.0 * full?) as u64))
+    };
+    let best_audio = info
+        .formats
+        .iter()
+        .filter(|format| format.vcodec.is_none() && format.acodec.is_some())
+        .filter_map(size)
+        .max();
+    let with_audio = |format: &FormatInfo| {
+        let bytes = size(format)?;
+        match format.acodec {
+            Some(_) => Some(bytes),
+            None => Some(bytes + best_audio.unwrap_or_default()),
+        }
+    };
+
+    let bytes = match (&options.format_id, options.mode) {
+        (Some(ids), mode) => {
+            let picked = ids
+                .split('+')
+                .map(|id| info.formats.iter().find(|format| format.format_id == id))
+                .collect::<Option<Vec<_>>>()?;
+            match picked.as_slice() {
+                [format] if mode == DownloadMode::Video => with_audio(format)?,
+                formats => formats
+                    .iter()
+                    .map(|format| size(format))
+                    .sum::<Option<u64>>()?,
+            }
+        }
+        (None, DownloadMode::Audio) => best_audio?,
+        (None, DownloadMode::Video) => {
+            let cap = height_cap(options.video_quality, options.max_height);
+            let mut videos = info.formats.iter().filter(|format| {
+                format.vcodec.is_some()
+                    && format
+                        .height
+                        .is_some_and(|height| cap.is_none_or(|cap| height <= u64::from(cap)))
+            });
+            let video = if cap.is_none() && options.video_quality == VideoQualityEnv::Worst {
+                videos.min_by_key(|format| (format.height, size(format)))
+            } else {
+                videos.max_by_key(|format| (format.height, size(format)))
+            };
+            // Sites without video formats (e.g. SoundCloud) only have the audio to go on
+            match video {
+                Some(format) => with_audio(format)?,
+                None => best_audio?,
+            }
+        }
+    };
+
+    // A clip only fetches its share of the video
+    let share = match (options.clip, wanted_seconds(full, options), full) {
+        (Some(_), Some(wanted), Some(full)) if full > 0.0 => (wanted / full).min(1.0),
+        _ => 1.0,
+    };
+    Some((bytes as f64 * share) as u64)
+}
+
+/// Seconds of video the download covers: the clip's section, or the whole video.
+fn wanted_seconds(duration: Option<f64>, options: &DownloadOptions) -> Option<f64> {
+    let Some(clip) = options.clip else {
+        return duration;
+    };
+    let end = clip.end_ms.map(|end| end as f64 / 1000.0);
+    let end = end.into_iter().chain(duration).reduce(f64::min)?;
+    Some((end - clip.start_ms as f64 / 1000.0).max(0.0))
+}
+
+// "12:00:00"-style length for error messages
+fn clock(seconds: f64) -> String {
+    let seconds = seconds.round() as u64;
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+// Sizes in the units the web page shows them in
+fn human_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{bytes} B"),
+        _ => format!("{value:.1} {}", UNITS[unit]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::options::Clip;
+    use serde_json::json;
+
+    const MB: u64 = 1024 * 1024;
+    const GB_LIMIT: u64 = 1024 * MB;
+
+    // A two-hour stream: 1080p and 480p video-only formats plus one audio track
+    fn stream() -> VideoInfo {
+        VideoInfo::from_metadata(&json!({
+            "id": "vod1",
+            "extractor_key": "Twitch",
+            "duration": 7200.0,
+            "formats": [
+                {"format_id": "audio", "vcodec": "none", "acodec": "mp4a", "filesize": 100 * MB},
+                {"format_id": "480p", "height": 480, "vcodec": "avc1", "acodec": "none",
+                 "filesize": 400 * MB},
+                // 2000 kbit/s for two hours is 1.8 GB
+                {"format_id": "1080p", "height": 1080, "vcodec": "avc1", "acodec": "none",
+                 "tbr": 2000.0}
+            ]
+        }))
+        .unwrap()
+    }
+
+    fn limited(max_duration_seconds: u64, max_filesize_bytes: u64) -> Config {
+        Config {
+            max_duration_seconds,
+            max_filesize_bytes,
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn refuses_long_videos_unless_only_a_short_clip_is_wanted() {
+        let config = limited(3600, 0);
+        let mut options = DownloadOptions::from_config(&config);
+
+        let refused = check_video(&config, &stream(), &options).unwrap_err();
+        assert_eq!(
+            refused.to_string(),
+            "Video is 2:00:00 long, over this server's limit of 1:00:00"
+        );
+
+        options.clip = Clip::from_timestamps(Some("1:00:00"), Some("1:00:30")).unwrap();
+        assert_eq!(check_video(&config, &stream(), &options), Ok(()));
+        assert_eq!(check_video(&limited(0, 0), &stream(), &options), Ok(()));
+    }
+
+    #[test]
+    fn estimates_size_from_the_formats_the_options_would_pick() {
+        let config = Config::default();
+        let best = DownloadOptions::from_config(&config);
+        let low = DownloadOptions {
+            max_height: Some(480),
+            ..best.clone()
+        };
+        let audio = best.clone().audio_only(None);
+        let by_id = DownloadOptions {
+            format_id: Some("480p+audio".to_string()),
+            ..best.clone()
+        };
+
+        assert_eq!(
+            estimated_size(&stream(), &best),
+            Some(1_800_000_000 + 100 * MB)
+        );
+        assert_eq!(estimated_size(&stream(), &low), Some(500 * MB));
+        assert_eq!(estimated_size(&stream(), &audio), Some(100 * MB));
+        assert_eq!(estimated_size(&stream(), &by_id), Some(500 * MB));
+
+        let refused = check_video(&limited(0, GB_LIMIT), &stream(), &best).unwrap_err();
+        assert!(matches!(refused, LimitExceeded::EstimatedSize { .. }));
+        assert_eq!(check_video(&limited(0, GB_LIMIT), &stream(), &low), Ok(()));
+    }
+
+    #[test]
+    fn stops_downloads_that_grow_past_the_limit() {
+        let config = limited(0, GB_LIMIT);
+
+        assert_eq!(check_download_size(&config, GB_LIMIT), Ok(()));
+        assert_eq!(
+            check_download_size(&config, GB_LIMIT + 1)
+                .unwrap_err()
+                .to_string(),
+            "Download grew past this server's file size limit of 1.0 GB and was stopped"
+        );
+        assert_eq!(check_download_size(&Config::default(), u64::MAX), Ok(()));
+    }
+}
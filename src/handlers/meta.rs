@@ -0,0 +1,158 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::handlers::info::VideoInfo;
+use crate::utils::canonical_url::canonicalize_url;
+
+/*
+ * Sidecar metadata for a cached video: downloads/{video_id}/meta.json.
+ *
+ * Records where the video came from and what it is (URL and its canonical
+ * form, title, uploader, duration), plus one entry per published file with the
+ * variant, the format selector it was downloaded with and when. Written
+ * atomically (temp file, then rename) whenever a download is published; cache
+ * hits read it back so they can answer with the same details without asking
+ * yt-dlp again.
+ */
+
+pub const META_FILE_NAME: &str = "meta.json";
+
+/// What we know about a cached video, as shown on finished jobs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VideoDetails {
+    pub id: String,
+    /// URL the video was first requested with.
+    pub url: String,
+    /// `url` without trackers or mirror hosts, see canonicalize_url.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub canonical_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webpage_url: Option<String>,
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uploader: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extractor: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_seconds: Option<f64>,
+}
+
+impl VideoDetails {
+    pub fn from_info(url: &str, info: &VideoInfo) -> Self {
+        Self {
+            id: info.id.clone(),
+            url: url.to_string(),
+            canonical_url: Some(canonicalize_url(url)),
+            webpage_url: info.webpage_url.clone(),
+            title: info.title.clone(),
+            uploader: info.uploader.clone(),
+            extractor: info.extractor.clone(),
+            duration_seconds: info.duration_seconds,
+        }
+    }
+}
+
+/// One published file of a cached video.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileMeta {
+    /// Cache variant ("" for the default mp4).
+    pub variant: String,
+    /// yt-dlp format selector the file was downloaded with.
+    pub format: String,
+    pub size_bytes: u64,
+    /// Unix timestamp, in seconds.
+    pub downloaded_at: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VideoMeta {
+    #[serde(flatten)]
+    pub video: VideoDetails,
+    /// Keyed by file name.
+    #[serde(default)]
+    pub files: BTreeMap<String, FileMeta>,
+}
+
+impl VideoMeta {
+    pub fn new(video: VideoDetails) -> Self {
+        Self {
+            video,
+            files: BTreeMap::new(),
+        }
+    }
+}
+
+/// Reads a video directory's meta.json; None when it is missing or unreadable.
+pub fn read_meta(cache_dir: &Path) -> Option<VideoMeta> {
+    let json = std::fs::read(cache_dir.join(META_FILE_NAME)).ok()?;
+    serde_json::from_slice(&json).ok()
+}
+
+/// Replaces meta.json atomically, so readers never see a half-written file.
+/// `temp_tag` keeps concurrent writers (different jobs) off each other's temp files.
+pub fn write_meta(
+    cache_dir: &Path,
+    meta: &VideoMeta,
+    temp_tag: &str,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    // Hidden ".tmp." name, so an interrupted write is scrubbed like any temp download
+    let temp = cache_dir.join(format!(".meta.{temp_tag}.tmp.json"));
+    std::fs::write(&temp, serde_json::to_vec_pretty(meta)?)?;
+    if let Err(e) = std::fs::rename(&temp, cache_dir.join(META_FILE_NAME)) {
+        let _ = std::fs::remove_file(&temp);
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_support::TestDirectory;
+
+    #[test]
+    fn meta_round_trips_through_the_sidecar() {
+        let cache_dir = TestDirectory::new();
+        assert!(read_meta(&cache_dir).is_none());
+
+        let mut meta = VideoMeta::new(VideoDetails {
+            id: "dQw4w9WgXcQ".to_string(),
+            url: "https://youtu.be/dQw4w9WgXcQ".to_string(),
+            canonical_url: Some("https://youtu.be/dQw4w9WgXcQ".to_string()),
+            webpage_url: None,
+            title: "Never Gonna Give You Up".to_string(),
+            uploader: Some("Rick Astley".to_string()),
+            extractor: Some("Youtube".to_string()),
+            duration_seconds: Some(213.0),
+        });
+        meta.files.insert(
+            "Never_Gonna_Give_You_Up.mp4".to_string(),
+            FileMeta {
+                variant: String::new(),
+                format: "bv*+ba/b".to_string(),
+                size_bytes: 4096,
+                downloaded_at: 1_700_000_000,
+            },
+        );
+        write_meta(&cache_dir, &meta, "abcd1234").unwrap();
+
+        let names: Vec<_> = std::fs::read_dir(&cache_dir)
+            .unwrap()
+            .flatten()
+            .map(|entry| entry.file_name())
+            .collect();
+        let read_back = read_meta(&cache_dir);
+
+        assert_eq!(read_back, Some(meta));
+        assert_eq!(names, [META_FILE_NAME]);
+    }
+}
@@ -1,6 +1,26 @@
 /*
  * Module declaration for the downloader functionality.
  *
+ * Contains the cache module, which lists and evicts cached videos.
  * Contains the downloader module, which handles the actual video download process.
+ * Contains the inflight module, which makes concurrent requests for one video share a download.
+ * Contains the info module, which normalizes and caches video metadata.
+ * Contains the jobs module, which tracks the state of queued and running downloads.
+ * Contains the limits module, which enforces the maximum video length and file size.
+ * Contains the meta module, which reads and writes each cached video's meta.json.
+ * Contains the options module, which maps per-request choices to yt-dlp args and cache variants.
+ * Contains the rate_limit module, which caps request rates and running jobs per client.
+ * Contains the short_links module, which follows redirector links to the video they point at.
+ * Contains the ytdlp module, which runs the yt-dlp binary and parses its progress.
  */
+pub mod cache;
 pub mod downloader;
+pub mod inflight;
+pub mod info;
+pub mod jobs;
+pub mod limits;
+pub mod meta;
+pub mod options;
+pub mod rate_limit;
+pub mod short_links;
+pub mod ytdlp;
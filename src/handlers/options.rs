@@ -0,0 +1,654 @@
+use std::ffi::OsString;
+use strum_macros::{EnumString, IntoStaticStr};
+
+use crate::config::{
+    AudioCodecPreferenceEnv, AudioQualityEnv, Config, VideoCodecPreferenceEnv, VideoQualityEnv,
+};
+use crate::handlers::info::VideoInfo;
+use crate::handlers::ytdlp::{audio_quality_level, audio_selector, format_selector};
+
+// The preferences stored under the plain cache names. They match Config's
+// defaults and must never change, or every existing cache entry is mislabeled.
+const CANONICAL_VIDEO: (VideoQualityEnv, VideoCodecPreferenceEnv) =
+    (VideoQualityEnv::Best, VideoCodecPreferenceEnv::VP9);
+const CANONICAL_AUDIO: (AudioQualityEnv, AudioCodecPreferenceEnv) =
+    (AudioQualityEnv::Best, AudioCodecPreferenceEnv::Opus);
+
+/*
+ * Per-request download options.
+ *
+ * Starts from the server's configured preferences and carries whatever the
+ * request asked for on top: audio-only output, quality and codec overrides, an
+ * exact yt-dlp format ID or a height cap, a time range to clip, and subtitles.
+ * Every combination that produces a different file gets its own cache variant,
+ * stored next to the default copy as `{title}.{variant}.{ext}` inside
+ * downloads/{video_id}/. Subtitle sidecars don't change the media file, so they
+ * sit beside it as `{title}.{lang}.{vtt|srt}` and are shared by every variant.
+ */
+
+/// Extensions our published downloads can have.
+const MEDIA_EXTENSIONS: [&str; 5] = ["mp4", "mp3", "m4a", "opus", "flac"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum DownloadMode {
+    #[default]
+    Video,
+    Audio,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString, IntoStaticStr)]
+#[strum(serialize_all = "lowercase")]
+pub enum AudioFormat {
+    Mp3,
+    M4a,
+    Opus,
+    Flac,
+}
+
+impl AudioFormat {
+    /// The container that keeps the preferred codec without re-encoding, where there is one.
+    fn for_codec(codec: AudioCodecPreferenceEnv) -> Self {
+        match codec {
+            AudioCodecPreferenceEnv::Opus => AudioFormat::Opus,
+            AudioCodecPreferenceEnv::Aac => AudioFormat::M4a,
+            AudioCodecPreferenceEnv::MP3 | AudioCodecPreferenceEnv::Any => AudioFormat::Mp3,
+        }
+    }
+}
+
+/// Which subtitle tracks to fetch: uploaded ones, auto-generated captions, or both.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum SubtitleSource {
+    #[default]
+    Manual,
+    Auto,
+    All,
+}
+
+/// Where subtitles end up: muxed into the mp4, or published as sidecar files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString, IntoStaticStr)]
+#[strum(serialize_all = "lowercase")]
+pub enum SubtitleOutput {
+    Embed,
+    Vtt,
+    Srt,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subtitles {
+    /// Language codes as yt-dlp knows them, e.g. "en" or "pt-BR".
+    pub languages: Vec<String>,
+    pub source: SubtitleSource,
+    pub output: SubtitleOutput,
+}
+
+impl Subtitles {
+    /// Extension of the sidecar files, or None when the subtitles are embedded.
+    pub fn sidecar_extension(&self) -> Option<&'static str> {
+        match self.output {
+            SubtitleOutput::Embed => None,
+            output => Some(output.into()),
+        }
+    }
+
+    /// yt-dlp arguments that fetch (and, for sidecars, convert) the subtitles.
+    pub fn args(&self) -> Vec<OsString> {
+        let mut args: Vec<OsString> = Vec::new();
+        if self.source != SubtitleSource::Auto {
+            args.push("--write-subs".into());
+        }
+        if self.source != SubtitleSource::Manual {
+            args.push("--write-auto-subs".into());
+        }
+        args.extend(["--sub-langs".into(), self.languages.join(",").into()]);
+        match self.sidecar_extension() {
+            None => args.push("--embed-subs".into()),
+            Some(ext) => args.extend(["--convert-subs".into(), ext.into()]),
+        }
+        args
+    }
+
+    /// Cache variant part for embedded subtitles, e.g. "subs-en-pt_BR-auto".
+    fn variant(&self) -> String {
+        let mut parts = vec!["subs".to_string()];
+        parts.extend(self.languages.iter().map(|language| file_safe(language)));
+        if self.source != SubtitleSource::Manual {
+            parts.push(lower(self.source));
+        }
+        parts.join("-")
+    }
+}
+
+/// True for a subtitle language code yt-dlp accepts, e.g. "en", "en-US" or "zh-Hans".
+pub fn is_valid_language(language: &str) -> bool {
+    !language.is_empty()
+        && language.len() <= 20
+        && !language.starts_with('-')
+        && language
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+/// A section of the video to keep, in milliseconds. An open end runs to the end of the video.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Clip {
+    pub start_ms: u64,
+    pub end_ms: Option<u64>,
+}
+
+impl Clip {
+    /// Builds a clip from optional start/end timestamps; None when neither is given.
+    pub fn from_timestamps(start: Option<&str>, end: Option<&str>) -> Result<Option<Self>, String> {
+        let parse = |field: &str, value: Option<&str>| {
+            value
+                .map(|value| {
+                    parse_timestamp(value).ok_or_else(|| {
+                        format!("Invalid {field} '{value}'. Use seconds (90.5) or [HH:]MM:SS[.mmm]")
+                    })
+                })
+                .transpose()
+        };
+        let start_ms = parse("start", start)?;
+        let end_ms = parse("end", end)?;
+        if start_ms.is_none() && end_ms.is_none() {
+            return Ok(None);
+        }
+
+        let clip = Self {
+            start_ms: start_ms.unwrap_or(0),
+            end_ms,
+        };
+        if clip.end_ms.is_some_and(|end| end <= clip.start_ms) {
+            return Err("end must be after start".to_string());
+        }
+        Ok(Some(clip))
+    }
+
+    /// yt-dlp's --download-sections value, e.g. "*90-120.5" or "*90-inf".
+    fn section(&self) -> String {
+        let end = self
+            .end_ms
+            .map_or_else(|| "inf".to_string(), |end| seconds(end, "."));
+        format!("*{}-{end}", seconds(self.start_ms, "."))
+    }
+
+    /// Cache variant part, e.g. "clip-90-120_5" or "clip-90-end".
+    fn variant(&self) -> String {
+        let end = self
+            .end_ms
+            .map_or_else(|| "end".to_string(), |end| seconds(end, "_"));
+        format!("clip-{}-{end}", seconds(self.start_ms, "_"))
+    }
+}
+
+/// Parses "90", "90.5", "1:30" or "01:02:03.250" into milliseconds.
+fn parse_timestamp(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let parts: Vec<&str> = value.split(':').collect();
+    if value.is_empty() || parts.len() > 3 {
+        return None;
+    }
+
+    let (whole, seconds) = parts.split_at(parts.len() - 1);
+    let seconds = seconds[0];
+    // Only the seconds part may carry a fraction, and minutes/seconds wrap at 60
+    if !seconds.chars().all(|c| c.is_ascii_digit() || c == '.') {
+        return None;
+    }
+    let seconds: f64 = seconds.parse().ok()?;
+    if !seconds.is_finite() || (parts.len() > 1 && seconds >= 60.0) {
+        return None;
+    }
+
+    let mut total_ms = (seconds * 1000.0).round() as u64;
+    for (unit, part) in whole.iter().rev().enumerate() {
+        if !part.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let value: u64 = part.parse().ok()?;
+        if unit == 0 && parts.len() == 3 && value >= 60 {
+            return None;
+        }
+        let unit_ms = 60_000 * 60u64.pow(unit as u32);
+        total_ms = total_ms.checked_add(value.checked_mul(unit_ms)?)?;
+    }
+    Some(total_ms)
+}
+
+// Whole seconds print bare; fractions keep milliseconds behind `point`
+fn seconds(ms: u64, point: &str) -> String {
+    match ms % 1000 {
+        0 => format!("{}", ms / 1000),
+        fraction => {
+            let fraction = format!("{fraction:03}");
+            format!("{}{point}{}", ms / 1000, fraction.trim_end_matches('0'))
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DownloadOptions {
+    pub mode: DownloadMode,
+    pub audio_format: AudioFormat,
+    pub video_quality: VideoQualityEnv,
+    pub video_codec: VideoCodecPreferenceEnv,
+    pub audio_quality: AudioQualityEnv,
+    pub audio_codec: AudioCodecPreferenceEnv,
+    /// Exact yt-dlp format ID (e.g. "137" or "137+251"); wins over everything above.
+    pub format_id: Option<String>,
+    /// Maximum video height in pixels (e.g. 720), in place of the quality preset's cap.
+    pub max_height: Option<u32>,
+    /// Only download this section of the video.
+    pub clip: Option<Clip>,
+    pub subtitles: Option<Subtitles>,
+}
+
+impl DownloadOptions {
+    /// The server's default download: an mp4 at the configured quality and codecs.
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            mode: DownloadMode::Video,
+            audio_format: AudioFormat::for_codec(config.audio_codec),
+            video_quality: config.video_quality,
+            video_codec: config.video_codec,
+            audio_quality: config.audio_quality,
+            audio_codec: config.audio_codec,
+            format_id: None,
+            max_height: None,
+            clip: None,
+            subtitles: None,
+        }
+    }
+
+    /// Switches to audio-only; without an explicit format, follows the codec preference.
+    pub fn audio_only(mut self, format: Option<AudioFormat>) -> Self {
+        self.mode = DownloadMode::Audio;
+        self.audio_format = format.unwrap_or_else(|| AudioFormat::for_codec(self.audio_codec));
+        self
+    }
+
+    /// Extension of the published file.
+    pub fn extension(&self) -> &'static str {
+        match self.mode {
+            DownloadMode::Video => "mp4",
+            DownloadMode::Audio => self.audio_format.into(),
+        }
+    }
+
+    /*
+     * Cache variant for these options. Names don't depend on the server's current
+     * preferences: only the canonical combination (CANONICAL_VIDEO and
+     * CANONICAL_AUDIO) keeps the plain `{title}.mp4`, and every other one spells
+     * out its quality and codecs, so changing VIDEO_QUALITY never passes an old
+     * copy off as the new default.
+     * Audio downloads are always a variant ("audio", plus quality and codec unless
+     * canonical); the format itself is told apart by the extension. Format IDs
+     * and height caps add "f{id}" and "{height}p", clips "clip-{start}-{end}"
+     * and embedded subtitles "subs-{langs}".
+     */
+    pub fn variant_key(&self) -> String {
+        let audio_differs = (self.audio_quality, self.audio_codec) != CANONICAL_AUDIO;
+
+        let mut parts = Vec::new();
+        match self.mode {
+            DownloadMode::Audio => {
+                parts.push("audio".to_string());
+                if audio_differs {
+                    parts.push(lower(self.audio_quality));
+                    parts.push(lower(self.audio_codec));
+                }
+            }
+            // An exact format ID already pins the video, whatever the preferences
+            DownloadMode::Video if self.format_id.is_some() => {}
+            DownloadMode::Video => {
+                if audio_differs || (self.video_quality, self.video_codec) != CANONICAL_VIDEO {
+                    parts.push(lower(self.video_quality));
+                    parts.push(lower(self.video_codec));
+                    parts.push(lower(self.audio_quality));
+                    parts.push(lower(self.audio_codec));
+                }
+            }
+        }
+        if let Some(format_id) = &self.format_id {
+            parts.push(format!("f{}", file_safe(format_id)));
+        }
+        if let Some(height) = self.max_height {
+            parts.push(format!("{height}p"));
+        }
+        if let Some(clip) = &self.clip {
+            parts.push(clip.variant());
+        }
+        if let Some(subtitles) = self.embedded_subtitles() {
+            parts.push(subtitles.variant());
+        }
+        parts.join("-")
+    }
+
+    /// Subtitles to fetch as sidecar files next to the media, if any.
+    pub fn sidecar_subtitles(&self) -> Option<&Subtitles> {
+        self.subtitles
+            .as_ref()
+            .filter(|subtitles| subtitles.output != SubtitleOutput::Embed)
+    }
+
+    fn embedded_subtitles(&self) -> Option<&Subtitles> {
+        self.subtitles
+            .as_ref()
+            .filter(|subtitles| subtitles.output == SubtitleOutput::Embed)
+    }
+
+    /// yt-dlp format selector for these options.
+    pub fn format_selector(&self) -> String {
+        match (&self.format_id, self.mode) {
+            // Only a video-only format gets the best audio merged in; the filter
+            // fails for one that has its own, so that is downloaded as it is
+            (Some(id), DownloadMode::Video) if !id.contains('+') => {
+                format!("{id}[acodec=none]+ba/{id}")
+            }
+            (Some(id), _) => id.clone(),
+            (None, DownloadMode::Video) => format_selector(
+                self.video_quality,
+                self.max_height,
+                self.video_codec,
+                self.audio_quality,
+                self.audio_codec,
+            ),
+            (None, DownloadMode::Audio) => audio_selector(self.audio_quality, self.audio_codec),
+        }
+    }
+
+    /// Refuses an exact format that can't make a video: an audio-only one in video mode.
+    pub fn check_format(&self, info: &VideoInfo) -> Result<(), String> {
+        let Some(id) = self.format_id.as_deref().filter(|id| !id.contains('+')) else {
+            return Ok(());
+        };
+        let audio_only = info
+            .formats
+            .iter()
+            .filter(|format| format.format_id == id)
+            .any(|format| format.vcodec.is_none() && format.acodec.is_some());
+        if self.mode == DownloadMode::Video && audio_only {
+            return Err(format!(
+                "Format {id} is audio only; send \"mode\": \"audio\" to download it"
+            ));
+        }
+        Ok(())
+    }
+
+    /*
+     * Output arguments: merge into mp4, or extract and convert the audio.
+     * Clips only download their section; ffmpeg (from libs/) re-encodes around
+     * the cut points so the clip starts exactly where asked, not at a keyframe.
+     * Embedded subtitles are muxed into the mp4 as it is merged.
+     */
+    pub fn output_args(&self) -> Vec<OsString> {
+        let mut args: Vec<OsString> = match self.mode {
+            DownloadMode::Video => vec!["--merge-output-format".into(), "mp4".into()],
+            DownloadMode::Audio => vec![
+                "--extract-audio".into(),
+                "--audio-format".into(),
+                self.extension().into(),
+                "--audio-quality".into(),
+                audio_quality_level(self.audio_quality).into(),
+            ],
+        };
+        if let Some(clip) = &self.clip {
+            args.extend([
+                "--download-sections".into(),
+                clip.section().into(),
+                "--force-keyframes-at-cuts".into(),
+            ]);
+        }
+        // Sidecars are fetched on their own, so cached media can gain them later
+        if let Some(subtitles) = self.embedded_subtitles() {
+            args.extend(subtitles.args());
+        }
+        args
+    }
+}
+
+/// True for the extension of a published download (mp4 or one of the audio formats).
+pub fn is_media_extension(ext: &str) -> bool {
+    MEDIA_EXTENSIONS.contains(&ext)
+}
+
+/// True for a plausible yt-dlp format ID: "137", "hls-720p", or "137+251" to merge two.
+pub fn is_valid_format_id(format_id: &str) -> bool {
+    !format_id.is_empty()
+        && format_id.len() <= 64
+        && format_id.split('+').all(|part| {
+            !part.is_empty()
+                && !part.starts_with('-')
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+        })
+}
+
+fn lower(value: impl std::fmt::Debug) -> String {
+    format!("{value:?}").to_lowercase()
+}
+
+// Cache file names can't contain dots (they separate title, variant and extension)
+fn file_safe(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn default_video_keeps_the_plain_cache_name() {
+        let config = Config::default();
+        let options = DownloadOptions::from_config(&config);
+
+        assert_eq!(options.variant_key(), "");
+        assert_eq!(options.extension(), "mp4");
+        assert_eq!(
+            options.output_args(),
+            [
+                OsString::from("--merge-output-format"),
+                OsString::from("mp4")
+            ]
+        );
+    }
+
+    #[test]
+    fn audio_downloads_are_their_own_variant() {
+        let config = Config::default();
+        let options = DownloadOptions::from_config(&config).audio_only(None);
+
+        // Default codec preference is Opus, so the format follows it
+        assert_eq!(options.extension(), "opus");
+        assert_eq!(options.variant_key(), "audio");
+        assert_eq!(options.format_selector(), "ba[acodec^=opus]/ba/b");
+
+        let flac = DownloadOptions::from_config(&config).audio_only(Some(AudioFormat::Flac));
+        assert_eq!(flac.extension(), "flac");
+        assert!(
+            flac.output_args()
+                .windows(2)
+                .any(|w| w[0] == "--audio-format" && w[1] == "flac")
+        );
+    }
+
+    #[test]
+    fn overrides_get_their_own_variant() {
+        let config = Config::default();
+        let low = DownloadOptions {
+            video_quality: VideoQualityEnv::Low,
+            video_codec: VideoCodecPreferenceEnv::Any,
+            ..DownloadOptions::from_config(&config)
+        };
+        assert_eq!(low.variant_key(), "low-any-best-opus");
+
+        let audio = DownloadOptions {
+            audio_quality: AudioQualityEnv::Medium,
+            ..DownloadOptions::from_config(&config)
+        }
+        .audio_only(Some(AudioFormat::Mp3));
+        assert_eq!(audio.variant_key(), "audio-medium-opus");
+
+        // Names are absolute: a server defaulting to Low still names its files for
+        // Low, and an explicit Best request still finds the plain copy
+        let low_server = Config {
+            video_quality: VideoQualityEnv::Low,
+            ..Config::default()
+        };
+        let server_default = DownloadOptions::from_config(&low_server);
+        assert_eq!(server_default.variant_key(), "low-vp9-best-opus");
+        let best = DownloadOptions {
+            video_quality: VideoQualityEnv::Best,
+            ..server_default.clone()
+        };
+        assert_eq!(best.variant_key(), "");
+        let exact = DownloadOptions {
+            format_id: Some("137".to_string()),
+            ..server_default
+        };
+        assert_eq!(exact.variant_key(), "f137");
+
+        // Video settings don't change an audio-only file
+        let same_audio = DownloadOptions {
+            video_quality: VideoQualityEnv::Worst,
+            ..DownloadOptions::from_config(&config)
+        }
+        .audio_only(None);
+        assert_eq!(same_audio.variant_key(), "audio");
+    }
+
+    #[test]
+    fn format_ids_and_height_caps_pick_formats_and_variants() {
+        let config = Config::default();
+        let exact = DownloadOptions {
+            format_id: Some("137".to_string()),
+            ..DownloadOptions::from_config(&config)
+        };
+        assert_eq!(exact.format_selector(), "137[acodec=none]+ba/137");
+        assert_eq!(exact.variant_key(), "f137");
+
+        // An audio-only format can't make a video, only an audio download
+        let info = VideoInfo::from_metadata(&serde_json::json!({
+            "id": "dQw4w9WgXcQ",
+            "formats": [
+                {"format_id": "137", "vcodec": "avc1", "acodec": "none"},
+                {"format_id": "251", "vcodec": "none", "acodec": "opus"}
+            ]
+        }))
+        .unwrap();
+        let audio_id = DownloadOptions {
+            format_id: Some("251".to_string()),
+            ..DownloadOptions::from_config(&config)
+        };
+        assert_eq!(exact.check_format(&info), Ok(()));
+        assert!(audio_id.check_format(&info).is_err());
+        assert_eq!(audio_id.audio_only(None).check_format(&info), Ok(()));
+
+        let merged = DownloadOptions {
+            format_id: Some("hls-720p.1+251".to_string()),
+            ..DownloadOptions::from_config(&config)
+        };
+        assert_eq!(merged.format_selector(), "hls-720p.1+251");
+        assert_eq!(merged.variant_key(), "fhls_720p_1_251");
+
+        let capped = DownloadOptions {
+            max_height: Some(720),
+            ..DownloadOptions::from_config(&config)
+        };
+        assert!(capped.format_selector().starts_with("bv*[height<=720]"));
+        assert_eq!(capped.variant_key(), "720p");
+
+        assert!(is_valid_format_id("137+251"));
+        assert!(!is_valid_format_id("137/best"));
+        assert!(!is_valid_format_id("--exec"));
+    }
+
+    #[test]
+    fn clips_download_a_section_and_are_keyed_by_range() {
+        let config = Config::default();
+        let clip = Clip::from_timestamps(Some("1:30"), Some("120.5")).unwrap();
+        assert_eq!(
+            clip,
+            Some(Clip {
+                start_ms: 90_000,
+                end_ms: Some(120_500)
+            })
+        );
+
+        let options = DownloadOptions {
+            clip,
+            ..DownloadOptions::from_config(&config)
+        };
+        assert_eq!(options.variant_key(), "clip-90-120_5");
+        assert!(
+            options
+                .output_args()
+                .windows(2)
+                .any(|w| w[0] == "--download-sections" && w[1] == "*90-120.5")
+        );
+
+        let open_ended = Clip::from_timestamps(Some("01:00:00"), None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(open_ended.section(), "*3600-inf");
+        assert_eq!(open_ended.variant(), "clip-3600-end");
+
+        assert_eq!(Clip::from_timestamps(None, None), Ok(None));
+        assert!(Clip::from_timestamps(Some("90"), Some("30")).is_err());
+        assert!(Clip::from_timestamps(Some("1:75"), None).is_err());
+        assert!(Clip::from_timestamps(Some("-5"), None).is_err());
+    }
+
+    #[test]
+    fn embedded_subtitles_are_a_variant_and_sidecars_are_not() {
+        let config = Config::default();
+        let subtitles = |output| Subtitles {
+            languages: vec!["en".to_string(), "pt-BR".to_string()],
+            source: SubtitleSource::All,
+            output,
+        };
+
+        let embedded = DownloadOptions {
+            subtitles: Some(subtitles(SubtitleOutput::Embed)),
+            ..DownloadOptions::from_config(&config)
+        };
+        assert_eq!(embedded.variant_key(), "subs-en-pt_BR-all");
+        let args = embedded.output_args();
+        assert!(args.iter().any(|arg| arg == "--embed-subs"));
+        assert!(args.iter().any(|arg| arg == "--write-auto-subs"));
+        assert!(
+            args.windows(2)
+                .any(|w| w[0] == "--sub-langs" && w[1] == "en,pt-BR")
+        );
+
+        let sidecar = DownloadOptions {
+            subtitles: Some(subtitles(SubtitleOutput::Srt)),
+            ..DownloadOptions::from_config(&config)
+        };
+        assert_eq!(sidecar.variant_key(), "");
+        assert!(!sidecar.output_args().iter().any(|arg| arg == "--sub-langs"));
+        let sidecar_args = subtitles(SubtitleOutput::Srt).args();
+        assert!(
+            sidecar_args
+                .windows(2)
+                .any(|w| w[0] == "--convert-subs" && w[1] == "srt")
+        );
+
+        assert!(is_valid_language("zh-Hans"));
+        assert!(!is_valid_language("en,--exec"));
+    }
+
+    #[test]
+    fn audio_formats_parse_lowercase() {
+        assert_eq!(AudioFormat::from_str("m4a"), Ok(AudioFormat::M4a));
+        assert_eq!(DownloadMode::from_str("audio"), Ok(DownloadMode::Audio));
+        assert!(AudioFormat::from_str("wav").is_err());
+    }
+}
@@ -0,0 +1,380 @@
+use axum::extract::{ConnectInfo, FromRequestParts};
+use axum::http::{HeaderMap, request::Parts};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::fmt;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::state::AppState;
+
+/*
+ * Per-client limits on POST /download.
+ *
+ * A client is its API key when the server requires keys, otherwise its IP
+ * address. Web page users all log in with the same WEB_UI_SECRET, so they are
+ * told apart by address too. With TRUST_PROXY_HEADERS that is the last
+ * X-Forwarded-For entry, the one our proxy appended; anything before it came
+ * from the client. Each client gets a token bucket holding RATE_LIMIT_BURST
+ * requests that refills at RATE_LIMIT_PER_MINUTE, and may run at most
+ * MAX_JOBS_PER_CLIENT jobs at once; a playlist counts as one job. Either limit
+ * set to 0 is off.
+ *
+ * Wrong keys get a bucket of their own, whatever the settings above: five in a
+ * row, at login or on any other request, then one more a minute. It is kept per
+ * address, since a guessed key would otherwise start a fresh bucket. Only
+ * failures spend from it, so a user who mistyped once isn't held up.
+ *
+ * Buckets that have refilled completely are forgotten, so the map only holds
+ * clients that were active recently.
+ */
+
+// Hint for clients at their job cap; jobs have no predictable end
+const JOB_CAP_RETRY_AFTER: Duration = Duration::from_secs(10);
+
+// Wrong keys a client may try before it has to wait, and how fast that refills
+const LOGIN_FAILURE_BURST: f64 = 5.0;
+const LOGIN_FAILURES_PER_SECOND: f64 = 1.0 / 60.0;
+
+/// Who a request counts against: an API key or an IP address.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ClientId(String);
+
+impl ClientId {
+    /// Identifies a request by its API key, falling back to its address.
+    pub fn from_request(state: &AppState, headers: &HeaderMap, peer: Option<IpAddr>) -> Self {
+        let keys = &state.api_keys;
+        if let Some(key) = keys.presented(headers).filter(|key| !keys.is_shared(key)) {
+            // Never keep or log the key itself
+            let mut hasher = DefaultHasher::new();
+            key.hash(&mut hasher);
+            return Self(format!("key-{:016x}", hasher.finish()));
+        }
+        Self::from_address(&state.config, headers, peer)
+    }
+
+    /// Identifies a request by its address alone, whatever key it carries.
+    pub fn from_address(config: &Config, headers: &HeaderMap, peer: Option<IpAddr>) -> Self {
+        let forwarded = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .next_back()
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.rsplit(',').next())
+            .and_then(|last| last.trim().parse::<IpAddr>().ok())
+            .filter(|_| config.trust_proxy_headers);
+        match forwarded.or(peer) {
+            Some(ip) => Self(format!("ip-{ip}")),
+            None => Self("unknown".to_string()),
+        }
+    }
+}
+
+impl fmt::Display for ClientId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromRequestParts<AppState> for ClientId {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Infallible> {
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+        Ok(Self::from_request(state, &parts.headers, peer))
+    }
+}
+
+/// Why a client was turned away, and when it may try again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Throttled {
+    RateLimited { retry_after: Duration },
+    TooManyJobs { max_jobs: usize },
+    TooManyLogins { retry_after: Duration },
+}
+
+impl Throttled {
+    pub fn retry_after(&self) -> Duration {
+        match self {
+            Self::RateLimited { retry_after } | Self::TooManyLogins { retry_after } => *retry_after,
+            Self::TooManyJobs { .. } => JOB_CAP_RETRY_AFTER,
+        }
+    }
+}
+
+impl fmt::Display for Throttled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::RateLimited { retry_after } => write!(
+                f,
+                "Too many download requests, try again in {}s",
+                retry_after.as_secs()
+            ),
+            Self::TooManyJobs { max_jobs } => write!(
+                f,
+                "You already have {max_jobs} downloads running, wait for one to finish"
+            ),
+            Self::TooManyLogins { retry_after } => write!(
+                f,
+                "Too many wrong keys, try again in {}s",
+                retry_after.as_secs()
+            ),
+        }
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl Bucket {
+    // How long until the bucket holds a whole token again
+    fn wait(&self, per_second: f64) -> Duration {
+        Duration::from_secs(((1.0 - self.tokens) / per_second).ceil() as u64)
+    }
+}
+
+/// Buckets and running job counts for every client, shared by every request.
+#[derive(Clone, Default)]
+pub struct ClientLimits {
+    buckets: Arc<Mutex<HashMap<ClientId, Bucket>>>,
+    running: Arc<Mutex<HashMap<ClientId, usize>>>,
+    failed_logins: Arc<Mutex<HashMap<ClientId, Bucket>>>,
+}
+
+/// One of a client's job slots, given back when dropped.
+pub struct JobSlot {
+    client: ClientId,
+    running: Arc<Mutex<HashMap<ClientId, usize>>>,
+}
+
+impl Drop for JobSlot {
+    fn drop(&mut self) {
+        let mut running = lock(&self.running);
+        if let Some(count) = running.get_mut(&self.client) {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                running.remove(&self.client);
+            }
+        }
+    }
+}
+
+impl ClientLimits {
+    /// Takes a token from the client's bucket, or says how long until one refills.
+    pub fn check_rate(&self, config: &Config, client: &ClientId) -> Result<(), Throttled> {
+        if config.rate_limit_per_minute == 0 {
+            return Ok(());
+        }
+        let capacity = f64::from(config.rate_limit_burst.max(1));
+        let per_second = f64::from(config.rate_limit_per_minute) / 60.0;
+
+        let mut buckets = lock(&self.buckets);
+        let bucket = refilled(&mut buckets, client, capacity, per_second);
+        if bucket.tokens < 1.0 {
+            return Err(Throttled::RateLimited {
+                retry_after: bucket.wait(per_second),
+            });
+        }
+        bucket.tokens -= 1.0;
+        Ok(())
+    }
+
+    /// Refuses a key check while the client has used up its tries for wrong keys.
+    pub fn check_login(&self, client: &ClientId) -> Result<(), Throttled> {
+        let mut failed = lock(&self.failed_logins);
+        let bucket = refilled(
+            &mut failed,
+            client,
+            LOGIN_FAILURE_BURST,
+            LOGIN_FAILURES_PER_SECOND,
+        );
+        if bucket.tokens < 1.0 {
+            return Err(Throttled::TooManyLogins {
+                retry_after: bucket.wait(LOGIN_FAILURES_PER_SECOND),
+            });
+        }
+        Ok(())
+    }
+
+    /// Counts a wrong key, at login or on any other request, against the client.
+    pub fn record_failed_login(&self, client: &ClientId) {
+        let mut failed = lock(&self.failed_logins);
+        let bucket = refilled(
+            &mut failed,
+            client,
+            LOGIN_FAILURE_BURST,
+            LOGIN_FAILURES_PER_SECOND,
+        );
+        bucket.tokens = (bucket.tokens - 1.0).max(0.0);
+    }
+
+    /// Claims one of the client's job slots for as long as the returned slot lives.
+    pub fn claim_job(&self, config: &Config, client: &ClientId) -> Result<JobSlot, Throttled> {
+        let mut running = lock(&self.running);
+        let count = running.entry(client.clone()).or_default();
+        if config.max_jobs_per_client > 0 && *count >= config.max_jobs_per_client {
+            return Err(Throttled::TooManyJobs {
+                max_jobs: config.max_jobs_per_client,
+            });
+        }
+        *count += 1;
+
+        Ok(JobSlot {
+            client: client.clone(),
+            running: self.running.clone(),
+        })
+    }
+}
+
+/// The client's bucket topped up to now, after forgetting every full bucket.
+fn refilled<'a>(
+    buckets: &'a mut HashMap<ClientId, Bucket>,
+    client: &ClientId,
+    capacity: f64,
+    per_second: f64,
+) -> &'a mut Bucket {
+    let now = Instant::now();
+    let refill = |bucket: &Bucket| {
+        let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+        (bucket.tokens + elapsed * per_second).min(capacity)
+    };
+    buckets.retain(|_, bucket| refill(bucket) < capacity);
+
+    let bucket = buckets.entry(client.clone()).or_insert(Bucket {
+        tokens: capacity,
+        refilled_at: now,
+    });
+    bucket.tokens = refill(bucket);
+    bucket.refilled_at = now;
+    bucket
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client(name: &str) -> ClientId {
+        ClientId(name.to_string())
+    }
+
+    #[test]
+    fn buckets_allow_a_burst_then_ask_clients_to_wait() {
+        let config = Config {
+            rate_limit_per_minute: 6,
+            rate_limit_burst: 2,
+            ..Config::default()
+        };
+        let limits = ClientLimits::default();
+
+        assert!(limits.check_rate(&config, &client("ip-10.0.0.1")).is_ok());
+        assert!(limits.check_rate(&config, &client("ip-10.0.0.1")).is_ok());
+        let throttled = limits.check_rate(&config, &client("ip-10.0.0.1"));
+        assert!(limits.check_rate(&config, &client("ip-10.0.0.2")).is_ok());
+
+        // Six a minute is one every ten seconds
+        assert_eq!(
+            throttled.map_err(|throttled| throttled.retry_after()),
+            Err(Duration::from_secs(10))
+        );
+        assert!(
+            limits
+                .check_rate(&Config::default(), &client("ip-10.0.0.1"))
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn failed_logins_run_out_while_other_clients_can_still_try() {
+        let limits = ClientLimits::default();
+
+        assert!(limits.check_login(&client("ip-10.0.0.1")).is_ok());
+        for _ in 0..5 {
+            limits.record_failed_login(&client("ip-10.0.0.1"));
+        }
+        assert_eq!(
+            limits.check_login(&client("ip-10.0.0.1")),
+            Err(Throttled::TooManyLogins {
+                retry_after: Duration::from_secs(60)
+            })
+        );
+        assert!(limits.check_login(&client("ip-10.0.0.2")).is_ok());
+    }
+
+    #[test]
+    fn job_slots_are_capped_per_client_and_freed_on_drop() {
+        let config = Config {
+            max_jobs_per_client: 1,
+            ..Config::default()
+        };
+        let limits = ClientLimits::default();
+
+        let first = limits.claim_job(&config, &client("key-a"));
+        let second = limits.claim_job(&config, &client("key-a"));
+        let other = limits.claim_job(&config, &client("key-b"));
+        assert!(first.is_ok());
+        assert_eq!(second.err(), Some(Throttled::TooManyJobs { max_jobs: 1 }));
+        assert!(other.is_ok());
+
+        drop(first);
+        assert!(limits.claim_job(&config, &client("key-a")).is_ok());
+    }
+
+    #[test]
+    fn identifies_clients_by_key_or_trusted_address() {
+        let peer = Some("192.0.2.7".parse().unwrap());
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", "10.0.0.1, 203.0.113.9".parse().unwrap());
+        let open = AppState::new(Config::default());
+        let proxied = AppState::new(Config {
+            trust_proxy_headers: true,
+            ..Config::default()
+        });
+        let keyed = AppState::new(Config {
+            api_keys: "s3cr3t".to_string(),
+            web_ui_secret: "shared".to_string(),
+            ..Config::default()
+        });
+
+        assert_eq!(
+            ClientId::from_request(&open, &headers, peer).to_string(),
+            "ip-192.0.2.7"
+        );
+        assert_eq!(
+            ClientId::from_request(&proxied, &headers, peer).to_string(),
+            "ip-203.0.113.9"
+        );
+        // Only the entry the proxy appended counts, whatever the client sent
+        headers.append("x-forwarded-for", "198.51.100.4".parse().unwrap());
+        assert_eq!(
+            ClientId::from_request(&proxied, &headers, peer).to_string(),
+            "ip-198.51.100.4"
+        );
+        headers.insert("x-api-key", "s3cr3t".parse().unwrap());
+        let by_key = ClientId::from_request(&keyed, &headers, peer).to_string();
+        assert!(by_key.starts_with("key-") && !by_key.contains("s3cr3t"));
+        // Everyone on the web page shares a secret, so each keeps their own address
+        headers.insert("x-api-key", "shared".parse().unwrap());
+        assert_eq!(
+            ClientId::from_request(&keyed, &headers, peer).to_string(),
+            "ip-192.0.2.7"
+        );
+        headers.insert("x-api-key", "s3cr3t".parse().unwrap());
+        // Unconfigured keys don't buy a fresh bucket
+        assert_eq!(
+            ClientId::from_request(&open, &headers, peer).to_string(),
+            "ip-192.0.2.7"
+        );
+    }
+}
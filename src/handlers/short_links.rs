@@ -0,0 +1,348 @@
+use reqwest::header::LOCATION;
+use reqwest::redirect::Policy;
+use std::collections::HashMap;
+use std::error::Error;
+use std::future::Future;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+use crate::handlers::downloader::BROWSER_USER_AGENT;
+use crate::utils::canonical_url::canonicalize_url;
+use crate::utils::video_id::extract_cache_id;
+
+/*
+ * Short-link resolution for redirectors such as vm.tiktok.com and t.co.
+ *
+ * Those links carry no video ID, so extract_cache_id can't match them and every
+ * request would pay for a full metadata fetch. For known short-link hosts we
+ * follow the redirects ourselves (a few hops, with a deadline), stop as soon as
+ * a URL extract_cache_id understands turns up, and remember the mapping (for
+ * a day, and at most MAX_REMEMBERED links, oldest forgotten first). Any
+ * failure just means the normal metadata path runs.
+ *
+ * The HTTP side sits behind RedirectClient so tests can point it at a local
+ * stub server instead of the real redirectors.
+ */
+
+const MAX_HOPS: usize = 5;
+const RESOLVE_TIMEOUT: Duration = Duration::from_secs(5);
+const RESOLVED_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+const MAX_REMEMBERED: usize = 10_000;
+
+// Hosts whose links only redirect to the real page
+const SHORT_LINK_HOSTS: [&str; 10] = [
+    "vm.tiktok.com",
+    "vt.tiktok.com",
+    "t.co",
+    "bit.ly",
+    "tinyurl.com",
+    "fb.watch",
+    "redd.it",
+    "on.soundcloud.com",
+    "b23.tv",
+    "instagr.am",
+];
+
+/// Makes a single request and reports where it redirects to.
+pub trait RedirectClient: Send + Sync + 'static {
+    /// The absolute URL `url` redirects to, or None when it doesn't redirect.
+    fn redirect_target(
+        &self,
+        url: &str,
+    ) -> impl Future<Output = Result<Option<String>, Box<dyn Error + Send + Sync>>> + Send;
+}
+
+/// RedirectClient over HTTP, one hop per request.
+#[derive(Clone)]
+pub struct HttpRedirects {
+    client: reqwest::Client,
+}
+
+impl Default for HttpRedirects {
+    fn default() -> Self {
+        let client = reqwest::Client::builder()
+            .redirect(Policy::none())
+            .timeout(RESOLVE_TIMEOUT)
+            .user_agent(BROWSER_USER_AGENT)
+            .build()
+            .expect("HTTP client should build");
+        Self { client }
+    }
+}
+
+impl RedirectClient for HttpRedirects {
+    async fn redirect_target(
+        &self,
+        url: &str,
+    ) -> Result<Option<String>, Box<dyn Error + Send + Sync>> {
+        let response = self.client.get(url).send().await?;
+        if !response.status().is_redirection() {
+            return Ok(None);
+        }
+        let Some(location) = response.headers().get(LOCATION) else {
+            return Ok(None);
+        };
+        // Relative redirects resolve against the URL we asked for
+        let target = response.url().join(location.to_str()?)?;
+        Ok(Some(target.to_string()))
+    }
+}
+
+/// Follows short links to the page they stand for, remembering what it found.
+#[derive(Clone, Default)]
+pub struct ShortLinkResolver<C = HttpRedirects> {
+    client: C,
+    resolved: Arc<Mutex<ResolvedLinks>>,
+}
+
+#[derive(Default)]
+struct ResolvedLinks {
+    entries: HashMap<String, ResolvedLink>,
+    // Insertions so far; evicting by this stays in order even when clock readings tie
+    inserted: u64,
+}
+
+struct ResolvedLink {
+    resolved_at: Instant,
+    order: u64,
+    target: String,
+}
+
+impl<C: RedirectClient> ShortLinkResolver<C> {
+    pub fn new(client: C) -> Self {
+        Self {
+            client,
+            resolved: Arc::default(),
+        }
+    }
+
+    /*
+     * Resolves a short link to the canonical URL of the page it points at, as
+     * long as that page is one extract_cache_id recognizes. Returns None for
+     * anything else: other hosts, too many hops, timeouts and request errors.
+     */
+    pub async fn resolve(&self, url: &str) -> Option<String> {
+        let short = canonicalize_url(url);
+        if !is_short_link(&short) {
+            return None;
+        }
+        if let Some(target) = self.remembered(&short) {
+            debug!(url = %short, target = %target, "Short link resolved from memory");
+            return Some(target);
+        }
+
+        let target = tokio::time::timeout(RESOLVE_TIMEOUT, self.follow(&short))
+            .await
+            .ok()
+            .flatten()?;
+        info!(url = %short, target = %target, "Short link resolved");
+        self.remember(&short, &target);
+        Some(target)
+    }
+
+    async fn follow(&self, short: &str) -> Option<String> {
+        let mut current = short.to_string();
+        for _ in 0..MAX_HOPS {
+            let next = match self.client.redirect_target(&current).await {
+                Ok(Some(next)) => canonicalize_url(&next),
+                Ok(None) => return None,
+                Err(e) => {
+                    debug!(url = %current, error = %e, "Short link request failed");
+                    return None;
+                }
+            };
+            if extract_cache_id(&next).is_some() {
+                return Some(next);
+            }
+            // Only ever request known redirectors, never wherever a Location header says
+            if !is_short_link(&next) {
+                return None;
+            }
+            current = next;
+        }
+        debug!(url = %short, hops = MAX_HOPS, "Short link gave up after too many redirects");
+        None
+    }
+
+    fn remembered(&self, short: &str) -> Option<String> {
+        self.links()
+            .entries
+            .get(short)
+            .filter(|link| link.resolved_at.elapsed() < RESOLVED_TTL)
+            .map(|link| link.target.clone())
+    }
+
+    fn remember(&self, short: &str, target: &str) {
+        let mut links = self.links();
+        let ResolvedLinks { entries, inserted } = &mut *links;
+        // Anyone can submit fresh short links, so the map needs a bound of its own:
+        // once full, expired links go first, then the oldest one
+        if entries.len() >= MAX_REMEMBERED && !entries.contains_key(short) {
+            entries.retain(|_, link| link.resolved_at.elapsed() < RESOLVED_TTL);
+        }
+        if entries.len() >= MAX_REMEMBERED && !entries.contains_key(short) {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, link)| link.order)
+                .map(|(url, _)| url.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+        *inserted += 1;
+        entries.insert(
+            short.to_string(),
+            ResolvedLink {
+                resolved_at: Instant::now(),
+                order: *inserted,
+                target: target.to_string(),
+            },
+        );
+    }
+
+    fn links(&self) -> MutexGuard<'_, ResolvedLinks> {
+        self.resolved.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// True for links on a known redirector host (after canonicalize_url).
+fn is_short_link(url: &str) -> bool {
+    let host = url
+        .split_once("://")
+        .map(|(_, rest)| rest)
+        .unwrap_or_default()
+        .split(['/', '?'])
+        .next()
+        .unwrap_or_default();
+    SHORT_LINK_HOSTS.contains(&host)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        Router,
+        http::{StatusCode, header},
+        response::IntoResponse,
+        routing::get,
+    };
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::net::TcpListener;
+
+    // Sends vm.tiktok.com requests to a local stub server instead, and reports
+    // redirects within the stub as vm.tiktok.com again
+    #[derive(Clone)]
+    struct StubRedirects {
+        base: String,
+        http: HttpRedirects,
+    }
+
+    impl RedirectClient for StubRedirects {
+        async fn redirect_target(
+            &self,
+            url: &str,
+        ) -> Result<Option<String>, Box<dyn Error + Send + Sync>> {
+            let path = url.split_once("://").map(|(_, rest)| rest).unwrap_or(url);
+            let path = &path[path.find('/').unwrap_or(path.len())..];
+            let local = url
+                .starts_with("https://vm.tiktok.com")
+                .then(|| format!("{}{path}", self.base));
+            let target = self
+                .http
+                .redirect_target(local.as_deref().unwrap_or(url))
+                .await?;
+            Ok(target.map(|target| target.replace(&self.base, "https://vm.tiktok.com")))
+        }
+    }
+
+    async fn stub_server(requests: Arc<AtomicUsize>) -> String {
+        let redirect = |location: &'static str| {
+            let requests = requests.clone();
+            move || async move {
+                requests.fetch_add(1, Ordering::SeqCst);
+                (StatusCode::FOUND, [(header::LOCATION, location)]).into_response()
+            }
+        };
+        let app = Router::new()
+            .route("/ZMabcdef/", get(redirect("/hop")))
+            .route(
+                "/hop",
+                get(redirect(
+                    "https://www.tiktok.com/@user/video/7123456789012345678?is_from_webapp=1",
+                )),
+            )
+            .route("/loop", get(redirect("/loop")))
+            .route(
+                "/internal",
+                get(redirect("http://169.254.169.254/latest/meta-data/")),
+            );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        base
+    }
+
+    #[tokio::test]
+    async fn follows_redirects_to_a_recognized_url_and_remembers_it() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let base = stub_server(requests.clone()).await;
+        let resolver = ShortLinkResolver::new(StubRedirects {
+            base,
+            http: HttpRedirects::default(),
+        });
+
+        let first = resolver.resolve("https://vm.tiktok.com/ZMabcdef/").await;
+        let second = resolver
+            .resolve("https://vm.tiktok.com/ZMabcdef/?utm_source=copy")
+            .await;
+
+        let expected = Some("https://tiktok.com/@user/video/7123456789012345678".to_string());
+        assert_eq!(first, expected);
+        assert_eq!(second, expected);
+        assert_eq!(
+            extract_cache_id(&first.unwrap()).as_deref(),
+            Some("tiktok-7123456789012345678")
+        );
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn gives_up_on_loops_and_never_leaves_redirector_hosts() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let base = stub_server(requests.clone()).await;
+        let resolver = ShortLinkResolver::new(StubRedirects {
+            base,
+            http: HttpRedirects::default(),
+        });
+
+        let looping = resolver.resolve("https://vm.tiktok.com/loop").await;
+        let hops = requests.load(Ordering::SeqCst);
+        let other_host = resolver.resolve("https://example.com/ZMabcdef/").await;
+        let internal = resolver.resolve("https://vm.tiktok.com/internal").await;
+
+        assert!(looping.is_none());
+        assert_eq!(hops, MAX_HOPS);
+        assert!(other_host.is_none());
+        // The metadata address is never requested, only the redirector
+        assert!(internal.is_none());
+        assert_eq!(requests.load(Ordering::SeqCst), MAX_HOPS + 1);
+    }
+
+    #[test]
+    fn forgets_the_oldest_links_once_full() {
+        let resolver = ShortLinkResolver::<HttpRedirects>::default();
+        for n in 0..=MAX_REMEMBERED {
+            resolver.remember(&format!("https://t.co/{n}"), "https://x.com/a/status/1");
+        }
+
+        let links = resolver.links();
+        assert_eq!(links.entries.len(), MAX_REMEMBERED);
+        assert!(!links.entries.contains_key("https://t.co/0"));
+        // Links are numbered in insertion order, whatever the clock said
+        let order = |n: usize| links.entries[&format!("https://t.co/{n}")].order;
+        assert_eq!(order(1), 2);
+        assert_eq!(order(MAX_REMEMBERED), MAX_REMEMBERED as u64 + 1);
+        assert_eq!(links.entries.values().map(|link| link.order).min(), Some(2));
+    }
+}
@@ -0,0 +1,712 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Stdio};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio::process::Command;
+use tokio_util::sync::CancellationToken;
+
+use crate::config::{
+    AudioCodecPreferenceEnv, AudioQualityEnv, VideoCodecPreferenceEnv, VideoQualityEnv,
+};
+use crate::handlers::downloader::BROWSER_USER_AGENT;
+use crate::handlers::options::{DownloadOptions, Subtitles};
+use crate::utils::ytdlp_update::{ffmpeg_binary_path, yt_dlp_binary_path};
+
+/*
+ * Runs the yt-dlp binary to fetch a video's metadata, for a single download, to
+ * list a playlist, or to fetch subtitle files on their own; and ffmpeg to
+ * generate thumbnails.
+ *
+ * The Downloader crate still installs the binaries, but the download itself
+ * goes through the CLI so we can read its progress line by line (percent,
+ * bytes, speed and ETA) and report it on the job while it runs. A download
+ * loads the metadata its job already fetched (--load-info-json) rather than
+ * extracting the page a second time.
+ */
+
+// Marker yt-dlp prints in front of every progress line we ask it for
+const PROGRESS_PREFIX: &str = "snatchr-progress";
+
+/// Error message for downloads stopped through their cancellation token.
+pub const CANCELLED_ERROR: &str = "Download cancelled";
+
+/// Snapshot of a running download, as reported by yt-dlp.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct DownloadProgress {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub percent: Option<f64>,
+    pub downloaded_bytes: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speed_bytes_per_sec: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eta_seconds: Option<u64>,
+}
+
+/*
+ * Builds a yt-dlp format selector from the quality and codec preferences.
+ * An explicit `max_height` replaces the quality preset's own height cap.
+ * Codec preferences are soft: if no stream matches, fall back to any codec
+ * at the same quality, then to the best single-file format.
+ */
+pub fn format_selector(
+    video_quality: VideoQualityEnv,
+    max_height: Option<u32>,
+    video_codec: VideoCodecPreferenceEnv,
+    audio_quality: AudioQualityEnv,
+    audio_codec: AudioCodecPreferenceEnv,
+) -> String {
+    let (video, single) = match (height_cap(video_quality, max_height), video_quality) {
+        (Some(height), _) => (
+            format!("bv*[height<={height}]"),
+            format!("b[height<={height}]"),
+        ),
+        (None, VideoQualityEnv::Worst) => ("wv*".to_string(), "w".to_string()),
+        (None, _) => ("bv*".to_string(), "b".to_string()),
+    };
+    let audio = audio_quality_filter(audio_quality);
+    let vcodec = match video_codec {
+        VideoCodecPreferenceEnv::VP9 => "[vcodec~='^vp0?9']",
+        VideoCodecPreferenceEnv::AVC1 => "[vcodec^=avc1]",
+        VideoCodecPreferenceEnv::AV1 => "[vcodec^=av01]",
+        VideoCodecPreferenceEnv::Any => "",
+    };
+    let acodec = audio_codec_filter(audio_codec);
+
+    let mut choices = Vec::new();
+    if !vcodec.is_empty() || !acodec.is_empty() {
+        choices.push(format!("{video}{vcodec}+{audio}{acodec}"));
+    }
+    choices.push(format!("{video}+{audio}"));
+    choices.push(single);
+    choices.push("b".to_string());
+    choices.dedup();
+    choices.join("/")
+}
+
+/// Tallest video a download may pick: an explicit `max_height`, else the quality preset's cap.
+pub fn height_cap(video_quality: VideoQualityEnv, max_height: Option<u32>) -> Option<u32> {
+    max_height.or(match video_quality {
+        VideoQualityEnv::High => Some(1080),
+        VideoQualityEnv::Medium => Some(720),
+        VideoQualityEnv::Low => Some(480),
+        VideoQualityEnv::Best | VideoQualityEnv::Worst => None,
+    })
+}
+
+/// Audio-only selector: preferred codec first, then any audio, then any format.
+pub fn audio_selector(
+    audio_quality: AudioQualityEnv,
+    audio_codec: AudioCodecPreferenceEnv,
+) -> String {
+    let audio = audio_quality_filter(audio_quality);
+    let acodec = audio_codec_filter(audio_codec);
+
+    let mut choices = vec![
+        format!("{audio}{acodec}"),
+        audio.to_string(),
+        "ba".to_string(),
+        "b".to_string(),
+    ];
+    choices.dedup();
+    choices.join("/")
+}
+
+/// yt-dlp's --audio-quality scale for extracted audio: 0 (best VBR) to 10 (worst).
+pub fn audio_quality_level(audio_quality: AudioQualityEnv) -> &'static str {
+    match audio_quality {
+        AudioQualityEnv::Best => "0",
+        AudioQualityEnv::High => "2",
+        AudioQualityEnv::Medium => "5",
+        AudioQualityEnv::Low => "7",
+        AudioQualityEnv::Worst => "9",
+    }
+}
+
+fn audio_quality_filter(audio_quality: AudioQualityEnv) -> &'static str {
+    match audio_quality {
+        AudioQualityEnv::Best | AudioQualityEnv::High => "ba",
+        AudioQualityEnv::Medium => "ba[abr<=128]",
+        AudioQualityEnv::Low => "ba[abr<=96]",
+        AudioQualityEnv::Worst => "wa",
+    }
+}
+
+fn audio_codec_filter(audio_codec: AudioCodecPreferenceEnv) -> &'static str {
+    match audio_codec {
+        AudioCodecPreferenceEnv::Opus => "[acodec^=opus]",
+        AudioCodecPreferenceEnv::Aac => "[acodec^=mp4a]",
+        AudioCodecPreferenceEnv::MP3 => "[acodec^=mp3]",
+        AudioCodecPreferenceEnv::Any => "",
+    }
+}
+
+/// One yt-dlp invocation: what to fetch, which formats, and where to put it.
+pub struct DownloadCommand<'a> {
+    pub url: &'a str,
+    /// Metadata fetched beforehand; when set, yt-dlp loads it instead of extracting `url`.
+    pub info_json: Option<&'a Path>,
+    /// Directory the output lands in (yt-dlp's `--paths`).
+    pub output_dir: &'a Path,
+    /// Output name without extension, e.g. ".Title.abcd1234.tmp".
+    pub file_stem: &'a str,
+    pub options: &'a DownloadOptions,
+}
+
+impl DownloadCommand<'_> {
+    /// Where yt-dlp leaves the merged (or extracted) file once it exits successfully.
+    pub fn output_path(&self) -> PathBuf {
+        self.output_dir
+            .join(format!("{}.{}", self.file_stem, self.options.extension()))
+    }
+
+    fn args(&self) -> Vec<OsString> {
+        let mut args: Vec<OsString> = vec![
+            "--no-playlist".into(),
+            "--quiet".into(),
+            "--progress".into(),
+            "--newline".into(),
+            "--progress-template".into(),
+            progress_template().into(),
+            "--user-agent".into(),
+            BROWSER_USER_AGENT.into(),
+            "--ffmpeg-location".into(),
+            "libs".into(),
+            // Lands next to the temp output as "{file_stem}.jpg"
+            "--write-thumbnail".into(),
+            "--convert-thumbnails".into(),
+            "jpg".into(),
+            "-f".into(),
+            self.options.format_selector().into(),
+            "--paths".into(),
+            self.output_dir.into(),
+            "--output".into(),
+            format!("{}.%(ext)s", self.file_stem).into(),
+        ];
+        args.extend(self.options.output_args());
+        match self.info_json {
+            Some(info_json) => args.extend(["--load-info-json".into(), info_json.into()]),
+            None => args.extend(["--".into(), self.url.into()]),
+        }
+        args
+    }
+}
+
+/// Why yt-dlp failed: its exit status and the last thing it said on stderr.
+fn failure_reason(status: ExitStatus, stderr: &str) -> String {
+    let reason = stderr
+        .lines()
+        .rev()
+        .find(|line| !line.trim().is_empty())
+        .unwrap_or("no output");
+    format!("yt-dlp exited with {status}: {}", reason.trim())
+}
+
+fn progress_template() -> String {
+    format!(
+        "download:{PROGRESS_PREFIX} %(progress.downloaded_bytes)s %(progress.total_bytes)s \
+         %(progress.total_bytes_estimate)s %(progress.speed)s %(progress.eta)s"
+    )
+}
+
+/// Parses one of our progress-template lines. yt-dlp prints "NA" for unknown values.
+pub fn parse_progress_line(line: &str) -> Option<DownloadProgress> {
+    let mut fields = line.split_whitespace();
+    if fields.next()? != PROGRESS_PREFIX {
+        return None;
+    }
+
+    let mut next_number = || fields.next().and_then(|v| v.parse::<f64>().ok());
+    let downloaded = next_number()?;
+    let total = next_number();
+    let estimate = next_number();
+    let speed = next_number();
+    let eta = next_number();
+
+    let total_bytes = total.or(estimate).map(|bytes| bytes as u64);
+    let percent = total_bytes
+        .filter(|total| *total > 0)
+        .map(|total| (downloaded / total as f64 * 100.0).min(100.0));
+
+    Some(DownloadProgress {
+        percent,
+        downloaded_bytes: downloaded as u64,
+        total_bytes,
+        speed_bytes_per_sec: speed,
+        eta_seconds: eta.map(|secs| secs as u64),
+    })
+}
+
+/*
+ * Runs the download and calls `on_progress` for every progress line.
+ * Tripping `cancel` kills yt-dlp straight away; the caller scrubs the temp files.
+ * On failure the error carries yt-dlp's last stderr line, which is usually
+ * the extractor's own explanation.
+ */
+pub async fn run_download(
+    command: &DownloadCommand<'_>,
+    cancel: &CancellationToken,
+    mut on_progress: impl FnMut(DownloadProgress),
+) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
+    let mut child = Command::new(yt_dlp_binary_path())
+        .args(command.args())
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    // Drain stderr alongside stdout so a chatty extractor can't fill the pipe
+    let mut stderr = child.stderr.take().expect("stderr is piped");
+    let stderr_task = tokio::spawn(async move {
+        let mut output = String::new();
+        let _ = stderr.read_to_string(&mut output).await;
+        output
+    });
+
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let mut lines = BufReader::new(stdout).lines();
+    loop {
+        let line = tokio::select! {
+            line = lines.next_line() => line?,
+            _ = cancel.cancelled() => {
+                let _ = child.kill().await;
+                return Err(CANCELLED_ERROR.into());
+            }
+        };
+        let Some(line) = line else {
+            break;
+        };
+        if let Some(progress) = parse_progress_line(&line) {
+            on_progress(progress);
+        }
+    }
+
+    // stdout closes a moment before exit (e.g. while ffmpeg merges), so stay cancellable
+    let status = tokio::select! {
+        status = child.wait() => status?,
+        _ = cancel.cancelled() => {
+            let _ = child.kill().await;
+            return Err(CANCELLED_ERROR.into());
+        }
+    };
+    let stderr = stderr_task.await.unwrap_or_default();
+    if !status.success() {
+        return Err(failure_reason(status, &stderr).into());
+    }
+
+    let output = command.output_path();
+    if !output.is_file() {
+        return Err(format!("yt-dlp finished without producing {}", output.display()).into());
+    }
+    Ok(output)
+}
+
+/*
+ * Grabs a representative frame of a video as a jpg thumbnail with the bundled
+ * ffmpeg, for cached videos whose site thumbnail we never fetched.
+ */
+pub async fn generate_thumbnail(
+    video: &Path,
+    output: &Path,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let result = Command::new(ffmpeg_binary_path())
+        .args(thumbnail_args(video, output))
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output()
+        .await?;
+    if !result.status.success() || !output.is_file() {
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        return Err(format!("ffmpeg exited with {}: {}", result.status, stderr.trim()).into());
+    }
+    Ok(())
+}
+
+fn thumbnail_args(video: &Path, output: &Path) -> Vec<OsString> {
+    vec![
+        "-y".into(),
+        "-loglevel".into(),
+        "error".into(),
+        "-i".into(),
+        video.into(),
+        // `thumbnail` picks the most typical of the first frames, skipping black intros
+        "-vf".into(),
+        "thumbnail,scale=640:-2".into(),
+        "-frames:v".into(),
+        "1".into(),
+        output.into(),
+    ]
+}
+
+/// A subtitle-only run: writes `{file_stem}.{lang}.{ext}` files, skipping the media.
+pub struct SubtitleCommand<'a> {
+    pub url: &'a str,
+    pub output_dir: &'a Path,
+    pub file_stem: &'a str,
+    pub subtitles: &'a Subtitles,
+}
+
+impl SubtitleCommand<'_> {
+    fn args(&self) -> Vec<OsString> {
+        let mut args: Vec<OsString> = vec![
+            "--skip-download".into(),
+            "--no-playlist".into(),
+            "--quiet".into(),
+            "--no-warnings".into(),
+            "--user-agent".into(),
+            BROWSER_USER_AGENT.into(),
+            "--ffmpeg-location".into(),
+            "libs".into(),
+            "--paths".into(),
+            self.output_dir.into(),
+            "--output".into(),
+            format!("{}.%(ext)s", self.file_stem).into(),
+        ];
+        args.extend(self.subtitles.args());
+        args.extend(["--".into(), self.url.into()]);
+        args
+    }
+}
+
+/// Fetches subtitles without the media. Missing languages aren't an error;
+/// they simply produce no file.
+pub async fn run_subtitle_download(
+    command: &SubtitleCommand<'_>,
+    cancel: &CancellationToken,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut process = Command::new(yt_dlp_binary_path());
+    process
+        .args(command.args())
+        .stdin(Stdio::null())
+        .kill_on_drop(true);
+
+    let output = tokio::select! {
+        output = process.output() => output?,
+        _ = cancel.cancelled() => return Err(CANCELLED_ERROR.into()),
+    };
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(failure_reason(output.status, &stderr).into());
+    }
+    Ok(())
+}
+
+/// yt-dlp's full info JSON for a single video, as a download can load it back.
+pub async fn fetch_metadata(
+    url: &str,
+    cancel: &CancellationToken,
+) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+    let mut command = Command::new(yt_dlp_binary_path());
+    command
+        .args(metadata_args(url))
+        .stdin(Stdio::null())
+        .kill_on_drop(true);
+
+    let output = tokio::select! {
+        output = command.output() => output?,
+        _ = cancel.cancelled() => return Err(CANCELLED_ERROR.into()),
+    };
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(failure_reason(output.status, &stderr).into());
+    }
+    Ok(serde_json::from_slice(&output.stdout)?)
+}
+
+fn metadata_args(url: &str) -> Vec<OsString> {
+    vec![
+        "--dump-single-json".into(),
+        "--no-playlist".into(),
+        "--no-warnings".into(),
+        "--user-agent".into(),
+        BROWSER_USER_AGENT.into(),
+        "--".into(),
+        url.into(),
+    ]
+}
+
+/// One video of an expanded playlist or channel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlaylistEntry {
+    pub url: String,
+    pub title: Option<String>,
+}
+
+fn playlist_args(url: &str, max_items: usize) -> Vec<OsString> {
+    vec![
+        "--flat-playlist".into(),
+        "--dump-single-json".into(),
+        "--no-warnings".into(),
+        "--playlist-end".into(),
+        max_items.to_string().into(),
+        "--user-agent".into(),
+        BROWSER_USER_AGENT.into(),
+        "--".into(),
+        url.into(),
+    ]
+}
+
+/*
+ * Expands a playlist or channel URL into its videos without downloading anything.
+ * Uses yt-dlp's flat listing, so even long playlists resolve in a single request.
+ * At most `max_items` entries are returned.
+ */
+pub async fn list_playlist(
+    url: &str,
+    max_items: usize,
+    cancel: &CancellationToken,
+) -> Result<Vec<PlaylistEntry>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut command = Command::new(yt_dlp_binary_path());
+    command
+        .args(playlist_args(url, max_items))
+        .stdin(Stdio::null())
+        .kill_on_drop(true);
+
+    let output = tokio::select! {
+        output = command.output() => output?,
+        _ = cancel.cancelled() => return Err(CANCELLED_ERROR.into()),
+    };
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(failure_reason(output.status, &stderr).into());
+    }
+
+    let mut entries = parse_playlist(&output.stdout)?;
+    entries.truncate(max_items);
+    Ok(entries)
+}
+
+fn parse_playlist(
+    json: &[u8],
+) -> Result<Vec<PlaylistEntry>, Box<dyn std::error::Error + Send + Sync>> {
+    let listing: Value = serde_json::from_slice(json)?;
+    let mut entries = Vec::new();
+    collect_playlist_entries(&listing, &mut entries);
+    if entries.is_empty() {
+        return Err("No videos found at this URL — is it a playlist or channel?".into());
+    }
+    Ok(entries)
+}
+
+// Channels list their tabs as nested playlists; flatten them into one list
+fn collect_playlist_entries(listing: &Value, entries: &mut Vec<PlaylistEntry>) {
+    let Some(items) = listing.get("entries").and_then(Value::as_array) else {
+        return;
+    };
+    for item in items {
+        if item.get("entries").is_some() {
+            collect_playlist_entries(item, entries);
+            continue;
+        }
+        let url = item
+            .get("webpage_url")
+            .or_else(|| item.get("url"))
+            .and_then(Value::as_str)
+            .filter(|url| url.starts_with("http://") || url.starts_with("https://"));
+        // Unavailable and private entries come through without a usable URL
+        let Some(url) = url else {
+            continue;
+        };
+        entries.push(PlaylistEntry {
+            url: url.to_string(),
+            title: item
+                .get("title")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn selector_prefers_codecs_then_falls_back() {
+        let selector = format_selector(
+            VideoQualityEnv::Best,
+            None,
+            VideoCodecPreferenceEnv::VP9,
+            AudioQualityEnv::Best,
+            AudioCodecPreferenceEnv::Opus,
+        );
+        assert_eq!(selector, "bv*[vcodec~='^vp0?9']+ba[acodec^=opus]/bv*+ba/b");
+    }
+
+    #[test]
+    fn selector_caps_height_for_lower_qualities() {
+        let selector = format_selector(
+            VideoQualityEnv::Medium,
+            None,
+            VideoCodecPreferenceEnv::Any,
+            AudioQualityEnv::Low,
+            AudioCodecPreferenceEnv::Any,
+        );
+        assert_eq!(selector, "bv*[height<=720]+ba[abr<=96]/b[height<=720]/b");
+
+        let capped = format_selector(
+            VideoQualityEnv::Best,
+            Some(1440),
+            VideoCodecPreferenceEnv::Any,
+            AudioQualityEnv::Best,
+            AudioCodecPreferenceEnv::Any,
+        );
+        assert_eq!(capped, "bv*[height<=1440]+ba/b[height<=1440]/b");
+    }
+
+    #[test]
+    fn parses_progress_lines() {
+        let progress = parse_progress_line("snatchr-progress 524288 1048576 NA 65536.5 8").unwrap();
+        assert_eq!(progress.downloaded_bytes, 524_288);
+        assert_eq!(progress.total_bytes, Some(1_048_576));
+        assert_eq!(progress.percent, Some(50.0));
+        assert_eq!(progress.speed_bytes_per_sec, Some(65_536.5));
+        assert_eq!(progress.eta_seconds, Some(8));
+    }
+
+    #[test]
+    fn falls_back_to_estimated_total_and_tolerates_unknowns() {
+        let progress = parse_progress_line("snatchr-progress 100 NA 400.0 NA NA").unwrap();
+        assert_eq!(progress.total_bytes, Some(400));
+        assert_eq!(progress.percent, Some(25.0));
+        assert!(progress.speed_bytes_per_sec.is_none());
+        assert!(progress.eta_seconds.is_none());
+
+        assert!(parse_progress_line("[download] Destination: video.mp4").is_none());
+        assert!(parse_progress_line("snatchr-progress NA NA NA NA NA").is_none());
+    }
+
+    #[test]
+    fn flattens_playlist_and_channel_listings() {
+        let listing = br#"{
+            "_type": "playlist",
+            "title": "Conference 2024",
+            "entries": [
+                {"_type": "url", "url": "https://www.youtube.com/watch?v=aaaaaaaaaaa", "title": "Keynote"},
+                {"_type": "url", "url": "https://www.youtube.com/watch?v=bbbbbbbbbbb", "title": null},
+                {"_type": "url", "url": "ccccccccccc", "title": "[Private video]"},
+                {"_type": "playlist", "title": "Shorts", "entries": [
+                    {"_type": "url", "url": "https://www.youtube.com/shorts/ddddddddddd", "title": "Short"}
+                ]}
+            ]
+        }"#;
+
+        let entries = parse_playlist(listing).unwrap();
+        let urls: Vec<_> = entries.iter().map(|e| e.url.as_str()).collect();
+        assert_eq!(
+            urls,
+            [
+                "https://www.youtube.com/watch?v=aaaaaaaaaaa",
+                "https://www.youtube.com/watch?v=bbbbbbbbbbb",
+                "https://www.youtube.com/shorts/ddddddddddd",
+            ]
+        );
+        assert_eq!(entries[0].title.as_deref(), Some("Keynote"));
+        assert!(entries[1].title.is_none());
+
+        assert!(parse_playlist(br#"{"_type": "playlist", "entries": []}"#).is_err());
+    }
+
+    #[test]
+    fn command_writes_hidden_temp_output_into_cache_dir() {
+        let options = DownloadOptions::from_config(&Config::default());
+        let command = DownloadCommand {
+            url: "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+            info_json: None,
+            output_dir: Path::new("downloads/dQw4w9WgXcQ"),
+            file_stem: ".Title.abcd1234.tmp",
+            options: &options,
+        };
+
+        let args = command.args();
+        assert!(
+            args.windows(2)
+                .any(|w| w[0] == "--output" && w[1] == ".Title.abcd1234.tmp.%(ext)s")
+        );
+        assert_eq!(
+            args.last().unwrap(),
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ"
+        );
+        assert_eq!(
+            command.output_path(),
+            PathBuf::from("downloads/dQw4w9WgXcQ/.Title.abcd1234.tmp.mp4")
+        );
+        assert!(
+            args.windows(2)
+                .any(|w| w[0] == "--convert-thumbnails" && w[1] == "jpg")
+        );
+
+        // With metadata at hand the page isn't extracted again
+        let info_json = Path::new("downloads/dQw4w9WgXcQ/.Title.abcd1234.tmp.info.json");
+        let args = DownloadCommand {
+            info_json: Some(info_json),
+            ..command
+        }
+        .args();
+        assert!(
+            args.windows(2)
+                .any(|w| w[0] == "--load-info-json" && Path::new(&w[1]) == info_json)
+        );
+        assert!(!args.iter().any(|arg| arg == "--"));
+    }
+
+    #[test]
+    fn subtitle_command_skips_the_media() {
+        use crate::handlers::options::{SubtitleOutput, SubtitleSource};
+
+        let subtitles = Subtitles {
+            languages: vec!["en".to_string()],
+            source: SubtitleSource::Manual,
+            output: SubtitleOutput::Vtt,
+        };
+        let command = SubtitleCommand {
+            url: "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+            output_dir: Path::new("downloads/dQw4w9WgXcQ"),
+            file_stem: ".Title.abcd1234.tmp",
+            subtitles: &subtitles,
+        };
+
+        let args = command.args();
+        assert!(args.iter().any(|arg| arg == "--skip-download"));
+        assert!(args.iter().any(|arg| arg == "--write-subs"));
+        assert!(!args.iter().any(|arg| arg == "--write-auto-subs"));
+        assert!(
+            args.windows(2)
+                .any(|w| w[0] == "--output" && w[1] == ".Title.abcd1234.tmp.%(ext)s")
+        );
+        assert_eq!(
+            args.last().unwrap(),
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ"
+        );
+    }
+
+    #[test]
+    fn audio_command_extracts_to_the_chosen_format() {
+        let options = DownloadOptions::from_config(&Config::default())
+            .audio_only(Some(crate::handlers::options::AudioFormat::Mp3));
+        let command = DownloadCommand {
+            url: "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+            info_json: None,
+            output_dir: Path::new("downloads/dQw4w9WgXcQ"),
+            file_stem: ".Title.abcd1234.tmp",
+            options: &options,
+        };
+
+        let args = command.args();
+        assert!(args.iter().any(|arg| arg == "--extract-audio"));
+        assert!(!args.iter().any(|arg| arg == "--merge-output-format"));
+        assert_eq!(
+            args.last().unwrap(),
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ"
+        );
+        assert_eq!(
+            command.output_path(),
+            PathBuf::from("downloads/dQw4w9WgXcQ/.Title.abcd1234.tmp.mp3")
+        );
+    }
+}
@@ -1,6 +1,6 @@
 use axum::{
     Router,
-    routing::{get, post},
+    routing::{delete, get, post, put},
 };
 use tokio::net::TcpListener;
 
@@ -8,13 +8,21 @@ mod config;
 use config::Config;
 
 mod routes;
+use routes::cache::{cache_listing, evict_cache_entry, pin_cache_entry, unpin_cache_entry};
 use routes::download::download_handler;
-use routes::files::serve_file;
+use routes::files::{serve_file, serve_thumbnail};
 use routes::health::health_check;
+use routes::info::{video_formats, video_info};
+use routes::jobs::{cancel_job, job_events, job_manifest, job_status};
+use routes::login::{login, logout};
 use routes::page::download_page;
 
 mod handlers;
+use handlers::cache::migrate_flat_entries;
+mod state;
+use state::AppState;
 mod utils;
+use utils::auth;
 use utils::cleanup::start_cleanup_scheduler;
 use utils::logger;
 use utils::ytdlp_update::start_ytdlp_update_scheduler;
@@ -26,29 +34,56 @@ pub use utils::logger::init as init_logging;
  * Sets up routes and listens on the configured address.
  */
 pub async fn run_server() {
-    // Load configuration from environment variables
-    let config = Config::from_env();
+    // Load configuration from environment variables once; handlers share it via AppState
+    let state = AppState::new(Config::from_env());
 
-    // Build the application router with routes and request logging
+    // Move cached videos from the old flat {id} layout to {platform}-{id}
+    migrate_flat_entries(std::path::Path::new(&state.config.download_dir));
+
+    // Build the application router with routes, API key checks and request logging
     let app = Router::new()
         .route("/", get(download_page)) // GET / -> download_page (HTML interface)
         .route("/health", get(health_check)) // GET /health -> health_check
+        .route("/login", post(login)) // POST /login -> login (web UI session cookie)
+        .route("/logout", post(logout)) // POST /logout -> logout
         .route("/download", post(download_handler)) // POST /download -> download_handler
+        .route("/info", get(video_info)) // GET /info?url=... -> video_info
+        .route("/info/formats", get(video_formats)) // GET /info/formats?url=... -> video_formats
+        .route("/cache", get(cache_listing)) // GET /cache -> cache_listing
+        .route("/cache/{video_id}", delete(evict_cache_entry)) // DELETE /cache/:video_id -> evict_cache_entry
+        .route("/cache/{video_id}/pin", put(pin_cache_entry).delete(unpin_cache_entry)) // PUT/DELETE /cache/:video_id/pin -> pin_cache_entry / unpin_cache_entry
+        .route("/jobs/{job_id}", get(job_status).delete(cancel_job)) // GET/DELETE /jobs/:job_id -> job_status / cancel_job
+        .route("/jobs/{job_id}/events", get(job_events)) // GET /jobs/:job_id/events -> job_events (SSE)
+        .route("/jobs/{job_id}/manifest", get(job_manifest)) // GET /jobs/:job_id/manifest -> job_manifest
+        .route("/files/{video_id}/thumbnail", get(serve_thumbnail)) // GET /files/:video_id/thumbnail -> serve_thumbnail
         .route("/files/{video_id}/{filename}", get(serve_file)) // GET /files/:video_id/:filename -> serve_file
-        .layer(axum::middleware::from_fn(logger::log_requests));
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            auth::require_api_key,
+        ))
+        .layer(axum::middleware::from_fn(logger::log_requests))
+        .with_state(state.clone());
 
     // Bind TCP listener to the configured address
-    let listener = TcpListener::bind(&config.address()).await.unwrap();
+    let listener = TcpListener::bind(&state.config.address()).await.unwrap();
 
     // Print styled startup banner with the resolved configuration
-    logger::print_banner(&config);
+    logger::print_banner(&state.config);
 
     // Start cleanup scheduler in background
-    tokio::spawn(start_cleanup_scheduler());
+    tokio::spawn(start_cleanup_scheduler(
+        state.config.clone(),
+        state.in_flight.clone(),
+    ));
 
     // Keep yt-dlp current
-    tokio::spawn(start_ytdlp_update_scheduler());
+    tokio::spawn(start_ytdlp_update_scheduler(state.config.clone()));
 
-    // Start serving requests
-    axum::serve(listener, app).await.unwrap();
+    // Start serving requests, keeping peer addresses for per-client rate limits
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
+    .await
+    .unwrap();
 }
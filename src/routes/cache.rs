@@ -0,0 +1,158 @@
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use serde::Serialize;
+use std::path::PathBuf;
+use tracing::{error, warn};
+
+use crate::handlers::cache::{
+    CacheEntry, cache_dir_for, cache_entry, evict, list_cache, set_pinned,
+};
+use crate::state::AppState;
+use crate::utils::cleanup::is_video_directory;
+
+/// Contents of DOWNLOAD_DIR, as returned by GET /cache.
+#[derive(Serialize)]
+pub struct CacheListing {
+    total_bytes: u64,
+    videos: Vec<CacheEntry>,
+}
+
+/// Lists cached videos, most recently used first: GET /cache
+/// Each entry has its ID, title, size on disk, last access and published variants.
+pub async fn cache_listing(State(state): State<AppState>) -> Json<CacheListing> {
+    let download_dir = PathBuf::from(&state.config.download_dir);
+    let videos = tokio::task::spawn_blocking(move || list_cache(&download_dir))
+        .await
+        .unwrap_or_default();
+
+    Json(CacheListing {
+        total_bytes: videos.iter().map(|video| video.size_bytes).sum(),
+        videos,
+    })
+}
+
+/// Evicts one cached video and all of its variants: DELETE /cache/{video_id}
+/// Answers 409 Conflict while a download of that video is running.
+pub async fn evict_cache_entry(
+    State(state): State<AppState>,
+    Path(video_id): Path<String>,
+) -> Result<Json<CacheEntry>, StatusCode> {
+    let download_dir = PathBuf::from(&state.config.download_dir);
+    let dir = cache_dir_for(&download_dir, &video_id).ok_or(StatusCode::BAD_REQUEST)?;
+    if !dir.is_dir() || !is_video_directory(&dir) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    // Hold the video's in-flight slot so no download publishes into it meanwhile
+    let Some(_claim) = state.in_flight.try_acquire(&video_id) else {
+        warn!(video = %video_id, "Refusing to evict a video that is downloading");
+        return Err(StatusCode::CONFLICT);
+    };
+    let entry = cache_entry(&dir).ok_or(StatusCode::NOT_FOUND)?;
+    evict(&dir).map_err(|e| {
+        error!(video = %video_id, error = %e, "Failed to evict cached video");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(Json(entry))
+}
+
+/// Pins a cached video so cleanup never removes it: PUT /cache/{video_id}/pin
+pub async fn pin_cache_entry(
+    State(state): State<AppState>,
+    Path(video_id): Path<String>,
+) -> Result<Json<CacheEntry>, StatusCode> {
+    update_pin(&state, &video_id, true)
+}
+
+/// Lets a pinned video expire again: DELETE /cache/{video_id}/pin
+pub async fn unpin_cache_entry(
+    State(state): State<AppState>,
+    Path(video_id): Path<String>,
+) -> Result<Json<CacheEntry>, StatusCode> {
+    update_pin(&state, &video_id, false)
+}
+
+fn update_pin(
+    state: &AppState,
+    video_id: &str,
+    pinned: bool,
+) -> Result<Json<CacheEntry>, StatusCode> {
+    let download_dir = PathBuf::from(&state.config.download_dir);
+    let dir = cache_dir_for(&download_dir, video_id).ok_or(StatusCode::BAD_REQUEST)?;
+    if !dir.is_dir() || !is_video_directory(&dir) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    set_pinned(&dir, pinned).map_err(|e| {
+        error!(video = %video_id, error = %e, "Failed to update pin");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    cache_entry(&dir).map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::utils::test_support::TestDirectory;
+
+    #[tokio::test]
+    async fn evicts_idle_videos_but_not_downloading_ones() {
+        let download_dir = TestDirectory::new();
+        for video_id in ["idle", "busy"] {
+            let dir = download_dir.join(video_id);
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join("Title.mp4"), vec![0_u8; 1024]).unwrap();
+        }
+        let state = AppState::new(Config {
+            download_dir: download_dir.to_string_lossy().into_owned(),
+            ..Config::default()
+        });
+        let _running = state.in_flight.acquire("busy").await;
+
+        let evicted = evict_cache_entry(State(state.clone()), Path("idle".to_string())).await;
+        let busy = evict_cache_entry(State(state.clone()), Path("busy".to_string())).await;
+        let missing = evict_cache_entry(State(state.clone()), Path("missing".to_string())).await;
+        let escape = evict_cache_entry(State(state.clone()), Path("..".to_string())).await;
+        let Json(listing) = cache_listing(State(state)).await;
+
+        assert_eq!(
+            evicted.map(|Json(entry)| entry.video_id),
+            Ok("idle".to_string())
+        );
+        assert_eq!(busy.map(|_| ()), Err(StatusCode::CONFLICT));
+        assert_eq!(missing.map(|_| ()), Err(StatusCode::NOT_FOUND));
+        assert_eq!(escape.map(|_| ()), Err(StatusCode::BAD_REQUEST));
+        assert_eq!(listing.videos.len(), 1);
+        assert_eq!(listing.total_bytes, 1024);
+    }
+
+    #[tokio::test]
+    async fn pins_and_unpins_cached_videos() {
+        let download_dir = TestDirectory::new();
+        let dir = download_dir.join("dQw4w9WgXcQ");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("Title.mp4"), vec![0_u8; 1024]).unwrap();
+        let state = AppState::new(Config {
+            download_dir: download_dir.to_string_lossy().into_owned(),
+            ..Config::default()
+        });
+        let video_id = || Path("dQw4w9WgXcQ".to_string());
+
+        let pinned = pin_cache_entry(State(state.clone()), video_id()).await;
+        let Json(listing) = cache_listing(State(state.clone())).await;
+        let unpinned = unpin_cache_entry(State(state.clone()), video_id()).await;
+        let again = unpin_cache_entry(State(state.clone()), video_id()).await;
+        let missing = pin_cache_entry(State(state), Path("missing".to_string())).await;
+
+        assert_eq!(pinned.map(|Json(entry)| entry.pinned), Ok(true));
+        assert!(listing.videos[0].pinned);
+        assert_eq!(unpinned.map(|Json(entry)| entry.pinned), Ok(false));
+        assert_eq!(again.map(|Json(entry)| entry.pinned), Ok(false));
+        assert_eq!(missing.map(|_| ()), Err(StatusCode::NOT_FOUND));
+    }
+}
@@ -4,39 +4,146 @@
  * Expects a JSON payload with a 'url' field representing the video URL to download.
  *
  * Steps:
- * 1. Generate a unique job ID for tracking the download.
- * 2. Clone the URL from the request payload.
- * 3. Offload the blocking download operation to a dedicated thread using `spawn_blocking`.
- * 4. Handle any errors during task execution or download process.
+ * 1. Check the client's rate limit and job cap, answering 429 when it is over
+ *    either, then validate the URL shape locally and refuse denied domains and
+ *    private network addresses (with an `error_code`) before yt-dlp sees them.
+ * 2. Register a job and return its ID straight away, so clients behind
+ *    proxies with short idle limits never hold a connection open.
+ * 3. Run the download in a background task using the shared Downloader from AppState.
+ * 4. Record any errors during task execution or download process on the job. On
+ *    timeout, trip the job's cancellation token so yt-dlp is killed, not abandoned.
  * 5. Construct a public-facing file URL by stripping the download directory prefix.
- * 6. Return a JSON response containing the relative URL to the downloaded file.
+ * 6. Mark the job done with that URL; clients read it back from GET /jobs/{id}.
+ *
+ * Optional fields pick the output: `"mode": "audio"` with an `audio_format` of
+ * mp3/m4a/opus/flac extracts the audio instead of the mp4, and `video_quality`,
+ * `video_codec`, `audio_quality` and `audio_codec` override the configured preferences.
+ * `format_id` picks an exact yt-dlp format and `max_height` caps the resolution.
+ * `start` and `end` keep only that section of the video, and `subtitles` lists
+ * the caption languages to embed or publish as .vtt/.srt files.
+ *
+ * With `"playlist": true` the URL is expanded into its videos first and each one
+ * runs through the same steps as its own child job, a few at a time.
  */
 
 use axum::Json;
+use axum::extract::State;
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use futures_util::{StreamExt, stream};
 use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::path::Path;
+use strum::IntoEnumIterator;
 use tokio::time::{Duration, timeout};
-use tracing::error;
+use tracing::{error, info, warn};
 use urlencoding::encode;
-use uuid::Uuid;
 
-use crate::config::Config;
-use crate::handlers::downloader::download_video;
+use crate::config::{Config, parse_codec, parse_quality};
+use crate::handlers::downloader::{download_video, find_subtitle_sidecars, find_thumbnail};
+use crate::handlers::jobs::{BatchItem, JobHandle, JobStatus, PublishedFiles};
+use crate::handlers::options::{
+    AudioFormat, Clip, DownloadMode, DownloadOptions, SubtitleOutput, SubtitleSource, Subtitles,
+    is_valid_format_id, is_valid_language,
+};
+use crate::handlers::rate_limit::{ClientId, Throttled};
+use crate::handlers::ytdlp::{PlaylistEntry, list_playlist};
+use crate::routes::files::THUMBNAIL_URL_NAME;
+use crate::state::AppState;
+use crate::utils::signed_url::signed_query;
+use crate::utils::url_policy::{UrlRejection, check_url};
+use crate::utils::video_id::extract_cache_id;
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Default)]
 pub struct DownloadRequest {
     url: String,
+    /// Treat the URL as a playlist or channel and download every video in it.
+    #[serde(default)]
+    playlist: bool,
+    /// "video" (default) or "audio".
+    mode: Option<String>,
+    /// Audio-only output format: mp3, m4a, opus or flac.
+    audio_format: Option<String>,
+    /// Per-request overrides, parsed like the matching env vars.
+    video_quality: Option<String>,
+    video_codec: Option<String>,
+    audio_quality: Option<String>,
+    audio_codec: Option<String>,
+    /// Exact yt-dlp format ID from GET /info/formats, e.g. "137" or "137+251".
+    format_id: Option<String>,
+    /// Maximum video height in pixels, e.g. 720 or 1080.
+    max_height: Option<u32>,
+    /// Clip start, in seconds ("90.5") or [HH:]MM:SS[.mmm] ("1:30"); defaults to the beginning.
+    start: Option<String>,
+    /// Clip end, in the same format; defaults to the end of the video.
+    end: Option<String>,
+    /// Subtitle languages to fetch, e.g. ["en", "pt-BR"].
+    subtitles: Option<Vec<String>>,
+    /// "manual" (default), "auto" for auto-generated captions, or "all".
+    subtitle_source: Option<String>,
+    /// "embed" into the mp4 (default for videos), or "vtt"/"srt" sidecar files.
+    subtitle_format: Option<String>,
 }
 
 #[derive(Serialize)]
 pub struct DownloadResponse {
     success: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
+    job_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    manifest_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     file_url: Option<String>,
+    /// Where the video's poster image will be served once the job is done.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thumbnail_url: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     error: Option<String>,
+    /// Machine-readable reason for refused URLs, e.g. "blocked_address".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_code: Option<&'static str>,
 }
 
-fn is_valid_video_url(url: &str) -> bool {
+impl DownloadResponse {
+    fn rejected(error: String) -> Self {
+        Self {
+            success: false,
+            job_id: None,
+            status_url: None,
+            manifest_url: None,
+            file_url: None,
+            thumbnail_url: None,
+            error: Some(error),
+            error_code: None,
+        }
+    }
+
+    fn refused(rejection: &UrlRejection) -> Self {
+        Self {
+            error_code: Some(rejection.code()),
+            ..Self::rejected(rejection.to_string())
+        }
+    }
+}
+
+/// 429 with Retry-After, in the same shape as any other rejected download.
+impl IntoResponse for Throttled {
+    fn into_response(self) -> Response {
+        (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(
+                header::RETRY_AFTER,
+                self.retry_after().as_secs().to_string(),
+            )],
+            Json(DownloadResponse::rejected(self.to_string())),
+        )
+            .into_response()
+    }
+}
+
+pub(crate) fn is_valid_video_url(url: &str) -> bool {
     let Ok(uri) = url.parse::<axum::http::Uri>() else {
         return false;
     };
@@ -44,105 +151,427 @@ fn is_valid_video_url(url: &str) -> bool {
     matches!(uri.scheme_str(), Some("http" | "https")) && uri.host().is_some()
 }
 
+/// Resolves the request's optional output fields on top of the configured defaults.
+fn download_options(config: &Config, request: &DownloadRequest) -> Result<DownloadOptions, String> {
+    let mode = match request.mode.as_deref() {
+        Some(mode) => mode
+            .to_lowercase()
+            .parse::<DownloadMode>()
+            .map_err(|_| format!("Unsupported mode '{mode}' (expected video or audio)"))?,
+        None => DownloadMode::Video,
+    };
+    let audio_format = request
+        .audio_format
+        .as_deref()
+        .map(|format| {
+            format.to_lowercase().parse::<AudioFormat>().map_err(|_| {
+                format!("Unsupported audio format '{format}' (expected mp3, m4a, opus or flac)")
+            })
+        })
+        .transpose()?;
+
+    let mut options = DownloadOptions::from_config(config);
+    if let Some(quality) = parse_override("video_quality", &request.video_quality, parse_quality)? {
+        options.video_quality = quality;
+    }
+    if let Some(codec) = parse_override("video_codec", &request.video_codec, parse_codec)? {
+        options.video_codec = codec;
+    }
+    if let Some(quality) = parse_override("audio_quality", &request.audio_quality, parse_quality)? {
+        options.audio_quality = quality;
+    }
+    if let Some(codec) = parse_override("audio_codec", &request.audio_codec, parse_codec)? {
+        options.audio_codec = codec;
+    }
+    match (&request.format_id, request.max_height) {
+        (Some(_), Some(_)) => {
+            return Err("Pass either format_id or max_height, not both".to_string());
+        }
+        (Some(format_id), None) if !is_valid_format_id(format_id) => {
+            return Err(format!("Invalid format_id '{format_id}'"));
+        }
+        (None, Some(0)) => return Err("max_height must be at least 1".to_string()),
+        (format_id, max_height) => {
+            options.format_id = format_id.clone();
+            options.max_height = max_height;
+        }
+    }
+    options.clip = Clip::from_timestamps(request.start.as_deref(), request.end.as_deref())?;
+    options.subtitles = subtitle_options(mode, request)?;
+
+    match mode {
+        DownloadMode::Audio => Ok(options.audio_only(audio_format)),
+        DownloadMode::Video if audio_format.is_some() => {
+            Err("audio_format needs \"mode\": \"audio\"".to_string())
+        }
+        DownloadMode::Video => Ok(options),
+    }
+}
+
+/// Resolves the subtitle fields; sidecars are the only option for audio-only downloads.
+fn subtitle_options(
+    mode: DownloadMode,
+    request: &DownloadRequest,
+) -> Result<Option<Subtitles>, String> {
+    let Some(languages) = &request.subtitles else {
+        if request.subtitle_source.is_some() || request.subtitle_format.is_some() {
+            return Err("subtitle_source and subtitle_format need a subtitles list".to_string());
+        }
+        return Ok(None);
+    };
+    if languages.is_empty() {
+        return Err("subtitles needs at least one language, e.g. [\"en\"]".to_string());
+    }
+    if let Some(language) = languages.iter().find(|l| !is_valid_language(l)) {
+        return Err(format!("Invalid subtitle language '{language}'"));
+    }
+
+    let source = match request.subtitle_source.as_deref() {
+        Some(source) => source
+            .to_lowercase()
+            .parse::<SubtitleSource>()
+            .map_err(|_| {
+                format!("Unsupported subtitle_source '{source}' (expected manual, auto or all)")
+            })?,
+        None => SubtitleSource::Manual,
+    };
+    let output = match request.subtitle_format.as_deref() {
+        Some(format) => format
+            .to_lowercase()
+            .parse::<SubtitleOutput>()
+            .map_err(|_| {
+                format!("Unsupported subtitle_format '{format}' (expected embed, vtt or srt)")
+            })?,
+        None if mode == DownloadMode::Audio => SubtitleOutput::Srt,
+        None => SubtitleOutput::Embed,
+    };
+    if mode == DownloadMode::Audio && output == SubtitleOutput::Embed {
+        return Err("Subtitles can only be embedded into videos; use vtt or srt".to_string());
+    }
+
+    Ok(Some(Subtitles {
+        languages: languages.clone(),
+        source,
+        output,
+    }))
+}
+
+/// Parses an optional quality/codec override, listing the accepted values on error.
+fn parse_override<T>(
+    field: &str,
+    value: &Option<String>,
+    parse: fn(&str) -> Option<T>,
+) -> Result<Option<T>, String>
+where
+    T: IntoEnumIterator + Debug,
+{
+    let Some(value) = value.as_deref() else {
+        return Ok(None);
+    };
+    parse(value).map(Some).ok_or_else(|| {
+        let expected: Vec<_> = T::iter().map(|option| format!("{option:?}")).collect();
+        format!(
+            "Unsupported {field} '{value}' (expected one of {})",
+            expected.join(", ")
+        )
+    })
+}
+
+/// Base URL clients should use to reach this server.
+pub(crate) fn public_base_url(config: &Config) -> String {
+    if config.external_url.is_empty() {
+        format!(
+            "{}://{}",
+            match config.use_https {
+                true => "https",
+                false => "http",
+            },
+            config.address()
+        )
+    } else {
+        config.external_url.clone()
+    }
+}
+
+/// Maps a file under the download directory to its /files/{video_id}/{filename} URL,
+/// signed unless PUBLIC_FILE_URLS is set.
+pub(crate) fn public_file_url(config: &Config, file_path: &Path) -> String {
+    let relative_path = file_path
+        .strip_prefix(&config.download_dir)
+        .unwrap_or(file_path);
+
+    // Extract video_id and filename from the relative path
+    let mut path_parts = relative_path.iter();
+    let video_id = path_parts.next().unwrap().to_string_lossy();
+    let filename = path_parts.next().unwrap().to_string_lossy();
+
+    let url = format!(
+        "{}/files/{}/{}",
+        public_base_url(config),
+        video_id,
+        encode(&filename)
+    );
+    with_signature(config, url, &video_id, &filename)
+}
+
+/// The /files/{video_id}/thumbnail URL of a video's poster image.
+pub(crate) fn public_thumbnail_url(config: &Config, video_id: &str) -> String {
+    let url = format!(
+        "{}/files/{}/thumbnail",
+        public_base_url(config),
+        encode(video_id)
+    );
+    with_signature(config, url, video_id, THUMBNAIL_URL_NAME)
+}
+
+fn with_signature(config: &Config, url: String, video_id: &str, file_name: &str) -> String {
+    match signed_query(config, video_id, file_name) {
+        Some(query) => format!("{url}?{query}"),
+        None => url,
+    }
+}
+
 #[axum::debug_handler]
-pub async fn download_handler(Json(payload): Json<DownloadRequest>) -> Json<DownloadResponse> {
-    let job_id = Uuid::new_v4().to_string();
-    let url = payload.url.clone();
-    let config = Config::from_env();
+pub async fn download_handler(
+    State(state): State<AppState>,
+    client: ClientId,
+    Json(payload): Json<DownloadRequest>,
+) -> Result<Json<DownloadResponse>, Throttled> {
+    // Every request spends a token, even one that turns out to be invalid
+    let limits = &state.client_limits;
+    limits
+        .check_rate(&state.config, &client)
+        .inspect_err(|throttled| warn!(client = %client, "{throttled}"))?;
 
     // Validate the URL shape locally. yt-dlp performs definitive extractor validation.
-    if !is_valid_video_url(&url) {
-        return Json(DownloadResponse {
-            success: false,
-            file_url: None,
-            error: Some("Unsupported or invalid video URL".to_string()),
-        });
+    if !is_valid_video_url(&payload.url) {
+        return Ok(Json(DownloadResponse::rejected(
+            "Unsupported or invalid video URL".to_string(),
+        )));
     }
+    if let Err(rejection) = check_url(&state.config, &payload.url).await {
+        warn!(url = %payload.url, code = rejection.code(), "Refused download URL");
+        return Ok(Json(DownloadResponse::refused(&rejection)));
+    }
+    let options = match download_options(&state.config, &payload) {
+        Ok(options) => options,
+        Err(e) => return Ok(Json(DownloadResponse::rejected(e))),
+    };
+    // Held by the background task until the job (or whole playlist) finishes
+    let slot = limits
+        .claim_job(&state.config, &client)
+        .inspect_err(|throttled| warn!(client = %client, "{throttled}"))?;
+
+    let job = state.jobs.create(&payload.url);
+    let job_id = job.id().to_string();
+    let status_url = format!("{}/jobs/{}", public_base_url(&state.config), job_id);
+    let manifest_url = payload.playlist.then(|| format!("{status_url}/manifest"));
+    // Known up front when the video ID can be read from the URL
+    let thumbnail_url = extract_cache_id(&payload.url)
+        .filter(|_| !payload.playlist)
+        .map(|video_id| public_thumbnail_url(&state.config, &video_id));
+    tokio::spawn(async move {
+        let _slot = slot;
+        if payload.playlist {
+            run_playlist_job(state, job, payload.url, options).await;
+        } else {
+            run_download_job(state, job, payload.url, options).await;
+        }
+    });
+
+    Ok(Json(DownloadResponse {
+        success: true,
+        status_url: Some(status_url),
+        manifest_url,
+        job_id: Some(job_id),
+        file_url: None,
+        thumbnail_url,
+        error: None,
+        error_code: None,
+    }))
+}
+
+/*
+ * Background half of a download request.
+ * Runs download_video under the configured timeout and records the outcome on the job.
+ */
+async fn run_download_job(state: AppState, job: JobHandle, url: String, options: DownloadOptions) {
+    let config = state.config.clone();
+    let sidecar_subtitles = options.sidecar_subtitles().cloned();
 
-    // Run the download_video function on a blocking thread since it performs sync operations
-    let job_id_clone = job_id.clone();
+    // Run download_video in its own task so a panic is reported on the job
+    let worker_job = job.clone();
     let result = timeout(
         Duration::from_secs(config.timeout_seconds),
-        tokio::task::spawn_blocking(move || download_video(url, job_id).map_err(|e| e.to_string())),
+        tokio::spawn(async move {
+            download_video(&state, url, &options, &worker_job)
+                .await
+                .map_err(|e| e.to_string())
+        }),
     )
     .await;
 
-    let (file_path, _duration) = match result {
+    let download = match result {
         Ok(task_result) => match task_result {
             Ok(download_result) => match download_result {
-                Ok(result) => result,
+                Ok(download) => download,
                 Err(e) => {
                     // The downloader already error-logged the failure with full context
-                    return Json(DownloadResponse {
-                        success: false,
-                        file_url: None,
-                        error: Some(format!("Download error: {}", e)),
-                    });
+                    job.fail(format!("Download error: {}", e));
+                    return;
                 }
             },
             Err(e) => {
-                error!(job = %job_id_clone, error = %e, "Worker task panicked");
-                return Json(DownloadResponse {
-                    success: false,
-                    file_url: None,
-                    error: Some(format!("Task join error: {}", e)),
-                });
+                error!(job = %job.id(), error = %e, "Worker task panicked");
+                job.fail(format!("Task join error: {}", e));
+                return;
             }
         },
         Err(_) => {
-            error!(job = %job_id_clone, timeout_s = config.timeout_seconds, "Download timed out");
-            return Json(DownloadResponse {
-                success: false,
-                file_url: None,
-                error: Some("Download timeout".to_string()),
-            });
+            error!(job = %job.id(), timeout_s = config.timeout_seconds, "Download timed out");
+            job.fail("Download timeout".to_string());
+            job.cancellation().cancel();
+            return;
         }
     };
 
-    // Create a full file URL pointing to our file serving endpoint
-    let relative_path = file_path
-        .strip_prefix(&config.download_dir)
-        .unwrap_or(&file_path);
+    // Create full file URLs pointing to our file serving endpoint
+    let file_path = download.path;
+    let subtitle_urls = sidecar_subtitles
+        .map(|subtitles| find_subtitle_sidecars(&file_path, &subtitles))
+        .unwrap_or_default()
+        .iter()
+        .map(|path| public_file_url(&config, path))
+        .collect();
+    let thumbnail_url = find_thumbnail(&file_path)
+        .and_then(|thumbnail| {
+            thumbnail
+                .parent()?
+                .file_name()?
+                .to_str()
+                .map(str::to_string)
+        })
+        .map(|video_id| public_thumbnail_url(&config, &video_id));
+    job.complete_download(PublishedFiles {
+        file_url: public_file_url(&config, &file_path),
+        thumbnail_url,
+        subtitle_urls,
+        video: download.video,
+    });
+}
 
-    // Extract video_id and filename from the relative path
-    let mut path_parts = relative_path.iter();
-    let video_id = path_parts.next().unwrap().to_string_lossy();
-    let filename = path_parts.next().unwrap().to_string_lossy();
+/*
+ * Background half of a playlist request.
+ * Expands the URL, registers a child job per video and runs each through
+ * run_download_job (cache, coalescing and timeout included). The registry
+ * keeps each child's item in the playlist's list up to date as it runs.
+ * Every entry goes through check_url first: whoever hosts the page picks the
+ * URLs it lists, so they get no more trust than the request's own URL.
+ */
+async fn run_playlist_job(state: AppState, job: JobHandle, url: String, options: DownloadOptions) {
+    job.set_status(JobStatus::FetchingMetadata);
+    let entries =
+        match list_playlist(&url, state.config.max_playlist_items, job.cancellation()).await {
+            Ok(entries) => entries,
+            Err(e) => {
+                error!(job = %job.id(), error = %e, "Playlist expansion failed");
+                job.fail(format!("Playlist error: {}", e));
+                return;
+            }
+        };
+    info!(job = %job.id(), videos = entries.len(), "Playlist expanded");
 
-    let base_url = if config.external_url.is_empty() {
-        format!(
-            "{}://{}",
-            match config.use_https {
-                true => "https",
-                false => "http",
-            },
-            config.address()
-        )
-    } else {
-        config.external_url
-    };
+    let concurrency = state.config.max_concurrent_downloads.max(1);
+    let children = register_entries(&state, &job, entries).await;
+    job.set_status(JobStatus::Downloading);
 
-    let file_url = format!("{}/files/{}/{}", base_url, video_id, encode(&filename));
+    stream::iter(children)
+        .for_each_concurrent(concurrency, |(child, entry)| {
+            run_download_job(state.clone(), child, entry.url, options.clone())
+        })
+        .await;
 
-    Json(DownloadResponse {
-        success: true,
-        file_url: Some(file_url),
-        error: None,
-    })
+    job.complete_batch();
+}
+
+/*
+ * Registers a child job and an item for every playlist entry, in order.
+ * Entries check_url refuses are failed straight away with their error code;
+ * the rest are returned with their child job, ready to download.
+ */
+async fn register_entries(
+    state: &AppState,
+    job: &JobHandle,
+    entries: Vec<PlaylistEntry>,
+) -> Vec<(JobHandle, PlaylistEntry)> {
+    let config = &state.config;
+    let checked: Vec<_> = stream::iter(entries)
+        .map(|entry| async move {
+            let refused = check_url(config, &entry.url).await.err();
+            (entry, refused)
+        })
+        .buffered(config.max_concurrent_downloads.max(1))
+        .collect()
+        .await;
+
+    let mut items = Vec::with_capacity(checked.len());
+    let mut children = Vec::new();
+    for (entry, refused) in checked {
+        let child = state.jobs.create_child(job, &entry.url);
+        let mut item = BatchItem {
+            job_id: child.id().to_string(),
+            url: entry.url.clone(),
+            title: entry.title.clone(),
+            status: JobStatus::Queued,
+            progress: None,
+            file_url: None,
+            error: None,
+            error_code: None,
+        };
+        match refused {
+            Some(rejection) => {
+                warn!(
+                    job = %job.id(),
+                    url = %entry.url,
+                    error = %rejection,
+                    "Refused playlist entry"
+                );
+                child.fail(rejection.to_string());
+                item.status = JobStatus::Failed;
+                item.error = Some(rejection.to_string());
+                item.error_code = Some(rejection.code());
+            }
+            None => children.push((child, entry)),
+        }
+        items.push(item);
+    }
+    job.set_items(items);
+    children
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::{AudioCodecPreferenceEnv, VideoCodecPreferenceEnv, VideoQualityEnv};
+    use axum::http::HeaderMap;
 
     #[tokio::test]
     async fn rejects_invalid_url_without_starting_download() {
-        let Json(response) = download_handler(Json(DownloadRequest {
-            url: "not a URL".to_string(),
-        }))
-        .await;
+        let state = AppState::new(Config::default());
+        let client = ClientId::from_request(&state, &HeaderMap::new(), None);
+        let Json(response) = download_handler(
+            State(state),
+            client,
+            Json(DownloadRequest {
+                url: "not a URL".to_string(),
+                ..DownloadRequest::default()
+            }),
+        )
+        .await
+        .unwrap();
 
         assert!(!response.success);
+        assert!(response.job_id.is_none());
         assert!(response.file_url.is_none());
         assert_eq!(
             response.error.as_deref(),
@@ -150,18 +579,272 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn refuses_private_addresses_with_an_error_code() {
+        let state = AppState::new(Config::default());
+        let client = ClientId::from_request(&state, &HeaderMap::new(), None);
+        let Json(response) = download_handler(
+            State(state.clone()),
+            client,
+            Json(DownloadRequest {
+                url: "http://169.254.169.254/latest/meta-data/".to_string(),
+                ..DownloadRequest::default()
+            }),
+        )
+        .await
+        .unwrap();
+
+        assert!(!response.success);
+        assert!(response.job_id.is_none());
+        assert_eq!(response.error_code, Some("blocked_address"));
+    }
+
+    #[tokio::test]
+    async fn playlist_entries_are_checked_like_submitted_urls() {
+        let state = AppState::new(Config::default());
+        let playlist = state.jobs.create("https://example.com/playlist");
+        let entries = vec![
+            PlaylistEntry {
+                url: "http://169.254.169.254/latest/meta-data/".to_string(),
+                title: None,
+            },
+            PlaylistEntry {
+                url: "https://93.184.215.14/watch".to_string(),
+                title: Some("Fine".to_string()),
+            },
+        ];
+
+        let children = register_entries(&state, &playlist, entries).await;
+
+        let items = state.jobs.get(playlist.id()).unwrap().items.unwrap();
+        assert_eq!(children.len(), 1);
+        assert_eq!(children[0].0.id(), items[1].job_id);
+        assert_eq!(items[0].status, JobStatus::Failed);
+        assert_eq!(items[0].error_code, Some("blocked_address"));
+        assert_eq!(
+            state.jobs.get(&items[0].job_id).unwrap().status,
+            JobStatus::Failed
+        );
+        assert_eq!(items[1].status, JobStatus::Queued);
+    }
+
+    #[tokio::test]
+    async fn answers_429_with_retry_after_once_the_bucket_is_empty() {
+        let state = AppState::new(Config {
+            rate_limit_per_minute: 1,
+            rate_limit_burst: 1,
+            ..Config::default()
+        });
+        let request = || {
+            let client = ClientId::from_request(&state, &HeaderMap::new(), None);
+            download_handler(
+                State(state.clone()),
+                client,
+                Json(DownloadRequest {
+                    url: "not a URL".to_string(),
+                    ..DownloadRequest::default()
+                }),
+            )
+        };
+
+        let first = request().await;
+        let throttled = request().await.map(|_| ()).unwrap_err();
+        let response = throttled.clone().into_response();
+
+        assert!(first.is_ok());
+        assert_eq!(
+            throttled,
+            Throttled::RateLimited {
+                retry_after: Duration::from_secs(60)
+            }
+        );
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "60");
+    }
+
+    #[test]
+    fn audio_mode_and_format_are_validated() {
+        let config = Config::default();
+        let request = |mode: Option<&str>, audio_format: Option<&str>| DownloadRequest {
+            url: "https://www.youtube.com/watch?v=dQw4w9WgXcQ".to_string(),
+            mode: mode.map(str::to_string),
+            audio_format: audio_format.map(str::to_string),
+            ..DownloadRequest::default()
+        };
+
+        let audio = download_options(&config, &request(Some("audio"), Some("MP3"))).unwrap();
+        assert_eq!(audio.mode, DownloadMode::Audio);
+        assert_eq!(audio.extension(), "mp3");
+
+        let video = download_options(&config, &request(None, None)).unwrap();
+        assert_eq!(video, DownloadOptions::from_config(&config));
+
+        let err = download_options(&config, &request(Some("audio"), Some("wav"))).unwrap_err();
+        assert!(err.contains("Unsupported audio format 'wav'"));
+        assert!(download_options(&config, &request(Some("gif"), None)).is_err());
+        assert!(download_options(&config, &request(None, Some("mp3"))).is_err());
+    }
+
+    #[test]
+    fn quality_and_codec_overrides_parse_like_env_vars() {
+        let config = Config::default();
+        let request = DownloadRequest {
+            url: "https://www.youtube.com/watch?v=dQw4w9WgXcQ".to_string(),
+            video_quality: Some("Low".to_string()),
+            video_codec: Some("AVC1".to_string()),
+            audio_codec: Some("aac".to_string()),
+            ..DownloadRequest::default()
+        };
+
+        let options = download_options(&config, &request).unwrap();
+        assert_eq!(options.video_quality, VideoQualityEnv::Low);
+        assert_eq!(options.video_codec, VideoCodecPreferenceEnv::AVC1);
+        assert_eq!(options.audio_quality, config.audio_quality);
+        assert_eq!(options.audio_codec, AudioCodecPreferenceEnv::Aac);
+        assert_ne!(options.variant_key(), "");
+
+        // Quality names are PascalCase, just like VIDEO_QUALITY
+        let err = download_options(
+            &config,
+            &DownloadRequest {
+                video_quality: Some("low".to_string()),
+                ..request
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            "Unsupported video_quality 'low' (expected one of Best, High, Medium, Low, Worst)"
+        );
+    }
+
+    #[test]
+    fn format_id_and_max_height_are_validated() {
+        let config = Config::default();
+        let request = |format_id: Option<&str>, max_height: Option<u32>| DownloadRequest {
+            url: "https://www.youtube.com/watch?v=dQw4w9WgXcQ".to_string(),
+            format_id: format_id.map(str::to_string),
+            max_height,
+            ..DownloadRequest::default()
+        };
+
+        let exact = download_options(&config, &request(Some("137+251"), None)).unwrap();
+        assert_eq!(exact.format_id.as_deref(), Some("137+251"));
+        let capped = download_options(&config, &request(None, Some(720))).unwrap();
+        assert_eq!(capped.max_height, Some(720));
+
+        assert!(download_options(&config, &request(Some("137"), Some(720))).is_err());
+        assert!(download_options(&config, &request(Some("best/--exec"), None)).is_err());
+        assert!(download_options(&config, &request(None, Some(0))).is_err());
+    }
+
+    #[test]
+    fn clip_range_is_parsed_and_checked() {
+        let config = Config::default();
+        let request = |start: Option<&str>, end: Option<&str>| DownloadRequest {
+            url: "https://www.youtube.com/watch?v=dQw4w9WgXcQ".to_string(),
+            start: start.map(str::to_string),
+            end: end.map(str::to_string),
+            ..DownloadRequest::default()
+        };
+
+        let clipped =
+            download_options(&config, &request(Some("1:00:00"), Some("1:00:30"))).unwrap();
+        assert_eq!(clipped.variant_key(), "clip-3600-3630");
+        assert!(
+            download_options(&config, &request(None, None))
+                .unwrap()
+                .clip
+                .is_none()
+        );
+
+        let error = download_options(&config, &request(Some("2:00"), Some("1:00"))).unwrap_err();
+        assert!(error.contains("after start"));
+        assert!(download_options(&config, &request(Some("soon"), None)).is_err());
+    }
+
+    #[test]
+    fn subtitle_fields_are_validated() {
+        let config = Config::default();
+        let request = |mode: Option<&str>, languages: Option<&[&str]>, format: Option<&str>| {
+            DownloadRequest {
+                url: "https://www.youtube.com/watch?v=dQw4w9WgXcQ".to_string(),
+                mode: mode.map(str::to_string),
+                subtitles: languages
+                    .map(|languages| languages.iter().map(|l| l.to_string()).collect()),
+                subtitle_format: format.map(str::to_string),
+                ..DownloadRequest::default()
+            }
+        };
+
+        let embedded = download_options(&config, &request(None, Some(&["en"]), None)).unwrap();
+        assert_eq!(
+            embedded.subtitles.map(|subtitles| subtitles.output),
+            Some(SubtitleOutput::Embed)
+        );
+        let audio =
+            download_options(&config, &request(Some("audio"), Some(&["en"]), None)).unwrap();
+        assert_eq!(
+            audio.subtitles.map(|subtitles| subtitles.output),
+            Some(SubtitleOutput::Srt)
+        );
+
+        assert!(
+            download_options(
+                &config,
+                &request(Some("audio"), Some(&["en"]), Some("embed"))
+            )
+            .is_err()
+        );
+        assert!(download_options(&config, &request(None, Some(&[]), None)).is_err());
+        assert!(download_options(&config, &request(None, Some(&["en,--exec"]), None)).is_err());
+        assert!(download_options(&config, &request(None, None, Some("vtt"))).is_err());
+    }
+
     #[test]
     fn response_omits_empty_optional_fields() {
         let response = DownloadResponse {
             success: true,
-            file_url: Some("http://localhost/files/id/video.mp4".to_string()),
+            job_id: Some("job".to_string()),
+            status_url: Some("http://localhost/jobs/job".to_string()),
+            manifest_url: None,
+            file_url: None,
+            thumbnail_url: None,
             error: None,
+            error_code: None,
         };
 
         let json = serde_json::to_value(response).expect("response should serialize");
 
         assert_eq!(json["success"], true);
-        assert_eq!(json["file_url"], "http://localhost/files/id/video.mp4");
+        assert_eq!(json["job_id"], "job");
+        assert_eq!(json["status_url"], "http://localhost/jobs/job");
+        assert!(json.get("manifest_url").is_none());
+        assert!(json.get("file_url").is_none());
         assert!(json.get("error").is_none());
     }
+
+    #[test]
+    fn file_url_is_relative_to_download_dir() {
+        let config = Config {
+            external_url: "https://snatchr.example".to_string(),
+            download_dir: "./downloads".to_string(),
+            public_file_urls: true,
+            ..Config::default()
+        };
+
+        let url = public_file_url(
+            &config,
+            Path::new("./downloads/dQw4w9WgXcQ/Never Gonna.mp4"),
+        );
+
+        assert_eq!(
+            url,
+            "https://snatchr.example/files/dQw4w9WgXcQ/Never%20Gonna.mp4"
+        );
+        assert_eq!(
+            public_thumbnail_url(&config, "dQw4w9WgXcQ"),
+            "https://snatchr.example/files/dQw4w9WgXcQ/thumbnail"
+        );
+    }
 }
@@ -1,23 +1,31 @@
 use axum::{
-    extract::{Path, Query},
+    extract::{Path, Query, State},
     http::{HeaderValue, StatusCode},
     response::{IntoResponse, Response},
 };
 use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::Arc;
 use tower::util::ServiceExt;
 use tower_http::services::ServeFile;
 use tracing::{debug, warn};
 
 use crate::config::Config;
+use crate::handlers::downloader::THUMBNAIL_FILE_NAME;
+use crate::utils::signed_url::{SignatureError, verify};
+
+/// Last path segment of thumbnail URLs, and the name their signatures cover.
+pub const THUMBNAIL_URL_NAME: &str = "thumbnail";
 
 /// Serves downloaded files: /files/{video_id}/{filename}
 /// Add ?stream=true to stream video instead of downloading
+/// Answers 403 when the URL's signature is missing, wrong or expired.
 pub async fn serve_file(
+    State(config): State<Arc<Config>>,
     Path((video_id, filename)): Path<(String, String)>,
     Query(params): Query<HashMap<String, String>>,
 ) -> Result<Response, StatusCode> {
-    let config = Config::from_env();
+    check_signature(&config, &video_id, &filename, &params)?;
 
     // Construct path: download_dir/video_id/filename
     let file_path = PathBuf::from(&config.download_dir)
@@ -63,14 +71,67 @@ pub async fn serve_file(
     Ok(response.into_response())
 }
 
+/// Serves a video's poster image: /files/{video_id}/thumbnail
+/// Shown inline, and doesn't count as use of the cached video.
+pub async fn serve_thumbnail(
+    State(config): State<Arc<Config>>,
+    Path(video_id): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Response, StatusCode> {
+    check_signature(&config, &video_id, THUMBNAIL_URL_NAME, &params)?;
+
+    let file_path = PathBuf::from(&config.download_dir)
+        .join(&video_id)
+        .join(THUMBNAIL_FILE_NAME);
+    if !file_path.is_file() {
+        debug!(video = %video_id, "Thumbnail not found");
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let request = axum::http::Request::builder()
+        .body(axum::body::Body::empty())
+        .unwrap();
+    let response = ServeFile::new(&file_path)
+        .oneshot(request)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(response.into_response())
+}
+
+// Checked before anything touches the disk, so refusals don't reveal what exists
+fn check_signature(
+    config: &Config,
+    video_id: &str,
+    file_name: &str,
+    params: &HashMap<String, String>,
+) -> Result<(), StatusCode> {
+    verify(config, video_id, file_name, params).map_err(|e| {
+        let reason = match e {
+            SignatureError::Missing => "missing",
+            SignatureError::Invalid => "invalid",
+            SignatureError::Expired => "expired",
+        };
+        warn!(video = %video_id, file = %file_name, reason, "Refused file URL signature");
+        StatusCode::FORBIDDEN
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::utils::signed_url::signed_query;
+    use crate::utils::test_support::{TestDirectory, query_params};
     use uuid::Uuid;
 
     #[tokio::test]
     async fn returns_not_found_for_missing_file() {
+        let config = Config {
+            public_file_urls: true,
+            ..Config::default()
+        };
         let result = serve_file(
+            State(Arc::new(config)),
             Path((Uuid::new_v4().to_string(), "missing.mp4".to_string())),
             Query(HashMap::new()),
         )
@@ -78,4 +139,65 @@ mod tests {
 
         assert!(matches!(result, Err(StatusCode::NOT_FOUND)));
     }
+
+    #[tokio::test]
+    async fn serves_thumbnail_as_an_image() {
+        let download_dir = TestDirectory::new();
+        let video_dir = download_dir.join("dQw4w9WgXcQ");
+        std::fs::create_dir_all(&video_dir).unwrap();
+        std::fs::write(video_dir.join(THUMBNAIL_FILE_NAME), b"\xFF\xD8\xFF").unwrap();
+        let config = Arc::new(Config {
+            download_dir: download_dir.to_string_lossy().into_owned(),
+            public_file_urls: true,
+            ..Config::default()
+        });
+        let thumbnail = |video_id: &str| {
+            serve_thumbnail(
+                State(config.clone()),
+                Path(video_id.to_string()),
+                Query(HashMap::new()),
+            )
+        };
+
+        let response = thumbnail("dQw4w9WgXcQ")
+            .await
+            .expect("thumbnail should be served");
+        let missing = thumbnail("missing").await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "image/jpeg");
+        assert!(response.headers().get("content-disposition").is_none());
+        assert!(matches!(missing, Err(StatusCode::NOT_FOUND)));
+    }
+
+    #[tokio::test]
+    async fn serves_files_only_with_a_valid_signature() {
+        let download_dir = TestDirectory::new();
+        let video_dir = download_dir.join("youtube-dQw4w9WgXcQ");
+        std::fs::create_dir_all(&video_dir).unwrap();
+        std::fs::write(video_dir.join("Title.mp4"), vec![0_u8; 16]).unwrap();
+        let config = Arc::new(Config {
+            download_dir: download_dir.to_string_lossy().into_owned(),
+            file_url_secret: "test-secret".to_string(),
+            ..Config::default()
+        });
+        let query = signed_query(&config, "youtube-dQw4w9WgXcQ", "Title.mp4").unwrap();
+        let fetch = |filename: &str, query: &str| {
+            serve_file(
+                State(config.clone()),
+                Path(("youtube-dQw4w9WgXcQ".to_string(), filename.to_string())),
+                Query(query_params(query)),
+            )
+        };
+
+        let signed = fetch("Title.mp4", &query).await;
+        let unsigned = fetch("Title.mp4", "").await;
+        let tampered = fetch("Title.mp4", &query.replace("expires=", "expires=9")).await;
+        let other_file = fetch("Other.mp4", &query).await;
+
+        assert_eq!(signed.map(|response| response.status()), Ok(StatusCode::OK));
+        assert!(matches!(unsigned, Err(StatusCode::FORBIDDEN)));
+        assert!(matches!(tampered, Err(StatusCode::FORBIDDEN)));
+        assert!(matches!(other_file, Err(StatusCode::FORBIDDEN)));
+    }
 }
@@ -0,0 +1,216 @@
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::warn;
+
+use crate::handlers::downloader::fetch_video_info;
+use crate::handlers::info::{FormatInfo, VideoInfo};
+use crate::routes::download::is_valid_video_url;
+use crate::state::AppState;
+use crate::utils::url_policy::check_url;
+
+#[derive(Deserialize)]
+pub struct InfoQuery {
+    url: String,
+}
+
+#[derive(Serialize)]
+pub struct InfoError {
+    success: bool,
+    error: String,
+    /// Machine-readable reason for refused URLs, e.g. "domain_denied".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_code: Option<&'static str>,
+}
+
+#[derive(Serialize)]
+pub struct FormatList {
+    id: String,
+    title: String,
+    /// Distinct video heights, highest first; any of them works as `max_height`.
+    heights: Vec<u64>,
+    /// Every format yt-dlp offers; any `format_id` works as `format_id`.
+    formats: Vec<FormatInfo>,
+}
+
+impl FormatList {
+    fn from_info(info: &VideoInfo) -> Self {
+        let mut heights: Vec<u64> = info
+            .formats
+            .iter()
+            .filter(|format| format.vcodec.is_some())
+            .filter_map(|format| format.height)
+            .collect();
+        heights.sort_unstable_by(|a, b| b.cmp(a));
+        heights.dedup();
+
+        Self {
+            id: info.id.clone(),
+            title: info.title.clone(),
+            heights,
+            formats: info.formats.clone(),
+        }
+    }
+}
+
+fn info_error(status: StatusCode, error: String) -> (StatusCode, Json<InfoError>) {
+    (
+        status,
+        Json(InfoError {
+            success: false,
+            error,
+            error_code: None,
+        }),
+    )
+}
+
+/// Returns a video's metadata without downloading it: GET /info?url=...
+/// Title, duration, uploader, thumbnails and formats, cached per video ID for a few minutes.
+pub async fn video_info(
+    State(state): State<AppState>,
+    Query(query): Query<InfoQuery>,
+) -> Result<Json<VideoInfo>, (StatusCode, Json<InfoError>)> {
+    lookup(&state, &query.url)
+        .await
+        .map(|info| Json(info.as_ref().clone()))
+}
+
+/// Lists the formats a video can be downloaded in: GET /info/formats?url=...
+/// Pick a `format_id` or one of the `heights` and pass it to POST /download.
+pub async fn video_formats(
+    State(state): State<AppState>,
+    Query(query): Query<InfoQuery>,
+) -> Result<Json<FormatList>, (StatusCode, Json<InfoError>)> {
+    lookup(&state, &query.url)
+        .await
+        .map(|info| Json(FormatList::from_info(&info)))
+}
+
+async fn lookup(
+    state: &AppState,
+    url: &str,
+) -> Result<Arc<VideoInfo>, (StatusCode, Json<InfoError>)> {
+    if !is_valid_video_url(url) {
+        return Err(info_error(
+            StatusCode::BAD_REQUEST,
+            "Unsupported or invalid video URL".to_string(),
+        ));
+    }
+    if let Err(rejection) = check_url(&state.config, url).await {
+        warn!(url = %url, code = rejection.code(), "Refused info URL");
+        let (status, Json(mut body)) = info_error(StatusCode::FORBIDDEN, rejection.to_string());
+        body.error_code = Some(rejection.code());
+        return Err((status, Json(body)));
+    }
+
+    fetch_video_info(state, url).await.map_err(|e| {
+        warn!(url = %url, error = %e, "Metadata lookup failed");
+        info_error(StatusCode::BAD_GATEWAY, format!("Metadata error: {}", e))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[tokio::test]
+    async fn rejects_invalid_url_without_fetching() {
+        let result = video_info(
+            State(AppState::new(Config::default())),
+            Query(InfoQuery {
+                url: "not a URL".to_string(),
+            }),
+        )
+        .await;
+
+        let Err((status, Json(body))) = result else {
+            panic!("invalid URL should be rejected");
+        };
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(!body.success);
+    }
+
+    #[tokio::test]
+    async fn serves_recently_fetched_info_from_cache() {
+        let state = AppState::new(Config::default());
+        state.info_cache.insert(VideoInfo {
+            id: "dQw4w9WgXcQ".to_string(),
+            title: "Never Gonna Give You Up".to_string(),
+            webpage_url: None,
+            extractor: Some("Youtube".to_string()),
+            duration_seconds: Some(213.0),
+            uploader: None,
+            upload_date: None,
+            view_count: None,
+            thumbnail: None,
+            thumbnails: Vec::new(),
+            formats: Vec::new(),
+        });
+
+        let Json(info) = video_info(
+            State(state),
+            Query(InfoQuery {
+                url: "https://youtu.be/dQw4w9WgXcQ".to_string(),
+            }),
+        )
+        .await
+        .map_err(|(status, _)| status)
+        .expect("cached info should be returned");
+
+        assert_eq!(info.title, "Never Gonna Give You Up");
+        assert_eq!(info.duration_seconds, Some(213.0));
+    }
+
+    #[tokio::test]
+    async fn format_list_offers_distinct_heights() {
+        let format = |format_id: &str, height: Option<u64>, vcodec: Option<&str>| FormatInfo {
+            format_id: format_id.to_string(),
+            ext: None,
+            width: None,
+            height,
+            fps: None,
+            vcodec: vcodec.map(str::to_string),
+            acodec: None,
+            filesize: None,
+            tbr: None,
+            note: None,
+        };
+        let state = AppState::new(Config::default());
+        state.info_cache.insert(VideoInfo {
+            id: "dQw4w9WgXcQ".to_string(),
+            title: "Never Gonna Give You Up".to_string(),
+            webpage_url: None,
+            extractor: Some("Youtube".to_string()),
+            duration_seconds: None,
+            uploader: None,
+            upload_date: None,
+            view_count: None,
+            thumbnail: None,
+            thumbnails: Vec::new(),
+            formats: vec![
+                format("251", None, None),
+                format("136", Some(720), Some("avc1")),
+                format("247", Some(720), Some("vp9")),
+                format("137", Some(1080), Some("avc1")),
+            ],
+        });
+
+        let Json(list) = video_formats(
+            State(state),
+            Query(InfoQuery {
+                url: "https://www.youtube.com/watch?v=dQw4w9WgXcQ".to_string(),
+            }),
+        )
+        .await
+        .map_err(|(status, _)| status)
+        .expect("cached formats should be returned");
+
+        assert_eq!(list.heights, [1080, 720]);
+        assert_eq!(list.formats.len(), 4);
+    }
+}
@@ -0,0 +1,188 @@
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures_util::stream::{self, Stream};
+use serde::Serialize;
+
+use crate::handlers::jobs::{JobRegistry, JobSnapshot, JobStatus};
+
+/// File links of a playlist job, as returned by GET /jobs/{job_id}/manifest.
+#[derive(Serialize)]
+pub struct Manifest {
+    job_id: String,
+    status: JobStatus,
+    files: Vec<ManifestFile>,
+}
+
+#[derive(Serialize)]
+struct ManifestFile {
+    url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    file_url: String,
+}
+
+/// Reports the state of a download job: /jobs/{job_id}
+/// Once the job is done the response carries the final file_url (or error).
+pub async fn job_status(
+    State(jobs): State<JobRegistry>,
+    Path(job_id): Path<String>,
+) -> Result<Json<JobSnapshot>, StatusCode> {
+    jobs.get(&job_id).map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Cancels a queued or running job: DELETE /jobs/{job_id}
+/// Kills the underlying yt-dlp process and scrubs its temp files.
+/// Answers 409 Conflict when the job has already finished.
+pub async fn cancel_job(
+    State(jobs): State<JobRegistry>,
+    Path(job_id): Path<String>,
+) -> Result<Json<JobSnapshot>, StatusCode> {
+    match jobs.cancel(&job_id) {
+        None => Err(StatusCode::NOT_FOUND),
+        Some(false) => Err(StatusCode::CONFLICT),
+        Some(true) => jobs.get(&job_id).map(Json).ok_or(StatusCode::NOT_FOUND),
+    }
+}
+
+/// Lists the file URLs of a playlist job's finished videos: /jobs/{job_id}/manifest
+/// Grows while the playlist downloads; 404 for unknown jobs and single-video jobs.
+pub async fn job_manifest(
+    State(jobs): State<JobRegistry>,
+    Path(job_id): Path<String>,
+) -> Result<Json<Manifest>, StatusCode> {
+    let snapshot = jobs.get(&job_id).ok_or(StatusCode::NOT_FOUND)?;
+    let items = snapshot.items.ok_or(StatusCode::NOT_FOUND)?;
+
+    let files = items
+        .into_iter()
+        .filter_map(|item| {
+            Some(ManifestFile {
+                file_url: item.file_url?,
+                url: item.url,
+                title: item.title,
+            })
+        })
+        .collect();
+
+    Ok(Json(Manifest {
+        job_id: snapshot.job_id,
+        status: snapshot.status,
+        files,
+    }))
+}
+
+/// Streams job updates as Server-Sent Events: /jobs/{job_id}/events
+/// Each event carries the job JSON, including percent, bytes, speed and ETA while
+/// downloading. The stream ends once the job is done or failed.
+pub async fn job_events(
+    State(jobs): State<JobRegistry>,
+    Path(job_id): Path<String>,
+) -> Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>, StatusCode> {
+    let updates = jobs.subscribe(&job_id).ok_or(StatusCode::NOT_FOUND)?;
+
+    // First event is the current state, then one per change (bursts coalesce)
+    let events = stream::unfold(Some((updates, true)), |state| async move {
+        let (mut updates, first) = state?;
+        if !first && updates.changed().await.is_err() {
+            return None;
+        }
+
+        let snapshot = updates.borrow_and_update().clone();
+        let next = (!snapshot.status.is_finished()).then_some((updates, false));
+        Some((Event::default().json_data(&snapshot), next))
+    });
+
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::jobs::BatchItem;
+
+    #[tokio::test]
+    async fn returns_snapshot_for_known_job() {
+        let jobs = JobRegistry::default();
+        let job = jobs.create("https://example.com/video");
+
+        let Json(snapshot) = job_status(State(jobs), Path(job.id().to_string()))
+            .await
+            .expect("job should be found");
+
+        assert_eq!(snapshot.job_id, job.id());
+        assert_eq!(snapshot.status, JobStatus::Queued);
+    }
+
+    #[tokio::test]
+    async fn returns_not_found_for_unknown_job() {
+        let result = job_status(State(JobRegistry::default()), Path("missing".to_string())).await;
+
+        assert!(matches!(result, Err(StatusCode::NOT_FOUND)));
+    }
+
+    #[tokio::test]
+    async fn cancels_running_job_once() {
+        let jobs = JobRegistry::default();
+        let job = jobs.create("https://example.com/video");
+
+        let Json(snapshot) = cancel_job(State(jobs.clone()), Path(job.id().to_string()))
+            .await
+            .expect("running job should be cancellable");
+        assert_eq!(snapshot.status, JobStatus::Cancelled);
+        assert!(job.cancellation().is_cancelled());
+
+        let again = cancel_job(State(jobs), Path(job.id().to_string())).await;
+        assert!(matches!(again, Err(StatusCode::CONFLICT)));
+    }
+
+    #[tokio::test]
+    async fn manifest_lists_finished_playlist_videos() {
+        let jobs = JobRegistry::default();
+        let playlist = jobs.create("https://example.com/playlist");
+        let item = |url: &str, file_url: Option<&str>| BatchItem {
+            job_id: url.to_string(),
+            url: url.to_string(),
+            title: None,
+            status: if file_url.is_some() {
+                JobStatus::Done
+            } else {
+                JobStatus::Downloading
+            },
+            progress: None,
+            file_url: file_url.map(str::to_string),
+            error: None,
+            error_code: None,
+        };
+        playlist.set_items(vec![
+            item(
+                "https://example.com/one",
+                Some("http://localhost/files/one/a.mp4"),
+            ),
+            item("https://example.com/two", None),
+        ]);
+
+        let Json(manifest) = job_manifest(State(jobs.clone()), Path(playlist.id().to_string()))
+            .await
+            .expect("playlist job should have a manifest");
+        assert_eq!(manifest.files.len(), 1);
+        assert_eq!(
+            manifest.files[0].file_url,
+            "http://localhost/files/one/a.mp4"
+        );
+
+        let single = jobs.create("https://example.com/video");
+        let result = job_manifest(State(jobs), Path(single.id().to_string())).await;
+        assert!(matches!(result, Err(StatusCode::NOT_FOUND)));
+    }
+
+    #[tokio::test]
+    async fn events_are_not_found_for_unknown_job() {
+        let result = job_events(State(JobRegistry::default()), Path("missing".to_string())).await;
+
+        assert!(matches!(result, Err(StatusCode::NOT_FOUND)));
+    }
+}
@@ -0,0 +1,124 @@
+use axum::{
+    Json,
+    extract::State,
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::handlers::rate_limit::{ClientId, ClientLimits};
+use crate::utils::auth::{ApiKeys, SESSION_COOKIE};
+
+/*
+ * Web UI login.
+ *
+ * The page can't attach headers to video players and download links, so it
+ * trades an API key (or WEB_UI_SECRET) for an HttpOnly cookie holding an HMAC
+ * of that key. The auth middleware accepts the cookie in place of the key, and
+ * SameSite=Strict keeps other sites from riding on it. Wrong keys are counted
+ * per client, so the form can't be used to guess keys at full speed.
+ */
+
+// How long the browser keeps the login
+const SESSION_MAX_AGE_SECS: u64 = 30 * 24 * 60 * 60;
+
+#[derive(Deserialize)]
+pub struct LoginRequest {
+    key: String,
+}
+
+/// Logs the web page in: POST /login with {"key": "..."}
+/// Answers 204 with the session cookie, 401 when the key isn't configured, or
+/// 429 once the client has sent too many wrong keys.
+pub async fn login(
+    State(keys): State<ApiKeys>,
+    State(config): State<Arc<Config>>,
+    State(limits): State<ClientLimits>,
+    client: ClientId,
+    Json(request): Json<LoginRequest>,
+) -> Response {
+    if let Err(throttled) = limits.check_login(&client) {
+        warn!(client = %client, "{throttled}");
+        return throttled.into_response();
+    }
+    let Some(session) = keys.session(request.key.trim()) else {
+        limits.record_failed_login(&client);
+        warn!(client = %client, "Rejected web UI login with an unknown key");
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    info!("Web UI logged in");
+    let cookie = session_cookie(&config, session, SESSION_MAX_AGE_SECS);
+    (StatusCode::NO_CONTENT, [(header::SET_COOKIE, cookie)]).into_response()
+}
+
+/// Logs the web page out again: POST /logout
+pub async fn logout(State(config): State<Arc<Config>>) -> Response {
+    let cookie = session_cookie(&config, "", 0);
+    (StatusCode::NO_CONTENT, [(header::SET_COOKIE, cookie)]).into_response()
+}
+
+fn session_cookie(config: &Config, value: &str, max_age: u64) -> String {
+    let secure = config.use_https || config.external_url.starts_with("https://");
+    format!(
+        "{SESSION_COOKIE}={value}; Path=/; Max-Age={max_age}; HttpOnly; SameSite=Strict{}",
+        if secure { "; Secure" } else { "" }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::AppState;
+    use axum::http::HeaderMap;
+
+    #[tokio::test]
+    async fn sets_a_cookie_only_for_configured_keys() {
+        let config = Config {
+            web_ui_secret: "open sesame".to_string(),
+            use_https: true,
+            ..Config::default()
+        };
+        let keys = ApiKeys::from_config(&config);
+        let config = Arc::new(config);
+        let limits = ClientLimits::default();
+        let client =
+            ClientId::from_request(&AppState::new(Config::default()), &HeaderMap::new(), None);
+        let attempt = |key: &str| {
+            login(
+                State(keys.clone()),
+                State(config.clone()),
+                State(limits.clone()),
+                client.clone(),
+                Json(LoginRequest {
+                    key: key.to_string(),
+                }),
+            )
+        };
+
+        let accepted = attempt("open sesame").await;
+        let rejected = attempt("guess").await;
+
+        assert_eq!(accepted.status(), StatusCode::NO_CONTENT);
+        assert_eq!(
+            accepted.headers()[header::SET_COOKIE],
+            format!(
+                "snatchr_key={}; Path=/; Max-Age=2592000; HttpOnly; SameSite=Strict; Secure",
+                keys.session("open sesame").unwrap()
+            )
+        );
+        assert_eq!(rejected.status(), StatusCode::UNAUTHORIZED);
+        assert!(rejected.headers().get(header::SET_COOKIE).is_none());
+
+        // Four more wrong keys use up the client's tries, even for the right key
+        for _ in 0..4 {
+            attempt("guess").await;
+        }
+        let throttled = attempt("open sesame").await;
+        assert_eq!(throttled.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(throttled.headers()[header::RETRY_AFTER], "60");
+    }
+}
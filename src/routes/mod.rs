@@ -1,12 +1,20 @@
 /*
  * Module declaration for the routes.
  *
+ * Contains the cache route, which lists and evicts cached videos.
  * Contains the download route, which handles the download request.
  * Contains the files route, which handles file serving.
  * Contains the health route, which handles health checks.
+ * Contains the info route, which returns video metadata without downloading.
+ * Contains the jobs route, which reports download job status.
+ * Contains the login route, which logs the web page in with an API key.
  * Contains the page route, which serves the HTML download page.
  */
+pub mod cache;
 pub mod download;
 pub mod files;
 pub mod health;
+pub mod info;
+pub mod jobs;
+pub mod login;
 pub mod page;
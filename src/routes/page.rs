@@ -1,5 +1,7 @@
 use crate::config::Config;
+use axum::extract::State;
 use axum::response::Html;
+use std::sync::Arc;
 
 /*
  * HTTP handler for serving the download page.
@@ -9,9 +11,7 @@ use axum::response::Html;
  */
 
 #[axum::debug_handler]
-pub async fn download_page() -> Html<&'static str> {
-    let config = Config::from_env();
-
+pub async fn download_page(State(config): State<Arc<Config>>) -> Html<&'static str> {
     if !config.enable_web_ui {
         return Html(
             r##"
@@ -134,9 +134,19 @@ pub async fn download_page() -> Html<&'static str> {
         <div class="badge"><span class="badge-dot"></span>API-only mode</div>
         <p class="lede">The web interface is disabled on this server, but the API is alive and kicking. Point your requests at these endpoints:</p>
         <div class="endpoints">
-            <div class="endpoint"><code>POST /download</code><span>Download a video — JSON body with a url field</span></div>
+            <div class="endpoint"><code>POST /download</code><span>Queue a download — JSON body with a url field, returns a job_id</span></div>
+            <div class="endpoint"><code>GET /info?url=...</code><span>Video metadata and formats without downloading</span></div>
+            <div class="endpoint"><code>GET /info/formats?url=...</code><span>Format IDs and heights to pick from</span></div>
+            <div class="endpoint"><code>GET /jobs/{job_id}</code><span>Poll a download job until it is done or failed</span></div>
+            <div class="endpoint"><code>DELETE /jobs/{job_id}</code><span>Cancel a job and stop its download</span></div>
+            <div class="endpoint"><code>GET /jobs/{job_id}/events</code><span>Live job progress as Server-Sent Events</span></div>
+            <div class="endpoint"><code>GET /jobs/{job_id}/manifest</code><span>File URLs of a finished playlist job</span></div>
             <div class="endpoint"><code>GET /health</code><span>Check server health</span></div>
+            <div class="endpoint"><code>GET /cache</code><span>List cached videos with their size, last access and variants</span></div>
+            <div class="endpoint"><code>DELETE /cache/{video_id}</code><span>Evict one cached video and all its files</span></div>
+            <div class="endpoint"><code>PUT /cache/{video_id}/pin</code><span>Pin a cached video so cleanup never removes it (DELETE to unpin)</span></div>
             <div class="endpoint"><code>GET /files/{video_id}/{filename}</code><span>Fetch a downloaded file</span></div>
+            <div class="endpoint"><code>GET /files/{video_id}/thumbnail</code><span>Poster image of a downloaded video</span></div>
         </div>
         <div class="hint">Want the pretty version? Set <code>ENABLE_WEB_UI=true</code> and restart.</div>
     </main>
@@ -406,14 +416,24 @@ pub async fn download_page() -> Html<&'static str> {
             animation: glowPulse 2s ease-in-out infinite;
         }
         @keyframes glowPulse { 50% { opacity: 0.5; transform: translateX(-50%) scale(0.92); } }
-        .loader-quip {
+        .loader-stage {
             margin-top: 22px; min-height: 24px;
             font-family: 'JetBrains Mono', monospace; font-size: 14px; color: #d8b4fe;
-            transition: opacity 0.3s ease;
         }
-        .loader-quip.fade { opacity: 0; }
         .loader-note { margin-top: 8px; font-size: 13px; color: var(--muted); }
-        .loader-elapsed {
+        .progress-track {
+            margin-top: 14px; width: 100%; max-width: 360px; height: 8px;
+            border-radius: 999px; overflow: hidden;
+            border: 1px solid var(--border); background: rgba(255, 255, 255, 0.04);
+        }
+        .progress-fill {
+            width: 0%; height: 100%; border-radius: inherit;
+            background: linear-gradient(90deg, var(--violet), var(--fuchsia));
+            transition: width 0.4s ease;
+        }
+        .progress-track.indeterminate .progress-fill { width: 30%; animation: indeterminate 1.4s ease-in-out infinite; }
+        @keyframes indeterminate { from { transform: translateX(-100%); } to { transform: translateX(340%); } }
+        .loader-stats {
             margin-top: 14px; padding: 5px 13px; border-radius: 999px;
             font-family: 'JetBrains Mono', monospace; font-size: 12.5px; color: var(--muted);
             border: 1px solid var(--border); background: rgba(255, 255, 255, 0.03);
@@ -640,6 +660,9 @@ pub async fn download_page() -> Html<&'static str> {
             border: 1px solid var(--border);
         }
         .btn-ghost:hover { background: rgba(255, 255, 255, 0.12); }
+        .login-form { display: flex; flex-wrap: wrap; gap: 10px; margin-top: 14px; }
+        .login-form .input-wrap { flex: 1; min-width: 200px; }
+        .login-form .input-wrap input { padding: 12px 14px; }
         media-controller {
             width: 100%; border-radius: 16px; overflow: hidden;
             --media-primary-color: #c4b5fd;
@@ -761,8 +784,9 @@ pub async fn download_page() -> Html<&'static str> {
                     </div>
                     <div class="hamster-glow"></div>
                 </div>
-                <div id="loaderQuip" class="loader-quip">Feeding the hamster…</div>
-                <div id="loaderElapsed" class="loader-elapsed">0s</div>
+                <div id="loaderStage" class="loader-stage">Queued…</div>
+                <div id="progressTrack" class="progress-track indeterminate"><div id="progressFill" class="progress-fill"></div></div>
+                <div id="loaderStats" class="loader-stats">waiting for the hamster…</div>
                 <div id="loadingHistory" style="width:100%"></div>
             </div>
 
@@ -783,6 +807,11 @@ pub async fn download_page() -> Html<&'static str> {
             }
         }
 
+        // File URLs may already carry a signature in their query string
+        function withQuery(url, param) {
+            return url + (url.includes('?') ? '&' : '?') + param;
+        }
+
         // --- Icons (inline SVG snippets reused in templates) ---
         const ICON_DOWNLOAD = '<svg fill="none" stroke="currentColor" stroke-width="2" viewBox="0 0 24 24"><path stroke-linecap="round" stroke-linejoin="round" d="M12 10v6m0 0l-3-3m3 3l3-3m2 8H7a2 2 0 01-2-2V5a2 2 0 012-2h5.586a1 1 0 01.707.293l5.414 5.414a1 1 0 01.293.707V19a2 2 0 01-2 2z"/></svg>';
         const ICON_CHECK = '<svg fill="none" stroke="currentColor" stroke-width="2.4" viewBox="0 0 24 24"><path stroke-linecap="round" stroke-linejoin="round" d="M5 13l4 4L19 7"/></svg>';
@@ -815,49 +844,68 @@ pub async fn download_page() -> Html<&'static str> {
             }
         });
 
-        // --- Elapsed time while the hamster runs ---
-        let elapsedTimer = null;
-        function startElapsed() {
-            const el = document.getElementById('loaderElapsed');
-            const startedAt = Date.now();
-            el.textContent = '0s';
-            elapsedTimer = setInterval(() => {
-                const total = Math.floor((Date.now() - startedAt) / 1000);
-                const mins = Math.floor(total / 60);
-                const secs = total % 60;
-                el.textContent = mins > 0 ? mins + 'm ' + secs + 's' : secs + 's';
-            }, 1000);
-        }
-        function stopElapsed() {
-            if (elapsedTimer) { clearInterval(elapsedTimer); elapsedTimer = null; }
+        // --- Live progress, fed by the job's event stream ---
+        const STAGES = {
+            queued: 'Queued…',
+            fetching_metadata: 'Fetching metadata…',
+            downloading: 'Downloading…',
+            publishing: 'Finishing up…'
+        };
+        function formatBytes(bytes) {
+            if (!bytes) return '0 B';
+            const units = ['B', 'KB', 'MB', 'GB'];
+            const i = Math.min(Math.floor(Math.log(bytes) / Math.log(1024)), units.length - 1);
+            return (bytes / Math.pow(1024, i)).toFixed(i ? 1 : 0) + ' ' + units[i];
+        }
+        function formatEta(secs) {
+            const mins = Math.floor(secs / 60);
+            return mins > 0 ? mins + 'm ' + (secs % 60) + 's' : secs + 's';
+        }
+        function resetProgress() {
+            document.getElementById('loaderStage').textContent = STAGES.queued;
+            document.getElementById('progressTrack').classList.add('indeterminate');
+            document.getElementById('progressFill').style.width = '';
+            document.getElementById('loaderStats').textContent = 'waiting for the hamster…';
+        }
+        function renderProgress(job) {
+            document.getElementById('loaderStage').textContent = STAGES[job.status] || job.status;
+            const track = document.getElementById('progressTrack');
+            const stats = document.getElementById('loaderStats');
+            const p = job.progress;
+            if (job.status !== 'downloading' || !p || p.percent == null) {
+                track.classList.add('indeterminate');
+                document.getElementById('progressFill').style.width = '';
+                stats.textContent = p ? formatBytes(p.downloaded_bytes) + ' so far' : 'waiting for the hamster…';
+                return;
+            }
+            track.classList.remove('indeterminate');
+            document.getElementById('progressFill').style.width = p.percent.toFixed(1) + '%';
+            const parts = [p.percent.toFixed(1) + '%', formatBytes(p.downloaded_bytes) + ' / ' + formatBytes(p.total_bytes)];
+            if (p.speed_bytes_per_sec) parts.push(formatBytes(p.speed_bytes_per_sec) + '/s');
+            if (p.eta_seconds != null) parts.push('ETA ' + formatEta(p.eta_seconds));
+            stats.textContent = parts.join(' · ');
         }
 
-        // --- Loader quips: the hamster deserves commentary ---
-        const QUIPS = [
-            'Feeding the hamster…',
-            'Wheel spinning at maximum RPM…',
-            'Snatching pixels from the void…',
-            'Negotiating with the server hamsters…',
-            'Untangling the internet tubes…',
-            'Compressing hamster sweat into video…',
-            'Almost there, little guy is giving it everything…'
-        ];
-        let quipTimer = null;
-        function startQuips() {
-            const el = document.getElementById('loaderQuip');
-            let i = 0;
-            el.textContent = QUIPS[0];
-            quipTimer = setInterval(() => {
-                el.classList.add('fade');
-                setTimeout(() => {
-                    i = (i + 1) % QUIPS.length;
-                    el.textContent = QUIPS[i];
-                    el.classList.remove('fade');
-                }, 300);
-            }, 2600);
-        }
-        function stopQuips() {
-            if (quipTimer) { clearInterval(quipTimer); quipTimer = null; }
+        // --- Job events: POST /download answers straight away with a job id ---
+        function waitForJob(jobId) {
+            return new Promise(resolve => {
+                const events = new EventSource('/jobs/' + encodeURIComponent(jobId) + '/events');
+                events.onmessage = function(e) {
+                    const job = JSON.parse(e.data);
+                    renderProgress(job);
+                    if (job.status === 'done') {
+                        events.close();
+                        resolve({ success: true, file_url: job.file_url, thumbnail_url: job.thumbnail_url });
+                    } else if (job.status === 'failed' || job.status === 'cancelled') {
+                        events.close();
+                        resolve({ success: false, error: job.error });
+                    }
+                };
+                events.onerror = function() {
+                    events.close();
+                    resolve({ success: false, error: 'Lost track of the download job.' });
+                };
+            });
         }
 
         // --- Download history ---
@@ -915,6 +963,33 @@ pub async fn download_page() -> Html<&'static str> {
             }
         });
 
+        // --- Login, shown when the server asks for an API key ---
+        const LOGIN_HTML = `
+            This server needs an API key or its shared secret before it will snatch anything.
+            <form id="loginForm" class="login-form" autocomplete="off">
+                <div class="input-wrap">
+                    <input type="password" id="accessKey" placeholder="API key or shared secret" required>
+                </div>
+                <button type="submit" class="btn btn-primary">Unlock</button>
+            </form>
+        `;
+        document.addEventListener('submit', async function(e) {
+            if (e.target.id !== 'loginForm') return;
+            e.preventDefault();
+            try {
+                const res = await fetch('/login', {
+                    method: 'POST',
+                    headers: { 'Content-Type': 'application/json' },
+                    body: JSON.stringify({ key: document.getElementById('accessKey').value })
+                });
+                if (!res.ok) throw new Error('rejected');
+                // The session cookie is set now; retry the download that asked for it
+                document.getElementById('downloadForm').requestSubmit();
+            } catch (_) {
+                shakeInput(e.target.querySelector('.input-wrap'));
+            }
+        });
+
         // --- Main form ---
         const ctaButton = document.getElementById('downloadBtn');
         const CTA_IDLE_HTML = ctaButton.innerHTML;
@@ -927,8 +1002,7 @@ pub async fn download_page() -> Html<&'static str> {
             shell.classList.toggle('is-busy', busy);
         }
 
-        function shakeInput() {
-            const wrap = document.querySelector('.input-wrap');
+        function shakeInput(wrap = document.querySelector('.input-wrap')) {
             wrap.classList.remove('shake');
             void wrap.offsetWidth; /* restart animation */
             wrap.classList.add('shake');
@@ -968,8 +1042,7 @@ pub async fn download_page() -> Html<&'static str> {
             loading.classList.remove('hidden');
             result.classList.add('hidden');
             syncBusyLayout();
-            startQuips();
-            startElapsed();
+            resetProgress();
             loading.scrollIntoView({ behavior: 'smooth', block: 'nearest' });
 
             try {
@@ -989,7 +1062,10 @@ pub async fn download_page() -> Html<&'static str> {
                         headers: { 'Content-Type': 'application/json' },
                         body: JSON.stringify({ url: url })
                     });
-                    data = await response.json();
+                    data = response.status === 401 ? { locked: true } : await response.json();
+                    if (data.success && data.job_id) {
+                        data = await waitForJob(data.job_id);
+                    }
                 }
 
                 if (data.success) {
@@ -1013,7 +1089,7 @@ pub async fn download_page() -> Html<&'static str> {
                             <button type="button" class="btn btn-ghost" data-copy="${data.file_url}">${ICON_COPY}Copy link</button>
                         </div>
                         <media-controller>
-                            <video slot="media" src="${data.file_url}?stream=true" playsinline>
+                            <video slot="media" src="${withQuery(data.file_url, 'stream=true')}"${data.thumbnail_url ? ` poster="${data.thumbnail_url}"` : ''} playsinline>
                                 Your browser does not support the video tag.
                             </video>
                             <media-control-bar>
@@ -1028,6 +1104,8 @@ pub async fn download_page() -> Html<&'static str> {
                         ${historyHTML(downloadHistory.slice(1))}
                     `;
                     result.innerHTML = panelHTML('success', ICON_CHECK, 'Snatched! 🎉', successBody);
+                } else if (data.locked) {
+                    result.innerHTML = panelHTML('warn', ICON_WARN, 'Locked', LOGIN_HTML);
                 } else {
                     result.innerHTML = panelHTML('error', ICON_X, 'Download failed',
                         (data.error || 'An unknown error occurred while processing your request.'));
@@ -1036,8 +1114,6 @@ pub async fn download_page() -> Html<&'static str> {
                 result.innerHTML = panelHTML('error', ICON_WARN, 'Connection error',
                     'Could not reach the server. Check your connection and try again.');
             } finally {
-                stopQuips();
-                stopElapsed();
                 button.disabled = false;
                 button.innerHTML = CTA_IDLE_HTML;
                 loading.classList.add('hidden');
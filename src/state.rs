@@ -0,0 +1,51 @@
+use axum::extract::FromRef;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+use crate::config::Config;
+use crate::handlers::downloader::SharedDownloader;
+use crate::handlers::inflight::InFlightDownloads;
+use crate::handlers::info::InfoCache;
+use crate::handlers::jobs::JobRegistry;
+use crate::handlers::rate_limit::ClientLimits;
+use crate::handlers::short_links::ShortLinkResolver;
+use crate::utils::auth::ApiKeys;
+
+/*
+ * Application state shared by every request.
+ *
+ * Holds the configuration parsed once at startup, the single long-lived yt-dlp
+ * Downloader, the job registry, the in-flight download registry, the
+ * metadata cache, remembered short links, the configured API keys,
+ * per-client rate limits and the MAX_CONCURRENT_DOWNLOADS permits every yt-dlp
+ * run takes. Handlers extract the whole AppState or just the part
+ * they need (e.g. `State<Arc<Config>>`) thanks to FromRef.
+ */
+#[derive(Clone, FromRef)]
+pub struct AppState {
+    pub config: Arc<Config>,
+    pub downloader: SharedDownloader,
+    pub jobs: JobRegistry,
+    pub in_flight: InFlightDownloads,
+    pub info_cache: InfoCache,
+    pub short_links: ShortLinkResolver,
+    pub api_keys: ApiKeys,
+    pub client_limits: ClientLimits,
+    pub ytdlp_permits: Arc<Semaphore>,
+}
+
+impl AppState {
+    pub fn new(config: Config) -> Self {
+        Self {
+            api_keys: ApiKeys::from_config(&config),
+            ytdlp_permits: Arc::new(Semaphore::new(config.max_concurrent_downloads.max(1))),
+            config: Arc::new(config),
+            downloader: SharedDownloader::default(),
+            jobs: JobRegistry::default(),
+            in_flight: InFlightDownloads::default(),
+            info_cache: InfoCache::default(),
+            short_links: ShortLinkResolver::default(),
+            client_limits: ClientLimits::default(),
+        }
+    }
+}
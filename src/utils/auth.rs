@@ -0,0 +1,548 @@
+use axum::{
+    Json,
+    extract::{ConnectInfo, Query, Request, State},
+    http::{HeaderMap, Method, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use hmac::{Hmac, Mac};
+use serde_json::json;
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use strum_macros::{AsRefStr, EnumString};
+use tracing::warn;
+
+use crate::config::Config;
+use crate::handlers::rate_limit::{ClientId, ClientLimits};
+use crate::utils::signed_url::verify;
+
+/*
+ * API key authentication.
+ *
+ * Keys come from API_KEYS (comma-separated) and API_KEYS_FILE (one per line),
+ * each written as "key" or "key:scope+scope". WEB_UI_SECRET adds one more key,
+ * shared by everyone using the web page. Clients send a key as
+ * "Authorization: Bearer <key>" or "X-Api-Key: <key>"; the web page logs in
+ * once through POST /login and a cookie rides along after that. The cookie holds
+ * an HMAC of the key (keyed by FILE_URL_SECRET), never the key itself, so a
+ * leaked cookie can't be sent as an API key. Wrong keys count against the
+ * sender's address just like failed logins do.
+ *
+ * With none of those settings the server stays open, exactly as before. Once
+ * any is set, every route except the page, /health and the login routes needs a
+ * key with the right scope, and a key file that can't be read locks everything.
+ * A signed /files URL is a key of its own, so it can be shared as a link; with
+ * PUBLIC_FILE_URLS there are no signatures and files need a key again.
+ */
+
+/// Name of the cookie the web page's login sets.
+pub const SESSION_COOKIE: &str = "snatchr_key";
+
+/// What a key may do. Admin may do everything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString, AsRefStr)]
+#[strum(serialize_all = "kebab-case")]
+pub enum Scope {
+    /// Start downloads, look up video info and follow jobs.
+    Download,
+    /// Fetch published files and thumbnails.
+    ReadFiles,
+    /// Manage the cache.
+    Admin,
+}
+
+// Scopes for keys written without any, and for WEB_UI_SECRET
+const DEFAULT_SCOPES: [Scope; 2] = [Scope::Download, Scope::ReadFiles];
+
+/// The configured keys, shared by every request.
+#[derive(Clone, Default)]
+pub struct ApiKeys {
+    keys: Arc<Vec<ApiKey>>,
+    // WEB_UI_SECRET, shared by everyone using the web page
+    shared: Option<Arc<str>>,
+    required: bool,
+}
+
+/// One configured key and what it may do.
+struct ApiKey {
+    key: String,
+    scopes: Vec<Scope>,
+    // What the login cookie holds for this key
+    session: String,
+}
+
+/// A key as a request presents it.
+enum Credential {
+    Key(String),
+    Session(String),
+}
+
+impl ApiKeys {
+    /*
+     * Loads the keys from API_KEYS, API_KEYS_FILE and WEB_UI_SECRET.
+     * Malformed entries are skipped with a warning rather than granted anything.
+     */
+    pub fn from_config(config: &Config) -> Self {
+        let mut keys: Vec<_> = config
+            .api_keys
+            .split(',')
+            .filter_map(|entry| parse_entry(entry, "API_KEYS"))
+            .collect();
+
+        if !config.api_keys_file.is_empty() {
+            match std::fs::read_to_string(&config.api_keys_file) {
+                Ok(contents) => keys.extend(
+                    contents
+                        .lines()
+                        .filter(|line| !line.trim_start().starts_with('#'))
+                        .filter_map(|line| parse_entry(line, "API_KEYS_FILE")),
+                ),
+                Err(e) => warn!(
+                    path = %config.api_keys_file,
+                    error = %e,
+                    "Failed to read API_KEYS_FILE, only other keys will work"
+                ),
+            }
+        }
+
+        let secret = config.web_ui_secret.trim();
+        let shared = (!secret.is_empty()).then(|| Arc::from(secret));
+        if !secret.is_empty() {
+            keys.push((secret.to_string(), DEFAULT_SCOPES.to_vec()));
+        }
+
+        let keys = keys
+            .into_iter()
+            .map(|(key, scopes)| ApiKey {
+                session: session_token(config, &key),
+                key,
+                scopes,
+            })
+            .collect();
+        Self {
+            keys: Arc::new(keys),
+            shared,
+            required: is_auth_configured(config),
+        }
+    }
+
+    /// False when no keys are configured and the server is open.
+    pub fn required(&self) -> bool {
+        self.required
+    }
+
+    /// Scopes granted to `presented`, or None when it isn't a configured key.
+    pub fn scopes(&self, presented: &str) -> Option<&[Scope]> {
+        self.find(presented).map(|key| key.scopes.as_slice())
+    }
+
+    /// The login cookie value for `key`, or None when it isn't a configured key.
+    pub fn session(&self, key: &str) -> Option<&str> {
+        self.find(key).map(|key| key.session.as_str())
+    }
+
+    /// The configured key a request carries, if keys are required at all.
+    pub fn presented(&self, headers: &HeaderMap) -> Option<&str> {
+        if !self.required {
+            return None;
+        }
+        let credential = presented_credential(headers)?;
+        self.resolve(&credential).map(|key| key.key.as_str())
+    }
+
+    /// True for WEB_UI_SECRET, which doesn't tell one person from another.
+    pub fn is_shared(&self, key: &str) -> bool {
+        self.shared
+            .as_deref()
+            .is_some_and(|shared| constant_time_eq(shared.as_bytes(), key.as_bytes()))
+    }
+
+    fn find(&self, presented: &str) -> Option<&ApiKey> {
+        self.keys
+            .iter()
+            .find(|key| constant_time_eq(key.key.as_bytes(), presented.as_bytes()))
+    }
+
+    fn resolve(&self, credential: &Credential) -> Option<&ApiKey> {
+        match credential {
+            Credential::Key(key) => self.find(key),
+            Credential::Session(token) => self
+                .keys
+                .iter()
+                .find(|key| constant_time_eq(key.session.as_bytes(), token.as_bytes())),
+        }
+    }
+}
+
+/// True when any of API_KEYS, API_KEYS_FILE or WEB_UI_SECRET is set.
+pub fn is_auth_configured(config: &Config) -> bool {
+    !config.api_keys.trim().is_empty()
+        || !config.api_keys_file.is_empty()
+        || !config.web_ui_secret.trim().is_empty()
+}
+
+/*
+ * Middleware that checks the request's key against the scope its route needs.
+ * Answers 401 when no valid key was sent and 403 when the key lacks the scope.
+ * A client that keeps sending wrong keys gets 429 until it has waited, whatever
+ * it sends next, so it can't tell a right guess from a wrong one any faster.
+ */
+pub async fn require_api_key(
+    State(keys): State<ApiKeys>,
+    State(config): State<Arc<Config>>,
+    State(limits): State<ClientLimits>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(scope) = required_scope(req.method(), req.uri().path()) else {
+        return next.run(req).await;
+    };
+    if !keys.required() || (scope == Scope::ReadFiles && is_signed_file_request(&config, &req)) {
+        return next.run(req).await;
+    }
+
+    let path = req.uri().path().to_string();
+    let Some(credential) = presented_credential(req.headers()) else {
+        warn!(path = %path, "Rejected request without an API key");
+        return rejection(StatusCode::UNAUTHORIZED, "A valid API key is required");
+    };
+    let peer = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    let client = ClientId::from_address(&config, req.headers(), peer);
+    if let Err(throttled) = limits.check_login(&client) {
+        warn!(client = %client, path = %path, "{throttled}");
+        return throttled.into_response();
+    }
+    let Some(scopes) = keys.resolve(&credential).map(|key| key.scopes.as_slice()) else {
+        limits.record_failed_login(&client);
+        warn!(client = %client, path = %path, "Rejected request with an unknown API key");
+        return rejection(StatusCode::UNAUTHORIZED, "A valid API key is required");
+    };
+    if !scopes.contains(&scope) && !scopes.contains(&Scope::Admin) {
+        warn!(path = %path, scope = scope.as_ref(), "Rejected API key without the needed scope");
+        return rejection(
+            StatusCode::FORBIDDEN,
+            &format!("This API key lacks the '{}' scope", scope.as_ref()),
+        );
+    }
+
+    next.run(req).await
+}
+
+/*
+ * The scope a route needs, or None for routes anyone may use.
+ * Routes not listed here are admin-only, so new ones start out locked.
+ */
+fn required_scope(method: &Method, path: &str) -> Option<Scope> {
+    let first = path
+        .trim_start_matches('/')
+        .split('/')
+        .next()
+        .unwrap_or_default();
+    let reading = *method == Method::GET || *method == Method::HEAD;
+    match first {
+        "" | "health" => None,
+        "login" | "logout" if *method == Method::POST => None,
+        "download" | "info" | "jobs" => Some(Scope::Download),
+        "files" if reading => Some(Scope::ReadFiles),
+        _ => Some(Scope::Admin),
+    }
+}
+
+/// True for a /files request whose URL carries a valid, unexpired signature.
+fn is_signed_file_request(config: &Config, req: &Request) -> bool {
+    if config.public_file_urls {
+        return false;
+    }
+    let Some((video_id, file_name)) = req
+        .uri()
+        .path()
+        .strip_prefix("/files/")
+        .and_then(|rest| rest.split_once('/'))
+    else {
+        return false;
+    };
+    let (Ok(video_id), Ok(file_name)) = (
+        urlencoding::decode(video_id),
+        urlencoding::decode(file_name),
+    ) else {
+        return false;
+    };
+    let Ok(Query(params)) = Query::<HashMap<String, String>>::try_from_uri(req.uri()) else {
+        return false;
+    };
+    verify(config, &video_id, &file_name, &params).is_ok()
+}
+
+/// The key sent as a bearer token, an X-Api-Key header or the login cookie.
+fn presented_credential(headers: &HeaderMap) -> Option<Credential> {
+    let header_value = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+
+    if let Some(token) =
+        header_value(header::AUTHORIZATION.as_str()).and_then(|value| value.strip_prefix("Bearer "))
+    {
+        return Some(Credential::Key(token.trim().to_string()));
+    }
+    if let Some(key) = header_value("x-api-key") {
+        return Some(Credential::Key(key.trim().to_string()));
+    }
+    header_value(header::COOKIE.as_str())?
+        .split(';')
+        .find_map(|cookie| {
+            cookie
+                .trim()
+                .strip_prefix(SESSION_COOKIE)?
+                .strip_prefix('=')
+        })
+        .filter(|token| !token.is_empty())
+        .map(|token| Credential::Session(token.to_string()))
+}
+
+// The login cookie's value for `key`. Changing FILE_URL_SECRET logs everyone out.
+fn session_token(config: &Config, key: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(config.file_url_secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(format!("session:{key}").as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+// Parses "key" or "key:scope+scope"; `source` names the setting in warnings
+fn parse_entry(entry: &str, source: &str) -> Option<(String, Vec<Scope>)> {
+    let entry = entry.trim();
+    if entry.is_empty() {
+        return None;
+    }
+    let (key, scopes) = match entry.split_once(':') {
+        Some((key, scopes)) => (key.trim(), Some(scopes)),
+        None => (entry, None),
+    };
+    if key.is_empty() {
+        warn!(source, "Skipping API key entry with an empty key");
+        return None;
+    }
+
+    let Some(scopes) = scopes else {
+        return Some((key.to_string(), DEFAULT_SCOPES.to_vec()));
+    };
+    match scopes
+        .split('+')
+        .map(|scope| scope.trim().parse())
+        .collect::<Result<Vec<Scope>, _>>()
+    {
+        Ok(scopes) => Some((key.to_string(), scopes)),
+        Err(_) => {
+            warn!(
+                source,
+                scopes,
+                "Skipping API key with an unknown scope (expected download, read-files or admin)"
+            );
+            None
+        }
+    }
+}
+
+// Compares without stopping at the first differing byte
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+fn rejection(status: StatusCode, message: &str) -> Response {
+    let body = Json(json!({ "success": false, "error": message }));
+    if status == StatusCode::UNAUTHORIZED {
+        (status, [(header::WWW_AUTHENTICATE, "Bearer")], body).into_response()
+    } else {
+        (status, body).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::AppState;
+    use crate::utils::signed_url::signed_query;
+    use axum::{Router, body::Body, middleware::from_fn_with_state, routing::get};
+    use tower::util::ServiceExt;
+
+    fn config(api_keys: &str, web_ui_secret: &str) -> Config {
+        Config {
+            api_keys: api_keys.to_string(),
+            web_ui_secret: web_ui_secret.to_string(),
+            file_url_secret: "test-secret".to_string(),
+            ..Config::default()
+        }
+    }
+
+    fn keys(api_keys: &str, web_ui_secret: &str) -> ApiKeys {
+        ApiKeys::from_config(&config(api_keys, web_ui_secret))
+    }
+
+    async fn status_for(
+        state: &AppState,
+        method: Method,
+        path: &str,
+        header: Option<(&str, &str)>,
+    ) -> StatusCode {
+        let app = Router::new()
+            .route("/", get(|| async { "page" }))
+            .route("/download", axum::routing::post(|| async { "queued" }))
+            .route("/files/{video_id}/{filename}", get(|| async { "file" }))
+            .route("/cache", get(|| async { "listing" }))
+            .layer(from_fn_with_state(state.clone(), require_api_key));
+        let mut request = Request::builder().method(method).uri(path);
+        if let Some((name, value)) = header {
+            request = request.header(name, value);
+        }
+        let response = app
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        response.status()
+    }
+
+    #[test]
+    fn parses_keys_with_and_without_scopes() {
+        let keys = keys(
+            " reader:read-files , ops:admin,plain, broken:superuser, :download",
+            "hunter2",
+        );
+
+        assert!(keys.required());
+        assert_eq!(keys.scopes("reader"), Some(&[Scope::ReadFiles][..]));
+        assert_eq!(keys.scopes("ops"), Some(&[Scope::Admin][..]));
+        assert_eq!(keys.scopes("plain"), Some(&DEFAULT_SCOPES[..]));
+        assert_eq!(keys.scopes("hunter2"), Some(&DEFAULT_SCOPES[..]));
+        assert!(keys.scopes("broken").is_none());
+        assert!(keys.scopes("").is_none());
+        assert!(!ApiKeys::from_config(&Config::default()).required());
+    }
+
+    #[tokio::test]
+    async fn enforces_scopes_per_route() {
+        let state = AppState::new(config("dl:download,reader:read-files,ops:admin", ""));
+        let bearer = |key: &'static str| Some(("authorization", key));
+
+        assert_eq!(
+            status_for(&state, Method::GET, "/", None).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            status_for(&state, Method::POST, "/download", None).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            status_for(&state, Method::POST, "/download", bearer("Bearer wrong")).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            status_for(&state, Method::POST, "/download", bearer("Bearer dl")).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            status_for(
+                &state,
+                Method::GET,
+                "/files/x/a.mp4",
+                Some(("x-api-key", "dl"))
+            )
+            .await,
+            StatusCode::FORBIDDEN
+        );
+        let token = state.api_keys.session("reader").unwrap();
+        let session = format!("theme=dark; snatchr_key={token}");
+        assert_eq!(
+            status_for(
+                &state,
+                Method::GET,
+                "/files/x/a.mp4",
+                Some(("cookie", &session))
+            )
+            .await,
+            StatusCode::OK
+        );
+        // The cookie holds a session token, not the key
+        assert_eq!(
+            status_for(
+                &state,
+                Method::GET,
+                "/files/x/a.mp4",
+                Some(("cookie", "snatchr_key=reader"))
+            )
+            .await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            status_for(&state, Method::GET, "/cache", Some(("x-api-key", token))).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            status_for(&state, Method::GET, "/cache", Some(("x-api-key", "reader"))).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            status_for(&state, Method::GET, "/cache", Some(("x-api-key", "ops"))).await,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn throttles_clients_that_guess_keys() {
+        let state = AppState::new(config("dl:download", ""));
+        let guess = |key: &'static str| Some(("x-api-key", key));
+
+        for _ in 0..5 {
+            assert_eq!(
+                status_for(&state, Method::POST, "/download", guess("wrong")).await,
+                StatusCode::UNAUTHORIZED
+            );
+        }
+        // Even the right key is refused until the client has waited
+        assert_eq!(
+            status_for(&state, Method::POST, "/download", guess("dl")).await,
+            StatusCode::TOO_MANY_REQUESTS
+        );
+    }
+
+    #[tokio::test]
+    async fn stays_open_without_configured_keys() {
+        let state = AppState::new(Config::default());
+
+        assert_eq!(
+            status_for(&state, Method::POST, "/download", None).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            status_for(&state, Method::GET, "/cache", None).await,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn signed_file_urls_need_no_key() {
+        let state = AppState::new(config("reader:read-files", ""));
+        let query = signed_query(&state.config, "youtube-x", "Never Gonna.mp4").unwrap();
+        let signed = format!("/files/youtube-x/Never%20Gonna.mp4?{query}");
+        let forged = format!("/files/youtube-x/Other.mp4?{query}");
+
+        assert_eq!(
+            status_for(&state, Method::GET, &signed, None).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            status_for(&state, Method::GET, &forged, None).await,
+            StatusCode::UNAUTHORIZED
+        );
+
+        // Public URLs carry no signature, so the key check is all there is
+        let public = AppState::new(Config {
+            public_file_urls: true,
+            ..config("reader:read-files", "")
+        });
+        assert_eq!(
+            status_for(&public, Method::GET, &signed, None).await,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+}
@@ -0,0 +1,139 @@
+/*
+ * URL canonicalization.
+ *
+ * The same video reaches us under many spellings: mobile and "www." hosts,
+ * music.youtube.com, share links carrying "utm_*" or "fbclid" trackers (and
+ * "si" on YouTube), fragments. canonicalize_url folds those into one form so requests for the
+ * same page coalesce and meta.json records a stable URL. It only rewrites the
+ * host and query; the path is left exactly as given.
+ */
+
+// Host prefixes that serve the same pages as the bare domain
+const MIRROR_HOST_PREFIXES: [&str; 3] = ["www.", "m.", "mobile."];
+
+// Query parameters that only track where a link was shared from
+const TRACKING_PARAMS: [&str; 15] = [
+    "fbclid",
+    "gclid",
+    "dclid",
+    "gbraid",
+    "wbraid",
+    "msclkid",
+    "mc_cid",
+    "mc_eid",
+    "igshid",
+    "igsh",
+    "ref_src",
+    "ref_url",
+    "share_id",
+    "is_from_webapp",
+    "sender_device",
+];
+
+// Trackers YouTube adds to share links; elsewhere these names can mean anything
+const YOUTUBE_TRACKING_PARAMS: [&str; 3] = ["si", "feature", "pp"];
+const YOUTUBE_HOSTS: [&str; 2] = ["youtube.com", "youtu.be"];
+
+/// Canonical form of `url`: lowercase host without mirror prefixes, tracking
+/// parameters and fragment removed. Anything that doesn't look like an
+/// absolute URL comes back trimmed but otherwise unchanged.
+pub fn canonicalize_url(url: &str) -> String {
+    let url = url.trim();
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return url.to_string();
+    };
+
+    // Fragments never reach the server
+    let rest = rest.split('#').next().unwrap_or_default();
+    let (authority, path_and_query) = match rest.find(['/', '?']) {
+        Some(index) => rest.split_at(index),
+        None => (rest, ""),
+    };
+    let (path, query) = match path_and_query.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (path_and_query, None),
+    };
+
+    let host = canonical_host(authority);
+    let youtube = YOUTUBE_HOSTS.contains(&host.as_str());
+    let mut canonical = format!("{}://{}{}", scheme.to_ascii_lowercase(), host, path);
+    let kept: Vec<_> = query
+        .unwrap_or_default()
+        .split('&')
+        .filter(|param| !param.is_empty() && !is_tracking_param(param, youtube))
+        .collect();
+    if !kept.is_empty() {
+        canonical.push('?');
+        canonical.push_str(&kept.join("&"));
+    }
+    canonical
+}
+
+fn canonical_host(authority: &str) -> String {
+    let mut host = authority.to_ascii_lowercase();
+    while let Some(prefix) = MIRROR_HOST_PREFIXES
+        .iter()
+        .find(|prefix| host.starts_with(*prefix))
+    {
+        host.drain(..prefix.len());
+    }
+    // YouTube Music pages are ordinary YouTube videos
+    if host == "music.youtube.com" {
+        host = "youtube.com".to_string();
+    }
+    host
+}
+
+fn is_tracking_param(param: &str, youtube: bool) -> bool {
+    let name = param.split('=').next().unwrap_or_default();
+    name.starts_with("utm_")
+        || TRACKING_PARAMS.contains(&name)
+        || (youtube && YOUTUBE_TRACKING_PARAMS.contains(&name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_trackers_and_mirror_hosts() {
+        assert_eq!(
+            canonicalize_url("https://m.youtube.com/watch?v=dQw4w9WgXcQ&si=abc123&feature=share"),
+            "https://youtube.com/watch?v=dQw4w9WgXcQ"
+        );
+        assert_eq!(
+            canonicalize_url("https://music.youtube.com/watch?v=dQw4w9WgXcQ&list=RDAMVM"),
+            "https://youtube.com/watch?v=dQw4w9WgXcQ&list=RDAMVM"
+        );
+        assert_eq!(
+            canonicalize_url("HTTPS://WWW.Vimeo.com/123456789?utm_source=newsletter#comments"),
+            "https://vimeo.com/123456789"
+        );
+        assert_eq!(
+            canonicalize_url(" https://www.instagram.com/reel/AbCdEfGhIjK/?igsh=MXd4 "),
+            "https://instagram.com/reel/AbCdEfGhIjK/"
+        );
+    }
+
+    #[test]
+    fn keeps_paths_and_meaningful_parameters() {
+        assert_eq!(
+            canonicalize_url("https://www.bilibili.com/video/BV1xx411c7mD?p=2&spm_id=1"),
+            "https://bilibili.com/video/BV1xx411c7mD?p=2&spm_id=1"
+        );
+        assert_eq!(
+            canonicalize_url("https://youtu.be/dQw4w9WgXcQ?t=42"),
+            "https://youtu.be/dQw4w9WgXcQ?t=42"
+        );
+        assert_eq!(
+            canonicalize_url("https://youtu.be/dQw4w9WgXcQ?pp=ygU&si=abc123"),
+            "https://youtu.be/dQw4w9WgXcQ"
+        );
+        // Only YouTube's share links use these names as trackers
+        assert_eq!(
+            canonicalize_url("https://example.com/gallery?pp=3&feature=slideshow"),
+            "https://example.com/gallery?pp=3&feature=slideshow"
+        );
+        assert_eq!(canonicalize_url("not a url"), "not a url");
+    }
+}
@@ -1,6 +1,10 @@
 use crate::config::Config;
+use crate::handlers::cache::list_cache;
+use crate::handlers::inflight::InFlightDownloads;
+use crate::handlers::options::is_media_extension;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::{Duration, SystemTime};
 use tokio::time::interval;
 use tracing::{debug, error, info, warn};
@@ -9,9 +13,18 @@ use tracing::{debug, error, info, warn};
  * Cleanup utility for removing old downloaded files.
  *
  * Scans the download directory and removes files older than the configured
- * cleanup_after_minutes setting. Uses job ID directories to track downloads.
+ * cleanup_after_minutes setting (0 keeps them forever). Uses job ID directories
+ * to track downloads; pinned directories never expire.
+ *
+ * Also keeps the cache within its disk budget (MAX_CACHE_BYTES, MIN_FREE_BYTES)
+ * by evicting the least recently accessed videos first.
  */
 
+/// Marker file that exempts a video directory from expiry and eviction.
+pub(crate) const PIN_MARKER: &str = ".pinned";
+// How often temp files are scrubbed when downloads never expire
+const NO_EXPIRY_CHECK_MINUTES: u64 = 15;
+
 // Custom error type for cleanup operations
 #[derive(Debug)]
 pub enum CleanupError {
@@ -19,6 +32,7 @@ pub enum CleanupError {
     TimeError(std::time::SystemTimeError),
     DirectoryNotFound,
     InvalidConfiguration,
+    OverBudget,
 }
 
 impl std::fmt::Display for CleanupError {
@@ -30,6 +44,12 @@ impl std::fmt::Display for CleanupError {
                 write!(f, "Download directory not found or could not be created")
             }
             CleanupError::InvalidConfiguration => write!(f, "Invalid cleanup configuration"),
+            CleanupError::OverBudget => {
+                write!(
+                    f,
+                    "Not enough disk space, even after evicting every idle video"
+                )
+            }
         }
     }
 }
@@ -48,15 +68,12 @@ impl From<std::time::SystemTimeError> for CleanupError {
     }
 }
 
-// Removes all files older than the configured cleanup time
-pub fn cleanup_old_files() -> Result<usize, CleanupError> {
-    let config = Config::from_env();
-
-    // Validate configuration
-    if config.cleanup_after_minutes == 0 {
-        return Err(CleanupError::InvalidConfiguration);
-    }
-
+// Removes all files older than the configured cleanup time.
+// Videos being downloaded are skipped, like in enforce_disk_budget.
+pub fn cleanup_old_files(
+    config: &Config,
+    in_flight: &InFlightDownloads,
+) -> Result<usize, CleanupError> {
     let download_dir = PathBuf::from(&config.download_dir);
 
     if !download_dir.exists() {
@@ -64,12 +81,16 @@ pub fn cleanup_old_files() -> Result<usize, CleanupError> {
         std::fs::create_dir_all(&download_dir).map_err(|_| CleanupError::DirectoryNotFound)?;
     }
 
-    // Calculate cutoff time (files older than this will be removed)
-    let now = SystemTime::now();
-    let cutoff_duration = Duration::from_secs(config.cleanup_after_minutes * 60);
-    let cutoff_time = now
-        .checked_sub(cutoff_duration)
-        .ok_or(CleanupError::InvalidConfiguration)?;
+    // Calculate cutoff time (files older than this will be removed).
+    // 0 keeps downloads forever, but temp files are still scrubbed below.
+    let cutoff_time = match config.cleanup_after_minutes {
+        0 => None,
+        minutes => Some(
+            SystemTime::now()
+                .checked_sub(Duration::from_secs(minutes * 60))
+                .ok_or(CleanupError::InvalidConfiguration)?,
+        ),
+    };
 
     let mut removed_count = 0;
 
@@ -90,8 +111,20 @@ pub fn cleanup_old_files() -> Result<usize, CleanupError> {
         if path.is_dir() {
             // Check if this is a video directory (video_id directory)
             if is_video_directory(&path) {
+                let claim = path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .and_then(|video_id| in_flight.try_acquire(video_id));
+                let Some(_claim) = claim else {
+                    debug!(
+                        path = %path.display(),
+                        "Skipping cleanup of a video that is downloading"
+                    );
+                    continue;
+                };
                 removed_count += scrub_incomplete_in_dir(&path);
-                if remove_if_old(&path, cutoff_time).is_ok() {
+                let expiring = cutoff_time.filter(|_| !is_pinned(&path));
+                if expiring.is_some_and(|cutoff| remove_if_old(&path, cutoff).is_ok()) {
                     info!(path = %path.display(), "Removed expired download");
                     removed_count += 1;
                 }
@@ -116,18 +149,126 @@ pub fn cleanup_old_files() -> Result<usize, CleanupError> {
     Ok(removed_count)
 }
 
-// Removes a file or directory if it's older than the cutoff time
-fn remove_if_old(path: &Path, cutoff_time: SystemTime) -> Result<(), CleanupError> {
-    // For video directories, check the .last_accessed marker file
-    let access_marker = path.join(".last_accessed");
-    let access_time = if access_marker.exists() {
-        // Use the modification time of the access marker
-        fs::metadata(&access_marker)?.modified()?
+/*
+ * Evicts least recently accessed videos until the cache fits MAX_CACHE_BYTES
+ * and the volume has MIN_FREE_BYTES free. Videos being downloaded are skipped,
+ * and each eviction holds that video's in-flight claim so no download publishes
+ * into a directory while it is being removed.
+ * Returns the number of videos evicted, or OverBudget when that wasn't enough.
+ */
+pub fn enforce_disk_budget(
+    config: &Config,
+    in_flight: &InFlightDownloads,
+) -> Result<usize, CleanupError> {
+    if config.max_cache_bytes == 0 && config.min_free_bytes == 0 {
+        return Ok(0);
+    }
+
+    let download_dir = PathBuf::from(&config.download_dir);
+    let videos = list_cache(&download_dir);
+    let mut cache_bytes: u64 = videos.iter().map(|video| video.size_bytes).sum();
+    let mut free_bytes = if config.min_free_bytes > 0 {
+        available_space(&download_dir)
+            .inspect_err(|e| {
+                warn!(error = %e, "Could not read free disk space, MIN_FREE_BYTES not enforced");
+            })
+            .ok()
     } else {
-        // Fall back to directory creation time for directories without access markers
-        fs::metadata(path)?.modified()?
+        None
+    };
+    let over_budget = |cache_bytes: u64, free_bytes: Option<u64>| {
+        (config.max_cache_bytes > 0 && cache_bytes > config.max_cache_bytes)
+            || free_bytes.is_some_and(|free| free < config.min_free_bytes)
     };
 
+    let mut evicted = 0;
+    // list_cache is most recently used first
+    for video in videos.iter().rev() {
+        if !over_budget(cache_bytes, free_bytes) {
+            break;
+        }
+        if video.pinned {
+            continue;
+        }
+        let Some(_claim) = in_flight.try_acquire(&video.video_id) else {
+            debug!(video = %video.video_id, "Skipping eviction of a video that is downloading");
+            continue;
+        };
+
+        let path = download_dir.join(&video.video_id);
+        match fs::remove_dir_all(&path) {
+            Ok(_) => {
+                info!(
+                    path = %path.display(),
+                    freed_bytes = video.size_bytes,
+                    "Evicted least recently used video"
+                );
+                cache_bytes = cache_bytes.saturating_sub(video.size_bytes);
+                free_bytes = free_bytes.map(|free| free.saturating_add(video.size_bytes));
+                evicted += 1;
+            }
+            Err(e) => {
+                error!(path = %path.display(), error = %e, "Failed to evict video");
+            }
+        }
+    }
+
+    if over_budget(cache_bytes, free_bytes) {
+        warn!(
+            cache_bytes,
+            free_bytes = free_bytes.unwrap_or_default(),
+            "Cache is still over its disk budget"
+        );
+        return Err(CleanupError::OverBudget);
+    }
+    Ok(evicted)
+}
+
+/// enforce_disk_budget on the blocking pool; walking the cache would stall the runtime.
+pub async fn enforce_disk_budget_blocking(
+    config: Arc<Config>,
+    in_flight: InFlightDownloads,
+) -> Result<usize, CleanupError> {
+    tokio::task::spawn_blocking(move || enforce_disk_budget(&config, &in_flight))
+        .await
+        .map_err(|e| CleanupError::IoError(std::io::Error::other(e)))?
+}
+
+/// Bytes free for unprivileged writes on the filesystem holding `path`.
+#[cfg(unix)]
+fn available_space(path: &Path) -> std::io::Result<u64> {
+    let stats = rustix::fs::statvfs(path)?;
+    Ok(stats.f_bavail.saturating_mul(stats.f_frsize))
+}
+
+#[cfg(not(unix))]
+fn available_space(_path: &Path) -> std::io::Result<u64> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "free space is only available on Unix",
+    ))
+}
+
+/// True when a video directory has been pinned through the cache API.
+pub(crate) fn is_pinned(path: &Path) -> bool {
+    path.join(PIN_MARKER).is_file()
+}
+
+/// When a video directory was last used: its .last_accessed marker, or the
+/// directory's own modification time for entries that were never served.
+pub(crate) fn last_access_time(path: &Path) -> std::io::Result<SystemTime> {
+    let access_marker = path.join(".last_accessed");
+    if access_marker.exists() {
+        fs::metadata(&access_marker)?.modified()
+    } else {
+        fs::metadata(path)?.modified()
+    }
+}
+
+// Removes a file or directory if it's older than the cutoff time
+fn remove_if_old(path: &Path, cutoff_time: SystemTime) -> Result<(), CleanupError> {
+    let access_time = last_access_time(path)?;
+
     // Remove if file is older than cutoff time
     if access_time < cutoff_time {
         if path.is_dir() {
@@ -153,8 +294,9 @@ fn is_temporary_file(path: &Path) -> bool {
         || name.starts_with("temp_video")
         || name.ends_with(".tmp")
         || name.ends_with(".temp")
-        // In-progress downloads published atomically as ".Title.<job>.tmp.mp4"
-        || (name.starts_with('.') && name.ends_with(".tmp.mp4"))
+        // In-progress downloads published atomically as ".Title.<job>.tmp.mp4",
+        // plus the per-format pieces and .part files yt-dlp writes next to them
+        || (name.starts_with('.') && name.contains(".tmp."))
 }
 
 /// Removes orphaned in-progress downloads inside a video cache directory.
@@ -181,10 +323,10 @@ fn scrub_incomplete_in_dir(dir: &Path) -> usize {
     removed
 }
 
-/// True when a directory looks like one of ours: a published mp4, an access
+/// True when a directory looks like one of ours: a published download, an access
 /// marker, and/or an in-progress temp download. Skips unrelated folders that
 /// happen to sit under DOWNLOAD_DIR (the old "anything except cache/" rule).
-fn is_video_directory(path: &Path) -> bool {
+pub(crate) fn is_video_directory(path: &Path) -> bool {
     let Ok(entries) = fs::read_dir(path) else {
         return false;
     };
@@ -201,11 +343,15 @@ fn is_video_directory(path: &Path) -> bool {
         if name == ".last_accessed" {
             return true;
         }
-        if name.starts_with('.') && name.ends_with(".tmp.mp4") {
+        if name.starts_with('.') && name.contains(".tmp.") {
             return true;
         }
-        // Published cache entry: "Title.mp4" (not a hidden temp)
-        if child.extension().is_some_and(|ext| ext == "mp4") && !name.starts_with('.') {
+        // Published cache entry: "Title.mp4", "Title.audio.mp3", ... (not a hidden temp)
+        let published = child
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(is_media_extension);
+        if published && !name.starts_with('.') {
             return true;
         }
     }
@@ -222,31 +368,37 @@ fn log_cleanup_result(removed_count: usize) {
 }
 
 // Runs cleanup in a background task with periodic execution
-pub async fn start_cleanup_scheduler() {
-    let config = Config::from_env();
-
-    // Validate configuration
-    if config.cleanup_after_minutes == 0 {
-        error!("cleanup_after_minutes cannot be 0, scheduler disabled");
-        return;
-    }
-
+pub async fn start_cleanup_scheduler(config: Arc<Config>, in_flight: InFlightDownloads) {
     // Check more often than the expiry window so files don't linger up to
     // another full TTL after they become eligible. Floor at 1 minute.
-    let check_every_minutes = (config.cleanup_after_minutes / 4).max(1);
+    // Without expiry there are still temp files and the disk budget to look after.
+    let check_every_minutes = match config.cleanup_after_minutes {
+        0 => NO_EXPIRY_CHECK_MINUTES,
+        minutes => (minutes / 4).max(1),
+    };
     let cleanup_interval = Duration::from_secs(check_every_minutes * 60);
     let mut interval_timer = interval(cleanup_interval);
 
-    info!(
-        expire_after_min = config.cleanup_after_minutes,
-        check_every_min = check_every_minutes,
-        "Cleanup scheduler started"
-    );
+    if config.cleanup_after_minutes == 0 {
+        info!(
+            check_every_min = check_every_minutes,
+            "Cleanup scheduler started, downloads never expire"
+        );
+    } else {
+        info!(
+            expire_after_min = config.cleanup_after_minutes,
+            check_every_min = check_every_minutes,
+            "Cleanup scheduler started"
+        );
+    }
 
     // Run initial cleanup
-    if let Err(e) = cleanup_old_files() {
+    if let Err(e) = cleanup_old_files(&config, &in_flight) {
         error!(error = %e, "Initial cleanup failed");
     }
+    if let Err(e) = enforce_disk_budget_blocking(config.clone(), in_flight.clone()).await {
+        warn!(error = %e, "Initial disk budget check failed");
+    }
 
     // Wait for first interval, then start the loop
     interval_timer.tick().await;
@@ -256,33 +408,20 @@ pub async fn start_cleanup_scheduler() {
         interval_timer.tick().await;
         debug!("Running scheduled cleanup");
 
-        if let Err(e) = cleanup_old_files() {
+        if let Err(e) = cleanup_old_files(&config, &in_flight) {
             error!(error = %e, "Scheduled cleanup failed");
         }
+        if let Err(e) = enforce_disk_budget_blocking(config.clone(), in_flight.clone()).await {
+            warn!(error = %e, "Scheduled disk budget check failed");
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::utils::test_support::TestDirectory;
     use std::time::UNIX_EPOCH;
-    use uuid::Uuid;
-
-    struct TestDirectory(PathBuf);
-
-    impl TestDirectory {
-        fn new() -> Self {
-            let path = std::env::temp_dir().join(format!("snatchr-test-{}", Uuid::new_v4()));
-            fs::create_dir_all(&path).expect("test directory should be created");
-            Self(path)
-        }
-    }
-
-    impl Drop for TestDirectory {
-        fn drop(&mut self) {
-            let _ = fs::remove_dir_all(&self.0);
-        }
-    }
 
     #[test]
     fn recognizes_temporary_download_files() {
@@ -293,6 +432,9 @@ mod tests {
         assert!(is_temporary_file(&PathBuf::from(
             ".Some_Title.abcd1234.tmp.mp4"
         )));
+        assert!(is_temporary_file(&PathBuf::from(
+            ".Some_Title.abcd1234.tmp.f251.webm.part"
+        )));
         assert!(!is_temporary_file(&PathBuf::from("finished.mp4")));
     }
 
@@ -314,6 +456,11 @@ mod tests {
         fs::write(with_mp4.join("clip.mp4"), b"video").unwrap();
         assert!(is_video_directory(&with_mp4));
 
+        let with_audio = test_dir.0.join("audio-id");
+        fs::create_dir(&with_audio).unwrap();
+        fs::write(with_audio.join("clip.audio.flac"), b"audio").unwrap();
+        assert!(is_video_directory(&with_audio));
+
         let with_marker = test_dir.0.join("accessed-id");
         fs::create_dir(&with_marker).unwrap();
         fs::write(with_marker.join(".last_accessed"), b"").unwrap();
@@ -350,6 +497,121 @@ mod tests {
         assert!(video_dir.exists());
     }
 
+    fn cached_video(root: &Path, video_id: &str, bytes: usize, accessed_secs_ago: u64) {
+        let dir = root.join(video_id);
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("Title.mp4"), vec![0_u8; bytes]).unwrap();
+        let marker = fs::File::create(dir.join(".last_accessed")).unwrap();
+        marker
+            .set_modified(SystemTime::now() - Duration::from_secs(accessed_secs_ago))
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn evicts_least_recently_used_videos_until_under_quota() {
+        let test_dir = TestDirectory::new();
+        cached_video(&test_dir.0, "oldest", 4096, 3000);
+        cached_video(&test_dir.0, "downloading", 4096, 2000);
+        cached_video(&test_dir.0, "older", 4096, 1000);
+        cached_video(&test_dir.0, "newest", 4096, 0);
+        let config = Config {
+            download_dir: test_dir.0.to_string_lossy().into_owned(),
+            max_cache_bytes: 9000,
+            ..Config::default()
+        };
+        let in_flight = InFlightDownloads::default();
+        let _running = in_flight.acquire("downloading").await;
+
+        let evicted = enforce_disk_budget(&config, &in_flight).unwrap();
+
+        assert_eq!(evicted, 2);
+        assert!(!test_dir.0.join("oldest").exists());
+        assert!(test_dir.0.join("downloading").exists());
+        assert!(!test_dir.0.join("older").exists());
+        assert!(test_dir.0.join("newest").exists());
+    }
+
+    #[tokio::test]
+    async fn reports_when_busy_videos_keep_the_cache_over_quota() {
+        let test_dir = TestDirectory::new();
+        cached_video(&test_dir.0, "downloading", 4096, 0);
+        let config = Config {
+            download_dir: test_dir.0.to_string_lossy().into_owned(),
+            max_cache_bytes: 1024,
+            ..Config::default()
+        };
+        let in_flight = InFlightDownloads::default();
+        let _running = in_flight.acquire("downloading").await;
+
+        let result = enforce_disk_budget(&config, &in_flight);
+
+        assert!(matches!(result, Err(CleanupError::OverBudget)));
+        assert!(test_dir.0.join("downloading").exists());
+    }
+
+    #[test]
+    fn zero_minutes_keeps_downloads_but_scrubs_temp_files() {
+        let test_dir = TestDirectory::new();
+        cached_video(&test_dir.0, "ancient", 2048, 365 * 24 * 60 * 60);
+        let orphan = test_dir.0.join("ancient").join(".Title.abcd1234.tmp.mp4");
+        fs::write(&orphan, b"partial").unwrap();
+        let config = Config {
+            download_dir: test_dir.0.to_string_lossy().into_owned(),
+            cleanup_after_minutes: 0,
+            ..Config::default()
+        };
+
+        let removed = cleanup_old_files(&config, &InFlightDownloads::default()).unwrap();
+
+        assert_eq!(removed, 1);
+        assert!(!orphan.exists());
+        assert!(test_dir.0.join("ancient").join("Title.mp4").exists());
+    }
+
+    #[tokio::test]
+    async fn skips_videos_that_are_downloading() {
+        let test_dir = TestDirectory::new();
+        cached_video(&test_dir.0, "downloading", 2048, 24 * 60 * 60);
+        let partial = test_dir
+            .0
+            .join("downloading")
+            .join(".Title.abcd1234.tmp.mp4");
+        fs::write(&partial, b"partial").unwrap();
+        let config = Config {
+            download_dir: test_dir.0.to_string_lossy().into_owned(),
+            cleanup_after_minutes: 60,
+            ..Config::default()
+        };
+        let in_flight = InFlightDownloads::default();
+        let _running = in_flight.acquire("downloading").await;
+
+        let removed = cleanup_old_files(&config, &in_flight).unwrap();
+
+        assert_eq!(removed, 0);
+        assert!(partial.exists());
+        assert!(test_dir.0.join("downloading").join("Title.mp4").exists());
+    }
+
+    #[tokio::test]
+    async fn pinned_videos_neither_expire_nor_get_evicted() {
+        let test_dir = TestDirectory::new();
+        cached_video(&test_dir.0, "pinned", 4096, 24 * 60 * 60);
+        cached_video(&test_dir.0, "expired", 4096, 24 * 60 * 60);
+        fs::write(test_dir.0.join("pinned").join(PIN_MARKER), b"").unwrap();
+        let config = Config {
+            download_dir: test_dir.0.to_string_lossy().into_owned(),
+            max_cache_bytes: 1024,
+            ..Config::default()
+        };
+
+        cleanup_old_files(&config, &InFlightDownloads::default()).unwrap();
+        let budget = enforce_disk_budget(&config, &InFlightDownloads::default());
+
+        assert!(test_dir.0.join("pinned").exists());
+        assert!(!test_dir.0.join("expired").exists());
+        assert!(matches!(budget, Err(CleanupError::OverBudget)));
+    }
+
     #[test]
     fn cleanup_errors_have_actionable_messages() {
         assert_eq!(
@@ -13,6 +13,7 @@ use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
 use tracing_subscriber::registry::LookupSpan;
 
 use crate::config::Config;
+use crate::utils::auth::is_auth_configured;
 
 /*
  * Console output for Snatchr.
@@ -234,16 +235,21 @@ pub fn print_banner(config: &Config) {
             paint(AMBER, "disabled (API only)")
         },
     );
+    row(
+        "Access",
+        if is_auth_configured(config) {
+            paint(GREEN, "API key required")
+        } else {
+            paint(AMBER, "open (set API_KEYS to require a key)")
+        },
+    );
+    let retention = match config.cleanup_after_minutes {
+        0 => "· kept forever".to_string(),
+        minutes => format!("· kept for {minutes} min"),
+    };
     row(
         "Downloads",
-        format!(
-            "{} {}",
-            config.download_dir,
-            paint(
-                GRAY,
-                &format!("· kept for {} min", config.cleanup_after_minutes)
-            ),
-        ),
+        format!("{} {}", config.download_dir, paint(GRAY, &retention)),
     );
     row(
         "Limits",
@@ -254,6 +260,17 @@ pub fn print_banner(config: &Config) {
             config.timeout_seconds,
         ),
     );
+    if config.rate_limit_per_minute > 0 {
+        row(
+            "Rate limit",
+            format!(
+                "{}/min per client {} burst of {}",
+                config.rate_limit_per_minute,
+                paint(GRAY, "·"),
+                config.rate_limit_burst.max(1),
+            ),
+        );
+    }
     row(
         "Video",
         format!(
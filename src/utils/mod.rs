@@ -1,9 +1,17 @@
 /*
  * Module declaration for the utils.
  *
- * Contains cleanup, console logging, yt-dlp updates, and URL→cache-id helpers.
+ * Contains API key authentication, URL canonicalization, cleanup, console
+ * logging, signed file URLs, submitted URL checks, yt-dlp updates, and
+ * URL→cache-id helpers, plus fixtures shared by the unit tests.
  */
+pub mod auth;
+pub mod canonical_url;
 pub mod cleanup;
 pub mod logger;
+pub mod signed_url;
+#[cfg(test)]
+pub mod test_support;
+pub mod url_policy;
 pub mod video_id;
 pub mod ytdlp_update;
@@ -0,0 +1,166 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::Config;
+
+/*
+ * Signed, expiring /files URLs.
+ *
+ * File URLs are otherwise guessable from the video ID and title, and work
+ * forever. Unless PUBLIC_FILE_URLS is set, every file and thumbnail URL we hand
+ * out carries "expires" (a Unix timestamp, FILE_URL_TTL_SECONDS ahead) and
+ * "signature", an HMAC-SHA256 over the video ID, file name and expiry keyed by
+ * FILE_URL_SECRET. Changing the secret, or restarting without one set, voids
+ * every URL handed out before.
+ */
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Why a file request was refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureError {
+    Missing,
+    Invalid,
+    Expired,
+}
+
+/// The query string to append to a file URL, or None when URLs are public.
+pub fn signed_query(config: &Config, video_id: &str, file_name: &str) -> Option<String> {
+    if config.public_file_urls {
+        return None;
+    }
+    let expires = unix_now().saturating_add(config.file_url_ttl_seconds);
+    let signature = hex::encode(
+        mac(config, video_id, file_name, expires)
+            .finalize()
+            .into_bytes(),
+    );
+    Some(format!("expires={expires}&signature={signature}"))
+}
+
+/// Checks the "expires" and "signature" query parameters of a file request.
+pub fn verify(
+    config: &Config,
+    video_id: &str,
+    file_name: &str,
+    params: &HashMap<String, String>,
+) -> Result<(), SignatureError> {
+    if config.public_file_urls {
+        return Ok(());
+    }
+    let (Some(expires), Some(signature)) = (params.get("expires"), params.get("signature")) else {
+        return Err(SignatureError::Missing);
+    };
+    let expires: u64 = expires.parse().map_err(|_| SignatureError::Invalid)?;
+    let signature = hex::decode(signature).map_err(|_| SignatureError::Invalid)?;
+
+    // Check the signature first so a forged expiry can't be told apart from a stale one
+    mac(config, video_id, file_name, expires)
+        .verify_slice(&signature)
+        .map_err(|_| SignatureError::Invalid)?;
+    if expires < unix_now() {
+        return Err(SignatureError::Expired);
+    }
+    Ok(())
+}
+
+fn mac(config: &Config, video_id: &str, file_name: &str, expires: u64) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(config.file_url_secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(format!("{video_id}/{file_name}:{expires}").as_bytes());
+    mac
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_support::query_params;
+
+    #[test]
+    fn accepts_its_own_signatures_for_the_same_file_only() {
+        let config = Config {
+            file_url_secret: "test-secret".to_string(),
+            ..Config::default()
+        };
+        let query = signed_query(&config, "youtube-dQw4w9WgXcQ", "Never Gonna.mp4").unwrap();
+        let other_secret = Config {
+            file_url_secret: "other-secret".to_string(),
+            ..Config::default()
+        };
+
+        assert_eq!(
+            verify(
+                &config,
+                "youtube-dQw4w9WgXcQ",
+                "Never Gonna.mp4",
+                &query_params(&query)
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            verify(
+                &config,
+                "youtube-dQw4w9WgXcQ",
+                "Other.mp4",
+                &query_params(&query)
+            ),
+            Err(SignatureError::Invalid)
+        );
+        assert_eq!(
+            verify(
+                &other_secret,
+                "youtube-dQw4w9WgXcQ",
+                "Never Gonna.mp4",
+                &query_params(&query)
+            ),
+            Err(SignatureError::Invalid)
+        );
+        assert_eq!(
+            verify(
+                &config,
+                "youtube-dQw4w9WgXcQ",
+                "Never Gonna.mp4",
+                &HashMap::new()
+            ),
+            Err(SignatureError::Missing)
+        );
+    }
+
+    #[test]
+    fn rejects_expired_urls_and_skips_checks_when_public() {
+        let config = Config {
+            file_url_secret: "test-secret".to_string(),
+            ..Config::default()
+        };
+        let expires = unix_now() - 1;
+        let signature = hex::encode(
+            mac(&config, "vimeo-1", "clip.mp4", expires)
+                .finalize()
+                .into_bytes(),
+        );
+        let stale = query_params(&format!("expires={expires}&signature={signature}"));
+        let public = Config {
+            public_file_urls: true,
+            ..Config::default()
+        };
+
+        assert_eq!(
+            verify(&config, "vimeo-1", "clip.mp4", &stale),
+            Err(SignatureError::Expired)
+        );
+        assert!(signed_query(&public, "vimeo-1", "clip.mp4").is_none());
+        assert_eq!(
+            verify(&public, "vimeo-1", "clip.mp4", &HashMap::new()),
+            Ok(())
+        );
+    }
+}
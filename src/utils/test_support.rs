@@ -0,0 +1,55 @@
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/*
+ * Helpers shared by the unit tests: a scratch directory that cleans up after
+ * itself, and the query parsing axum's Query extractor would do.
+ */
+
+/// A fresh directory under the system temp dir, removed again when dropped.
+pub struct TestDirectory(pub PathBuf);
+
+impl TestDirectory {
+    pub fn new() -> Self {
+        let path = std::env::temp_dir().join(format!("snatchr-test-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&path).expect("test directory should be created");
+        Self(path)
+    }
+}
+
+impl Default for TestDirectory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Deref for TestDirectory {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TestDirectory {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TestDirectory {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// "name=value&..." pairs as a map, like the Query extractor hands them over.
+pub fn query_params(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+}
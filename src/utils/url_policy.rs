@@ -0,0 +1,260 @@
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::Duration;
+use tracing::debug;
+
+use crate::config::Config;
+
+/*
+ * Which submitted URLs we are willing to hand to yt-dlp.
+ *
+ * yt-dlp's generic extractor fetches whatever it is given. check_url turns away
+ * the obvious requests for cloud metadata endpoints, the admin panel on the LAN
+ * or the server itself before yt-dlp runs:
+ *
+ * 1. DENIED_DOMAINS refuses a host or any subdomain of it.
+ * 2. A non-empty ALLOWED_DOMAINS refuses everything it doesn't list.
+ * 3. Unless ALLOW_PRIVATE_ADDRESSES is set, the host is resolved and refused if
+ *    any address is loopback, private, link-local or otherwise not public.
+ *
+ * Hosts that don't resolve within RESOLVE_TIMEOUT are refused as well, since a
+ * DNS server that stalls for us could still answer yt-dlp with 127.0.0.1.
+ * This is a pre-flight filter, not complete SSRF protection. yt-dlp resolves
+ * the host again itself, so a DNS answer that changes in between (rebinding)
+ * gets through, and HTTP redirects it follows while fetching metadata are never
+ * checked. download_video checks the page URL in the metadata again before it
+ * downloads anything, but the metadata request has been made by then. Servers
+ * that must not reach internal hosts need an egress firewall as well.
+ */
+
+const RESOLVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Why a URL was refused, each with its own error code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UrlRejection {
+    DomainDenied { host: String },
+    DomainNotAllowed { host: String },
+    BlockedAddress { host: String },
+    UnresolvedHost { host: String },
+}
+
+impl UrlRejection {
+    /// Stable, machine-readable code for API responses.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::DomainDenied { .. } => "domain_denied",
+            Self::DomainNotAllowed { .. } => "domain_not_allowed",
+            Self::BlockedAddress { .. } => "blocked_address",
+            Self::UnresolvedHost { .. } => "unresolved_host",
+        }
+    }
+}
+
+impl fmt::Display for UrlRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DomainDenied { host } => write!(f, "Downloads from {host} are blocked"),
+            Self::DomainNotAllowed { host } => {
+                write!(f, "{host} is not on this server's list of allowed sites")
+            }
+            Self::BlockedAddress { host } => {
+                write!(f, "{host} points to a private or local network address")
+            }
+            Self::UnresolvedHost { host } => write!(f, "{host} could not be resolved"),
+        }
+    }
+}
+
+impl std::error::Error for UrlRejection {}
+
+/// Applies the domain lists and the private address check to a submitted URL.
+pub async fn check_url(config: &Config, url: &str) -> Result<(), UrlRejection> {
+    let Some((host, port)) = host_and_port(url) else {
+        // is_valid_video_url already turned away URLs without a host
+        return Ok(());
+    };
+
+    if domain_list(&config.denied_domains).any(|domain| matches_domain(&host, domain)) {
+        return Err(UrlRejection::DomainDenied { host });
+    }
+    let mut allowed = domain_list(&config.allowed_domains).peekable();
+    if allowed.peek().is_some() && !allowed.any(|domain| matches_domain(&host, domain)) {
+        return Err(UrlRejection::DomainNotAllowed { host });
+    }
+
+    if config.allow_private_addresses {
+        return Ok(());
+    }
+    let addresses = match host.parse::<IpAddr>() {
+        Ok(ip) => vec![ip],
+        Err(_) => match resolve(&host, port).await {
+            Some(addresses) => addresses,
+            None => {
+                debug!(host = %host, "Could not resolve host in time, refusing it");
+                return Err(UrlRejection::UnresolvedHost { host });
+            }
+        },
+    };
+    if addresses.iter().any(|ip| !is_public(*ip)) {
+        return Err(UrlRejection::BlockedAddress { host });
+    }
+    Ok(())
+}
+
+/// The host's addresses; None when the lookup fails, times out or comes back empty.
+async fn resolve(host: &str, port: u16) -> Option<Vec<IpAddr>> {
+    let lookup = tokio::net::lookup_host((host, port));
+    let resolved = tokio::time::timeout(RESOLVE_TIMEOUT, lookup)
+        .await
+        .ok()?
+        .ok()?;
+    let addresses: Vec<_> = resolved.map(|addr| addr.ip()).collect();
+    (!addresses.is_empty()).then_some(addresses)
+}
+
+// Lowercase host without brackets or a trailing dot, and the port to resolve it on
+fn host_and_port(url: &str) -> Option<(String, u16)> {
+    let uri = url.parse::<axum::http::Uri>().ok()?;
+    let host = uri
+        .host()?
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .trim_end_matches('.')
+        .to_ascii_lowercase();
+    let port = uri.port_u16().unwrap_or(match uri.scheme_str() {
+        Some("http") => 80,
+        _ => 443,
+    });
+    Some((host, port))
+}
+
+fn domain_list(list: &str) -> impl Iterator<Item = &str> {
+    list.split(',')
+        .map(|domain| domain.trim().trim_start_matches("*.").trim_end_matches('.'))
+        .filter(|domain| !domain.is_empty())
+}
+
+/// True when `host` is `domain` or one of its subdomains.
+fn matches_domain(host: &str, domain: &str) -> bool {
+    host.eq_ignore_ascii_case(domain)
+        || host.len().checked_sub(domain.len() + 1).is_some_and(|dot| {
+            host.as_bytes()[dot] == b'.' && host[dot + 1..].eq_ignore_ascii_case(domain)
+        })
+}
+
+/// False for loopback, private, link-local, shared, multicast and reserved ranges.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(mapped) => is_public_v4(mapped),
+            None => is_public_v6(ip),
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        || a == 0
+        // 100.64.0.0/10, carrier-grade NAT
+        || (a == 100 && (64..128).contains(&b))
+        // 192.0.0.0/24, protocol assignments
+        || (a == 192 && b == 0 && ip.octets()[2] == 0)
+        // 198.18.0.0/15, benchmarking
+        || (a == 198 && (18..20).contains(&b))
+        // 240.0.0.0/4, reserved
+        || a >= 240)
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // fc00::/7, unique local
+        || (first & 0xfe00) == 0xfc00
+        // fe80::/10, link-local
+        || (first & 0xffc0) == 0xfe80
+        // 2001:db8::/32, documentation
+        || (first == 0x2001 && ip.segments()[1] == 0x0db8)
+        // 64:ff9b::/96 translates to IPv4, which we can't see through
+        || (first == 0x0064 && ip.segments()[1] == 0xff9b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn blocks_private_and_local_addresses() {
+        let config = Config::default();
+        for url in [
+            "http://169.254.169.254/latest/meta-data/",
+            "http://127.0.0.1:3000/health",
+            "http://10.0.0.5/video.mp4",
+            "http://[::1]/",
+            "http://[::ffff:192.168.1.1]/",
+            "http://[fd12:3456::1]/",
+            "http://localhost/",
+        ] {
+            let result = check_url(&config, url).await;
+            assert_eq!(
+                result.map_err(|e| e.code()),
+                Err("blocked_address"),
+                "{url}"
+            );
+        }
+
+        assert_eq!(
+            check_url(&config, "https://93.184.215.14/watch").await,
+            Ok(())
+        );
+        assert_eq!(
+            check_url(&config, "https://no-such-host.invalid/watch")
+                .await
+                .map_err(|e| e.code()),
+            Err("unresolved_host")
+        );
+        let permissive = Config {
+            allow_private_addresses: true,
+            ..Config::default()
+        };
+        assert_eq!(
+            check_url(&permissive, "http://192.168.1.20/clip.mp4").await,
+            Ok(())
+        );
+    }
+
+    #[tokio::test]
+    async fn applies_deny_and_allow_lists_before_resolving() {
+        let config = Config {
+            allowed_domains: "youtube.com, *.vimeo.com".to_string(),
+            denied_domains: "music.youtube.com".to_string(),
+            ..Config::default()
+        };
+        let code = |result: Result<(), UrlRejection>| result.map_err(|e| e.code());
+
+        assert_eq!(
+            code(check_url(&config, "https://music.youtube.com/watch?v=x").await),
+            Err("domain_denied")
+        );
+        assert_eq!(
+            code(check_url(&config, "https://evil-youtube.com/watch?v=x").await),
+            Err("domain_not_allowed")
+        );
+        assert_eq!(
+            code(check_url(&config, "https://www.tiktok.com/@user/video/1").await),
+            Err("domain_not_allowed")
+        );
+        assert!(matches_domain("www.youtube.com", "youtube.com"));
+        assert!(matches_domain("player.VIMEO.com", "vimeo.com"));
+        assert!(!matches_domain("notyoutube.com", "youtube.com"));
+    }
+}
@@ -7,8 +7,44 @@ use std::sync::LazyLock;
  * Lets us serve a cached file without calling yt-dlp for metadata.
  * Patterns mirror the IDs yt-dlp typically uses for these hosts.
  * If extraction fails or disagrees, the normal metadata path still runs.
+ *
+ * Cache keys are namespaced by platform, "{platform}-{id}" (e.g.
+ * "youtube-dQw4w9WgXcQ"), because bare IDs collide across sites: a Vimeo and a
+ * Facebook video can share a numeric ID, and so can a TikTok and a tweet.
+ * After a metadata fetch the platform comes from yt-dlp's extractor, mapped
+ * onto the same names, so both paths agree on the key.
+ *
+ * Only URL shapes that carry the ID yt-dlp reports are matched. Reddit post
+ * pages, Rumble watch pages and SoundCloud track slugs name a video by
+ * something else, so those go through metadata (their v.redd.it, embed and
+ * /tracks/ forms are matched).
  */
 
+// yt-dlp extractor keys (lowercased) of the videos the URL patterns match, with
+// the platform name each is cached under. Matched exactly, since a prefix
+// would file "Kickstarter" under kick.
+const EXTRACTORS: [(&str, &str); 19] = [
+    ("youtube", "youtube"),
+    ("tiktok", "tiktok"),
+    ("vimeo", "vimeo"),
+    ("instagram", "instagram"),
+    ("twitter", "twitter"),
+    ("twitchclips", "twitch"),
+    ("twitchvod", "twitch"),
+    ("facebook", "facebook"),
+    ("facebookreel", "facebook"),
+    ("reddit", "reddit"),
+    ("dailymotion", "dailymotion"),
+    ("streamable", "streamable"),
+    ("soundcloud", "soundcloud"),
+    ("bilibili", "bilibili"),
+    ("rumble", "rumble"),
+    ("rumbleembed", "rumble"),
+    ("kick", "kick"),
+    ("kickvod", "kick"),
+    ("kickclip", "kick"),
+];
+
 static YOUTUBE_WATCH: LazyLock<Regex> = LazyLock::new(|| {
     Regex::new(r"(?i)(?:youtube\.com|youtube-nocookie\.com)/watch\?(?:[^#]*&)?v=([A-Za-z0-9_-]{11})").unwrap()
 });
@@ -38,32 +74,99 @@ static FACEBOOK_VIDEO: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r"(?i)facebook\.com/.+/videos/(\d+)").unwrap());
 static FACEBOOK_WATCH: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r"(?i)(?:facebook\.com/watch/?\?v=|fb\.watch/)(\d+)").unwrap());
+static REDDIT_VIDEO: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)v\.redd\.it/([A-Za-z0-9]+)").unwrap());
+static DAILYMOTION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)(?:dailymotion\.com/(?:embed/)?video/|dai\.ly/)([A-Za-z0-9]+)").unwrap()
+});
+static STREAMABLE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)streamable\.com/(?:[eo]/)?([A-Za-z0-9]+)").unwrap());
+// API and player URLs carry the numeric track ID, raw or percent-encoded
+static SOUNDCLOUD_TRACK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)soundcloud\.com(?:/|%2F)tracks(?:/|%2F)(\d+)").unwrap());
+static BILIBILI: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)bilibili\.com/video/(BV[0-9A-Za-z]{10})").unwrap());
+// Multi-part videos get a "_p{n}" suffix from yt-dlp, so leave those to metadata
+static BILIBILI_PART: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"[?&]p=\d").unwrap());
+static RUMBLE_EMBED: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)rumble\.com/embed/([0-9a-z]+)").unwrap());
+static KICK_VIDEO: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)kick\.com/video/([0-9a-f-]{36})").unwrap());
+static KICK_CLIP: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)kick\.com/[^/?#]+(?:/clips/|\?(?:[^#]*&)?clip=)(clip_[A-Za-z0-9]+)").unwrap()
+});
 
-/// Extracts a cache key (platform video id) from a URL, when the format is known.
+/// Extracts a cache key ("{platform}-{id}") from a URL, when the format is known.
 pub fn extract_cache_id(url: &str) -> Option<String> {
-    for re in [
-        &*YOUTUBE_WATCH,
-        &*YOUTUBE_SHORTS,
-        &*YOUTUBE_EMBED,
-        &*YOUTUBE_LIVE,
-        &*YOUTU_BE,
-        &*TIKTOK_VIDEO,
-        &*VIMEO,
-        &*INSTAGRAM,
-        &*TWITTER,
-        &*TWITCH_CLIP,
-        &*FACEBOOK_VIDEO,
-        &*FACEBOOK_WATCH,
+    for (platform, re) in [
+        ("youtube", &*YOUTUBE_WATCH),
+        ("youtube", &*YOUTUBE_SHORTS),
+        ("youtube", &*YOUTUBE_EMBED),
+        ("youtube", &*YOUTUBE_LIVE),
+        ("youtube", &*YOUTU_BE),
+        ("tiktok", &*TIKTOK_VIDEO),
+        ("vimeo", &*VIMEO),
+        ("instagram", &*INSTAGRAM),
+        ("twitter", &*TWITTER),
+        ("twitch", &*TWITCH_CLIP),
+        ("facebook", &*FACEBOOK_VIDEO),
+        ("facebook", &*FACEBOOK_WATCH),
+        ("reddit", &*REDDIT_VIDEO),
+        ("dailymotion", &*DAILYMOTION),
+        ("streamable", &*STREAMABLE),
+        ("soundcloud", &*SOUNDCLOUD_TRACK),
+        ("bilibili", &*BILIBILI),
+        ("rumble", &*RUMBLE_EMBED),
+        ("kick", &*KICK_VIDEO),
+        ("kick", &*KICK_CLIP),
     ] {
-        if let Some(caps) = re.captures(url) {
-            if let Some(id) = caps.get(1) {
-                return Some(id.as_str().to_string());
-            }
+        if platform == "bilibili" && BILIBILI_PART.is_match(url) {
+            continue;
+        }
+        if let Some(id) = re.captures(url).and_then(|caps| caps.get(1)) {
+            return Some(cache_key(platform, id.as_str()));
         }
     }
     None
 }
 
+/// Cache key for a video yt-dlp reported with `extractor` (its extractor_key) and `id`.
+pub fn cache_key_for(extractor: Option<&str>, id: &str) -> String {
+    cache_key(&platform_for_extractor(extractor), id)
+}
+
+/// Maps a yt-dlp extractor onto the platform names the URL patterns use.
+/// Unknown extractors keep their own (lowercased) name; a missing one is "generic".
+pub fn platform_for_extractor(extractor: Option<&str>) -> String {
+    let name: String = extractor
+        .unwrap_or_default()
+        .split(':')
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+
+    match EXTRACTORS.iter().find(|(key, _)| *key == name) {
+        Some((_, platform)) => platform.to_string(),
+        None if name.is_empty() => "generic".to_string(),
+        None => name,
+    }
+}
+
+// IDs from unknown extractors can contain anything; keep keys to one safe path segment
+fn cache_key(platform: &str, id: &str) -> String {
+    let id: String = id
+        .chars()
+        .map(|c| match c {
+            c if c.is_ascii_alphanumeric() || c == '-' || c == '_' => c,
+            _ => '_',
+        })
+        .collect();
+    format!("{platform}-{id}")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -72,27 +175,27 @@ mod tests {
     fn extracts_youtube_variants() {
         assert_eq!(
             extract_cache_id("https://www.youtube.com/watch?v=dQw4w9WgXcQ").as_deref(),
-            Some("dQw4w9WgXcQ")
+            Some("youtube-dQw4w9WgXcQ")
         );
         assert_eq!(
             extract_cache_id("https://youtube.com/watch?v=dQw4w9WgXcQ&t=30").as_deref(),
-            Some("dQw4w9WgXcQ")
+            Some("youtube-dQw4w9WgXcQ")
         );
         assert_eq!(
             extract_cache_id("https://youtu.be/dQw4w9WgXcQ").as_deref(),
-            Some("dQw4w9WgXcQ")
+            Some("youtube-dQw4w9WgXcQ")
         );
         assert_eq!(
             extract_cache_id("https://www.youtube.com/shorts/dQw4w9WgXcQ").as_deref(),
-            Some("dQw4w9WgXcQ")
+            Some("youtube-dQw4w9WgXcQ")
         );
         assert_eq!(
             extract_cache_id("https://www.youtube.com/embed/dQw4w9WgXcQ").as_deref(),
-            Some("dQw4w9WgXcQ")
+            Some("youtube-dQw4w9WgXcQ")
         );
         assert_eq!(
             extract_cache_id("https://m.youtube.com/watch?v=dQw4w9WgXcQ").as_deref(),
-            Some("dQw4w9WgXcQ")
+            Some("youtube-dQw4w9WgXcQ")
         );
     }
 
@@ -100,27 +203,105 @@ mod tests {
     fn extracts_other_supported_platforms() {
         assert_eq!(
             extract_cache_id("https://www.tiktok.com/@user/video/7123456789012345678").as_deref(),
-            Some("7123456789012345678")
+            Some("tiktok-7123456789012345678")
         );
         assert_eq!(
             extract_cache_id("https://vimeo.com/123456789").as_deref(),
-            Some("123456789")
+            Some("vimeo-123456789")
         );
         assert_eq!(
             extract_cache_id("https://www.instagram.com/reel/AbCdEfGhIjK/").as_deref(),
-            Some("AbCdEfGhIjK")
+            Some("instagram-AbCdEfGhIjK")
         );
         assert_eq!(
             extract_cache_id("https://x.com/someone/status/1234567890123456789").as_deref(),
-            Some("1234567890123456789")
+            Some("twitter-1234567890123456789")
         );
         assert_eq!(
             extract_cache_id("https://clips.twitch.tv/SomeClipSlug-abc").as_deref(),
-            Some("SomeClipSlug-abc")
+            Some("twitch-SomeClipSlug-abc")
         );
         assert_eq!(
             extract_cache_id("https://www.facebook.com/watch/?v=1234567890").as_deref(),
-            Some("1234567890")
+            Some("facebook-1234567890")
+        );
+    }
+
+    #[test]
+    fn extracts_newer_platforms() {
+        let cases = [
+            ("https://v.redd.it/zv89llsvexdz", "reddit-zv89llsvexdz"),
+            (
+                "https://www.dailymotion.com/video/x8abc12",
+                "dailymotion-x8abc12",
+            ),
+            ("https://dai.ly/x8abc12", "dailymotion-x8abc12"),
+            ("https://streamable.com/e/moo", "streamable-moo"),
+            (
+                "https://w.soundcloud.com/player/?url=https%3A//api.soundcloud.com/tracks%2F123456",
+                "soundcloud-123456",
+            ),
+            (
+                "https://www.bilibili.com/video/BV1xx411c7mD/",
+                "bilibili-BV1xx411c7mD",
+            ),
+            ("https://rumble.com/embed/v5abcd/", "rumble-v5abcd"),
+            (
+                "https://kick.com/video/7f1e3d4a-1b2c-4d5e-8f90-a1b2c3d4e5f6",
+                "kick-7f1e3d4a-1b2c-4d5e-8f90-a1b2c3d4e5f6",
+            ),
+            (
+                "https://kick.com/streamer?clip=clip_01HABCDEF",
+                "kick-clip_01HABCDEF",
+            ),
+        ];
+        for (url, key) in cases {
+            assert_eq!(extract_cache_id(url).as_deref(), Some(key), "{url}");
+        }
+
+        // Pages that name the video by something other than yt-dlp's ID
+        assert!(extract_cache_id("https://www.bilibili.com/video/BV1xx411c7mD?p=2").is_none());
+        assert!(extract_cache_id("https://soundcloud.com/artist/track-name").is_none());
+        assert!(extract_cache_id("https://rumble.com/v4abcd-some-title.html").is_none());
+    }
+
+    #[test]
+    fn same_numeric_id_on_different_platforms_gets_different_keys() {
+        let vimeo = extract_cache_id("https://vimeo.com/1234567890");
+        let facebook = extract_cache_id("https://www.facebook.com/watch/?v=1234567890");
+        assert_ne!(vimeo, facebook);
+    }
+
+    #[test]
+    fn extractor_keys_map_onto_url_platforms() {
+        assert_eq!(
+            cache_key_for(Some("Youtube"), "dQw4w9WgXcQ"),
+            extract_cache_id("https://youtu.be/dQw4w9WgXcQ").unwrap()
+        );
+        assert_eq!(
+            cache_key_for(Some("TwitchClips"), "SomeClipSlug-abc"),
+            "twitch-SomeClipSlug-abc"
+        );
+        assert_eq!(
+            cache_key_for(Some("TikTok"), "7123456789012345678"),
+            "tiktok-7123456789012345678"
+        );
+        assert_eq!(
+            cache_key_for(Some("DailyMotion"), "x8abc"),
+            "dailymotion-x8abc"
+        );
+        assert_eq!(
+            cache_key_for(Some("RumbleEmbed"), "v5abcd"),
+            "rumble-v5abcd"
+        );
+        assert_eq!(cache_key_for(Some("KickClip"), "clip_01H"), "kick-clip_01H");
+        assert_eq!(cache_key_for(Some("Mixcloud"), "abc"), "mixcloud-abc");
+        // Other sites that merely start with a platform's name keep their own
+        assert_eq!(cache_key_for(Some("Kickstarter"), "abc"), "kickstarter-abc");
+        assert_eq!(cache_key_for(Some("YoutubeTab"), "PL1"), "youtubetab-PL1");
+        assert_eq!(
+            cache_key_for(None, "../etc/passwd"),
+            "generic-___etc_passwd"
         );
     }
 
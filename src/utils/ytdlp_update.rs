@@ -6,6 +6,7 @@
  */
 
 use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Duration;
 
 use tracing::{info, warn};
@@ -15,14 +16,18 @@ use crate::config::Config;
 
 const UPDATE_EVERY: Duration = Duration::from_secs(24 * 60 * 60);
 
-fn yt_dlp_binary_path() -> PathBuf {
+pub(crate) fn yt_dlp_binary_path() -> PathBuf {
     let name = if cfg!(windows) { "yt-dlp.exe" } else { "yt-dlp" };
     PathBuf::from("libs").join(name)
 }
 
+pub(crate) fn ffmpeg_binary_path() -> PathBuf {
+    let name = if cfg!(windows) { "ffmpeg.exe" } else { "ffmpeg" };
+    PathBuf::from("libs").join(name)
+}
+
 /// Ensure binaries exist, then ask yt-dlp to self-update and log the result.
-async fn update_ytdlp() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let config = Config::from_env();
+async fn update_ytdlp(config: &Config) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let libraries_dir = PathBuf::from("libs");
     let output_dir = PathBuf::from(&config.download_dir);
 
@@ -60,10 +65,10 @@ async fn update_ytdlp() -> Result<(), Box<dyn std::error::Error + Send + Sync>>
 }
 
 /// Runs an update immediately, then every 24 hours.
-pub async fn start_ytdlp_update_scheduler() {
+pub async fn start_ytdlp_update_scheduler(config: Arc<Config>) {
     info!("yt-dlp update scheduler started");
 
-    if let Err(e) = update_ytdlp().await {
+    if let Err(e) = update_ytdlp(&config).await {
         warn!(error = %e, "yt-dlp update check failed (boot)");
     }
 
@@ -75,7 +80,7 @@ pub async fn start_ytdlp_update_scheduler() {
         interval_timer.tick().await;
         info!("Running scheduled yt-dlp update check");
 
-        if let Err(e) = update_ytdlp().await {
+        if let Err(e) = update_ytdlp(&config).await {
             warn!(error = %e, "yt-dlp update check failed");
         }
     }